@@ -0,0 +1,94 @@
+#[macro_use]
+extern crate criterion;
+extern crate rust16;
+
+use criterion::Criterion;
+use rust16::cpu::{Chip16Graphics, Memory};
+use rust16::opcode::separate_byte;
+use std::hint::black_box;
+
+fn test_memory() -> Memory {
+	let mut memory = Memory::new();
+	for dir in 0..0x10000 {
+		let byte = if dir % 5 == 0 { 0 } else { (dir * 37 + dir / 256) as u8 }; //Some transparent pixel pairs
+		memory.write_byte(dir, byte as i8);
+	}
+	memory
+}
+
+//What DRW used to cost: a memory read and the clipping checks for every pixel
+fn per_pixel_drw(graphics: &mut Chip16Graphics, mem: &mut Memory, spr_x: i16, spr_y: i16, spr_address: i16) -> bool {
+	let spritew = graphics.state.spritew as u32 as i32;
+	let spriteh = graphics.state.spriteh as u32 as i32;
+	let width = spritew * 2;
+	let spr_x = spr_x as i32;
+	let spr_y = spr_y as i32;
+	if spr_x > 319 || spr_y > 239 || spritew == 0 || spriteh == 0 || (spr_x + width) <= 0 || (spr_y + spriteh) <= 0 {
+		return false;
+	}
+	let mut hit = false;
+	for y in 0i32..spriteh {
+		let screen_y = spr_y + if graphics.state.vflip { spriteh - 1 - y } else { y };
+		if !(0..240).contains(&screen_y) {
+			continue;
+		}
+		for x in 0i32..width {
+			let screen_x = spr_x + if graphics.state.hflip { width - 1 - x } else { x };
+			if !(0..320).contains(&screen_x) {
+				continue;
+			}
+			let dir = (spr_address as u16).wrapping_add((y * spritew + x / 2) as u16);
+			let (hh_pixel, ll_pixel) = separate_byte(mem.read_byte(dir as usize));
+			let pixel = (if x % 2 == 0 { hh_pixel } else { ll_pixel }) as u8;
+			if pixel == 0 {
+				continue;
+			}
+			let screen_pos = (320 * screen_y + screen_x) as usize;
+			hit |= graphics.screen[screen_pos] != 0;
+			graphics.screen[screen_pos] = pixel;
+		}
+	}
+	hit
+}
+
+//A frame of 16x16 sprites covering the screen with a border hanging off every edge, in all four flips
+fn sprite_frame<F: FnMut(&mut Chip16Graphics, &mut Memory, i16, i16, i16) -> bool>(
+	graphics: &mut Chip16Graphics, memory: &mut Memory, mut drw: F) -> bool {
+	graphics.state.spritew = 8;
+	graphics.state.spriteh = 16;
+	let mut hit = false;
+	for y in -1..16 {
+		for x in -1..21 {
+			graphics.state.hflip = x % 2 == 0;
+			graphics.state.vflip = y % 2 == 0;
+			hit |= drw(graphics, memory, x as i16 * 16 - 8, y as i16 * 16 - 8, ((x + y * 24) * 128) as i16);
+		}
+	}
+	hit
+}
+
+fn drw_time(c: &mut Criterion) {
+	let mut memory = test_memory();
+	let mut graphics = Chip16Graphics::new();
+
+	c.bench_function("per pixel drw 16x16 sprites", |b| b.iter(|| {
+		sprite_frame(&mut graphics, black_box(&mut memory), per_pixel_drw)
+	}));
+	c.bench_function("span drw 16x16 sprites", |b| b.iter(|| {
+		sprite_frame(&mut graphics, black_box(&mut memory), |g, m, x, y, a| g.drw(m, x, y, a))
+	}));
+
+	graphics.state.spritew = 160;
+	graphics.state.spriteh = 240;
+	graphics.state.hflip = true;
+	graphics.state.vflip = false;
+	c.bench_function("per pixel drw full screen", |b| b.iter(|| {
+		per_pixel_drw(&mut graphics, black_box(&mut memory), 0, 0, 0x1000)
+	}));
+	c.bench_function("span drw full screen", |b| b.iter(|| {
+		graphics.drw(black_box(&mut memory), 0, 0, 0x1000)
+	}));
+}
+
+criterion_group!(benches, drw_time);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+#[macro_use]
+extern crate criterion;
+extern crate image;
+extern crate rust16;
+
+use criterion::Criterion;
+use rust16::cpu::Chip16Graphics;
+use std::hint::black_box;
+
+fn test_graphics() -> Chip16Graphics {
+	let mut graphics = Chip16Graphics::new();
+	for (i, pixel) in graphics.screen.iter_mut().enumerate() {
+		*pixel = ((i * 7 + i / 320) % 16) as u8;
+	}
+	graphics
+}
+
+//What every frame used to cost: a palette Vec, a scaled ImageBuffer and a put_pixel per scaled pixel
+fn legacy_frame(graphics: &Chip16Graphics, size: u32) -> image::RgbaImage {
+	let mut colours: Vec<[u8;4]> = Vec::with_capacity(16);
+	for p in graphics.palette.iter() {
+		colours.push([((p & 0xFF0000) >> 16) as u8, ((p & 0x00FF00) >> 8) as u8, (p & 0x0000FF) as u8, 255]);
+	}
+	let mut buffer_image = image::ImageBuffer::new(320 * size, 240 * size);
+	for (pixel, i) in graphics.screen.iter().zip(0..76800u32) {
+		let y = i / 320;
+		let x = i % 320;
+		for j in 0..size {
+			for k in 0..size {
+				buffer_image.put_pixel(size * x + j, size * y + k,
+					image::Rgba(if *pixel == 0 {
+						colours[graphics.state.bg as usize]
+					} else {
+						colours[*pixel as usize]
+				}));
+			}
+		}
+	}
+	buffer_image
+}
+
+fn frame_time(c: &mut Criterion) {
+	let graphics = test_graphics();
+	c.bench_function("legacy frame x2", |b| b.iter(|| legacy_frame(black_box(&graphics), 2)));
+	c.bench_function("legacy frame x4", |b| b.iter(|| legacy_frame(black_box(&graphics), 4)));
+
+	let mut frame = image::RgbaImage::new(320, 240);
+	c.bench_function("lookup table frame", |b| b.iter(|| black_box(&graphics).write_rgba(&mut frame)));
+}
+
+criterion_group!(benches, frame_time);
+criterion_main!(benches);
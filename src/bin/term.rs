@@ -0,0 +1,41 @@
+extern crate rust16;
+use rust16::cpu;
+use rust16::frontend;
+use std::env;
+use std::path::Path;
+use std::process;
+
+fn main() {
+	let mut args = env::args();
+	args.next();
+	let path = match args.next() {
+		Some(path) => path,
+		None => {
+			eprintln!("No ROM specified");
+			process::exit(2);
+		},
+	};
+	let columns: u32 = match args.next() {
+		Some(columns) => match columns.trim().parse() {
+			Ok(columns) => columns,
+			Err(_) => {
+				eprintln!("The number of columns must be a number");
+				process::exit(2);
+			},
+		},
+		None => 80,
+	};
+	let rows = columns * 3 / 8; //Every row holds two square-ish pixels, this keeps 4:3
+	let mut cpu = match cpu::Cpu::load(Path::new(&path[..])) {
+		Ok(cpu) => cpu,
+		Err(e) => {
+			eprintln!("{}", e);
+			process::exit(1);
+		},
+	};
+	frontend::terminal::start_program(&mut cpu, columns, rows);
+	if let Some(reason) = cpu.halted {
+		eprintln!("The program stopped: {}", reason);
+		process::exit(1);
+	}
+}
@@ -0,0 +1,214 @@
+use image::{Rgba, RgbaImage};
+use std::fs;
+use std::path::{Path, PathBuf};
+use cli;
+use config::Config;
+use font::{self, GLYPH_WIDTH, GLYPH_HEIGHT};
+use overlay;
+
+pub const DEFAULT_DIR: &str = "programs/ROMs";
+
+//The browser draws on its own 640x480 image so the text is readable at the default scale
+pub const WIDTH: u32 = 640;
+pub const HEIGHT: u32 = 480;
+const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+const LIST_COLUMNS: usize = 40;
+const DETAILS_X: u32 = 4 + LIST_COLUMNS as u32 * GLYPH_WIDTH + 12;
+const TOP: u32 = 20;
+const ROWS: usize = ((HEIGHT - TOP - 2 * LINE_HEIGHT) / LINE_HEIGHT) as usize;
+
+const BACKGROUND: Rgba<u8> = Rgba([0x10, 0x10, 0x18, 255]);
+const HIGHLIGHT: Rgba<u8> = Rgba([0x30, 0x40, 0x7F, 255]);
+const TEXT: Rgba<u8> = Rgba([0xE0, 0xE0, 0xE0, 255]);
+const DIM: Rgba<u8> = Rgba([0x80, 0x80, 0x90, 255]);
+const ERROR: Rgba<u8> = Rgba([0xFF, 0x50, 0x50, 255]);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+	pub name: String,
+	pub path: PathBuf,
+	pub dir: bool,
+}
+
+pub struct Browser {
+	pub dir: PathBuf,
+	pub entries: Vec<Entry>,
+	pub selected: usize,
+	pub error: Option<String>, //Shown until the next move, like a ROM that didn't load
+	details: Vec<String>,
+}
+
+//The parent directory first, then directories and then .c16 and .bin files, by name
+fn list(dir: &Path) -> Result<Vec<Entry>, String> {
+	let read = fs::read_dir(dir).map_err(|e| format!("{} {}", e, dir.display()))?;
+	let mut dirs = Vec::new();
+	let mut roms = Vec::new();
+	for entry in read.filter_map(|entry| entry.ok()) {
+		let path = entry.path();
+		let name = entry.file_name().to_string_lossy().into_owned();
+		if name.starts_with('.') {
+			continue;
+		}
+		if path.is_dir() {
+			dirs.push(Entry { name: name, path: path, dir: true });
+		} else {
+			match path.extension().and_then(|ext| ext.to_str()) {
+				Some("c16") | Some("bin") => roms.push(Entry { name: name, path: path, dir: false }),
+				_ => {},
+			}
+		}
+	}
+	dirs.sort_by_key(|entry| entry.name.to_lowercase());
+	roms.sort_by_key(|entry| entry.name.to_lowercase());
+	let mut entries = Vec::new();
+	if let Some(parent) = dir.parent() {
+		entries.push(Entry { name: "..".to_string(), path: parent.to_path_buf(), dir: true });
+	}
+	entries.extend(dirs);
+	entries.extend(roms);
+	Ok(entries)
+}
+
+//Text that goes with a ROM, a file with the same name in a Documents directory next to any of its parents
+pub fn document(rom: &Path) -> Option<PathBuf> {
+	let stem = match rom.file_stem() {
+		Some(stem) => stem.to_string_lossy().to_lowercase(),
+		None => return None,
+	};
+	for dir in rom.ancestors().skip(1) {
+		let read = match fs::read_dir(dir.join("Documents")) {
+			Ok(read) => read,
+			Err(_) => continue,
+		};
+		for entry in read.filter_map(|entry| entry.ok()) {
+			let path = entry.path();
+			let same = path.file_stem().map(|s| s.to_string_lossy().to_lowercase() == stem).unwrap_or(false);
+			if same && path.extension().and_then(|ext| ext.to_str()) == Some("txt") {
+				return Some(path);
+			}
+		}
+	}
+	None
+}
+
+//The header information and the document of a ROM, as lines
+pub fn details(rom: &Path) -> Vec<String> {
+	let mut lines: Vec<String> = match cli::info(rom) {
+		Ok(info) => info.lines().skip(1).map(|line| line.to_string()).collect(), //Without the path
+		Err(e) => vec![e],
+	};
+	if let Some(path) = document(rom) {
+		if let Ok(bytes) = fs::read(&path) {
+			lines.push(String::new());
+			for line in String::from_utf8_lossy(&bytes).lines() {
+				lines.push(line.replace('\t', "    ").trim_end().to_string());
+			}
+		}
+	}
+	lines
+}
+
+//Where the last ROM came from, then programs/ROMs, then the working directory
+pub fn start_dir(config: &Config) -> PathBuf {
+	match config.last_rom_dir {
+		Some(ref dir) if dir.is_dir() => dir.clone(),
+		_ if Path::new(DEFAULT_DIR).is_dir() => PathBuf::from(DEFAULT_DIR),
+		_ => PathBuf::from("."),
+	}
+}
+
+fn wrap(line: &str, columns: usize) -> Vec<String> {
+	let chars: Vec<char> = line.chars().collect();
+	if chars.is_empty() {
+		return vec![String::new()];
+	}
+	chars.chunks(columns).map(|chunk| chunk.iter().collect()).collect()
+}
+
+impl Browser {
+	pub fn open(dir: &Path) -> Result<Browser, String> {
+		let dir = fs::canonicalize(dir).map_err(|e| format!("{} {}", e, dir.display()))?;
+		let entries = list(&dir)?;
+		let mut browser = Browser { dir: dir, entries: entries, selected: 0, error: None, details: Vec::new() };
+		browser.select(if browser.entries.len() > 1 { 1 } else { 0 }); //Skips ..
+		Ok(browser)
+	}
+
+	pub fn select(&mut self, index: usize) -> () {
+		self.selected = index.min(self.entries.len().saturating_sub(1));
+		self.error = None;
+		self.details = match self.entries.get(self.selected) {
+			Some(entry) if !entry.dir => details(&entry.path),
+			_ => Vec::new(),
+		};
+	}
+
+	pub fn move_by(&mut self, delta: i64) -> () {
+		let index = (self.selected as i64 + delta).max(0);
+		self.select(index as usize);
+	}
+
+	//Goes into a directory, or gives back the ROM to launch
+	pub fn activate(&mut self) -> Option<PathBuf> {
+		let entry = match self.entries.get(self.selected) {
+			Some(entry) => entry.clone(),
+			None => return None,
+		};
+		if !entry.dir {
+			return Some(entry.path);
+		}
+		let previous = self.dir.clone();
+		match Browser::open(&entry.path) {
+			Ok(browser) => {
+				*self = browser;
+				if let Some(i) = self.entries.iter().position(|e| e.name != ".." && e.path == previous) {
+					self.select(i); //Coming back up keeps the directory we were in selected
+				}
+			},
+			Err(e) => self.error = Some(e),
+		}
+		None
+	}
+
+	pub fn parent(&mut self) -> () {
+		if self.entries.first().map(|entry| entry.name == "..").unwrap_or(false) {
+			self.select(0);
+			self.activate();
+		}
+	}
+
+	pub fn render(&self) -> RgbaImage {
+		let mut frame = RgbaImage::from_pixel(WIDTH, HEIGHT, BACKGROUND);
+		let title = self.dir.display().to_string();
+		let columns = (WIDTH / GLYPH_WIDTH) as usize - 1;
+		let skip = title.chars().count().saturating_sub(columns); //The end of a long path is what tells them apart
+		font::draw_text(&mut frame, 4, 4, &title.chars().skip(skip).collect::<String>(), DIM, 1);
+
+		let first = if self.selected >= ROWS { self.selected + 1 - ROWS } else { 0 };
+		for (row, (i, entry)) in self.entries.iter().enumerate().skip(first).take(ROWS).enumerate() {
+			let y = (TOP + row as u32 * LINE_HEIGHT) as i64;
+			if i == self.selected {
+				overlay::fill(&mut frame, 2, y - 1, (LIST_COLUMNS as u32 * GLYPH_WIDTH + 4) as i64, LINE_HEIGHT as i64, HIGHLIGHT);
+			}
+			let mut name = entry.name.clone();
+			if entry.dir {
+				name.push('/');
+			}
+			let name: String = name.chars().take(LIST_COLUMNS).collect();
+			font::draw_text(&mut frame, 4, y, &name, if entry.dir { DIM } else { TEXT }, 1);
+		}
+
+		let columns = ((WIDTH - DETAILS_X) / GLYPH_WIDTH) as usize - 1;
+		let lines = self.details.iter().flat_map(|line| wrap(line, columns));
+		for (row, line) in lines.take(ROWS).enumerate() {
+			font::draw_text(&mut frame, DETAILS_X as i64, (TOP + row as u32 * LINE_HEIGHT) as i64, &line, TEXT, 1);
+		}
+
+		let bottom = (HEIGHT - LINE_HEIGHT) as i64;
+		if let Some(ref error) = self.error {
+			font::draw_text(&mut frame, 4, bottom - LINE_HEIGHT as i64, error, ERROR, 1);
+		}
+		font::draw_text(&mut frame, 4, bottom, "Up/Down choose  Return open  Backspace go up  F1 back to the program", DIM, 1);
+		frame
+	}
+}
@@ -0,0 +1,124 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use cpu::Memory;
+
+//Keeps an address at a value, written again at every vblank
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cheat {
+	pub address: u16,
+	pub value: u16,
+	pub word: bool, //Two bytes, little endian like the rest of memory
+	pub name: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+	Equal(u8),
+	Changed,
+	Unchanged,
+	Increased,
+	Decreased,
+}
+
+//Narrows down the addresses a value could be at by comparing memory between searches
+pub struct Search {
+	pub candidates: Vec<u16>,
+	previous: Vec<u8>, //Memory when the last search ran
+}
+
+impl Cheat {
+	pub fn apply(&self, memory: &mut Memory) -> () {
+		if self.word {
+			memory.write_byte(self.address as usize, self.value as u8 as i8);
+			memory.write_byte(self.address.wrapping_add(1) as usize, (self.value >> 8) as u8 as i8);
+		} else {
+			memory.write_byte(self.address as usize, self.value as u8 as i8);
+		}
+	}
+
+	//ADDRESS = VALUE in hex, 4 digits make a word. A name can follow after #
+	pub fn parse(line: &str) -> Result<Cheat, String> {
+		let (code, name) = match line.find('#') {
+			Some(i) => (&line[..i], line[i + 1..].trim()),
+			None => (line, ""),
+		};
+		let mut parts = code.splitn(2, '=');
+		let address = parts.next().unwrap().trim();
+		let value = match parts.next() {
+			Some(value) => value.trim(),
+			None => return Err(format!("{} has no value, write it as 3000 = 05", line.trim())),
+		};
+		let address = u16::from_str_radix(address, 16).map_err(|_| format!("{} is not an address", address))?;
+		let word = match value.len() {
+			1 | 2 => false,
+			3 | 4 => true,
+			_ => return Err(format!("{} is not a byte or a word", value)),
+		};
+		let value = u16::from_str_radix(value, 16).map_err(|_| format!("{} is not a byte or a word", value))?;
+		Ok(Cheat { address: address, value: value, word: word, name: name.to_string() })
+	}
+
+	pub fn to_text(&self) -> String {
+		let mut text = if self.word {
+			format!("{:04X} = {:04X}", self.address, self.value)
+		} else {
+			format!("{:04X} = {:02X}", self.address, self.value)
+		};
+		if !self.name.is_empty() {
+			text.push_str(&format!(" # {}", self.name));
+		}
+		text
+	}
+}
+
+//A cheat a line, # starts a comment when it is the first thing on it
+pub fn parse(text: &str) -> Result<Vec<Cheat>, String> {
+	let mut cheats = Vec::new();
+	for line in text.lines() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+		cheats.push(Cheat::parse(line)?);
+	}
+	Ok(cheats)
+}
+
+pub fn to_text(cheats: &[Cheat]) -> String {
+	cheats.iter().map(|cheat| cheat.to_text() + "\n").collect()
+}
+
+pub fn load(path: &Path) -> Result<Vec<Cheat>, String> {
+	let mut text = String::new();
+	File::open(path).and_then(|mut file| file.read_to_string(&mut text)).map_err(|e| format!("{} {}", e, path.display()))?;
+	parse(&text).map_err(|e| format!("{} in {}", e, path.display()))
+}
+
+fn snapshot(memory: &Memory) -> Vec<u8> {
+	memory.as_slice().iter().map(|&byte| byte as u8).collect()
+}
+
+impl Search {
+	pub fn new(memory: &Memory) -> Search {
+		Search { candidates: (0..0x10000).map(|address| address as u16).collect(), previous: snapshot(memory) }
+	}
+
+	pub fn filter(&mut self, memory: &Memory, comparison: Comparison) -> () {
+		let now = snapshot(memory);
+		{
+			let previous = &self.previous;
+			self.candidates.retain(|&address| {
+				let (before, after) = (previous[address as usize], now[address as usize]);
+				match comparison {
+					Comparison::Equal(value) => after == value,
+					Comparison::Changed => after != before,
+					Comparison::Unchanged => after == before,
+					Comparison::Increased => after > before,
+					Comparison::Decreased => after < before,
+				}
+			});
+		}
+		self.previous = now;
+	}
+}
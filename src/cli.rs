@@ -0,0 +1,578 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use cpu::Cpu;
+use loading;
+use disasm;
+use palette;
+use filters;
+use viewer::SpriteSheet;
+use overlay::DrawLogWriter;
+use recording::{self, Recorder};
+use frontend::headless;
+use config::Config;
+use games::{self, GameDatabase};
+use frontend::keys::KeyBindings;
+use cheats;
+use console;
+use gdb;
+use profile::Profile;
+use coverage::Coverage;
+use symbols::Symbols;
+
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+pub const USAGE: &str = "Usage: rust-chip16 [COMMAND] ROM [OPTIONS]
+
+Commands:
+  run [ROM] [SCALE]        Play ROM in a window, the default when no command is given.
+                           Without a ROM the window opens on the ROM browser, F1 shows it
+  disasm ROM               Print the program as assembly
+  info ROM                 Print the header and checksum of ROM
+  headless ROM             Run ROM without a window
+  screenshot ROM FILE      Run ROM without a window and save the last frame as a PNG
+
+Options:
+  --scale N                Pixels per emulator pixel (run, screenshot)
+  --speed X                Emulation speed, 2 runs twice as fast (run)
+  --keys FILE              Key bindings, lines like p1.up = Up (run)
+  --paused                 Start paused, P pauses and resumes (run)
+  --watch                  Reload ROM when the file changes (run)
+  --seed N                 Seed for RND so a run can be repeated
+  --trace FILE             Write every instruction executed to FILE
+  --cheats FILE            Cheats to use instead of the ones saved for ROM, lines like 3000 = 05
+  --symbols FILE           Labels for disasm, traces and breakpoints, ROM.sym when there is one
+  --palette PALETTE        Replace the default palette, PAL still works
+  --palette-override PALETTE
+                           Always show PALETTE, whatever the program loads
+  --gdb PORT               Wait for a GDB remote protocol debugger on 127.0.0.1:PORT (run, headless)
+  --profile FILE           Count the instructions every subroutine runs and write a report to FILE (run, headless)
+  --folded FILE            Write the call stacks counted as folded stacks for flame graphs (run, headless)
+  --coverage FILE          Mark the instructions that ran and the bytes read as data in a disassembly,
+                           or write an lcov tracefile when FILE ends in .info or .lcov (run, headless)
+  --frames N               Frames to run first (60, headless with --gdb 0)
+  --dump-palette           Print the palette in effect (headless)
+  --dump-sprites ADDRESS:WIDTHxHEIGHT[:COUNT] FILE
+                           Save sprites from memory as a PNG (headless)
+  --draw-log FILE          Write every DRW of every frame to FILE (headless)
+  --record FILE            Record the frames run as a GIF when FILE ends in .gif,
+                           or as numbered PNGs in the directory FILE (headless)
+  -h, --help               Print this help
+
+A PALETTE is a palette file or one of default, colourblind, high-contrast and grayscale.
+";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+	Run,
+	Disasm,
+	Info,
+	Headless,
+	Screenshot,
+	Help,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+	pub command: Command,
+	pub rom: PathBuf,
+	pub output: PathBuf, //Where screenshot saves the frame
+	pub scale: Option<u32>,
+	pub speed: Option<f64>,
+	pub keys: Option<PathBuf>,
+	pub paused: bool,
+	pub watch: bool,
+	pub seed: Option<u64>,
+	pub trace: Option<PathBuf>,
+	pub cheats: Option<PathBuf>,
+	pub symbols: Option<PathBuf>,
+	pub gdb: Option<u16>,
+	pub profile: Option<PathBuf>,
+	pub folded: Option<PathBuf>,
+	pub coverage: Option<PathBuf>,
+	pub palette: Option<String>,
+	pub palette_override: Option<String>,
+	pub frames: Option<u64>,
+	pub dump_palette: bool,
+	pub dump_sprites: Option<(String, PathBuf)>,
+	pub draw_log: Option<PathBuf>,
+	pub record: Option<PathBuf>,
+}
+
+impl Command {
+	fn from_name(name: &str) -> Option<Command> {
+		match name {
+			"run" => Some(Command::Run),
+			"disasm" => Some(Command::Disasm),
+			"info" => Some(Command::Info),
+			"headless" => Some(Command::Headless),
+			"screenshot" => Some(Command::Screenshot),
+			"help" => Some(Command::Help),
+			_ => None,
+		}
+	}
+
+	fn name(&self) -> &'static str {
+		match *self {
+			Command::Run => "run",
+			Command::Disasm => "disasm",
+			Command::Info => "info",
+			Command::Headless => "headless",
+			Command::Screenshot => "screenshot",
+			Command::Help => "help",
+		}
+	}
+
+	fn accepts(&self, flag: &str) -> bool {
+		use self::Command::*;
+		match flag {
+			"--scale" => *self == Run || *self == Screenshot,
+			"--speed" | "--keys" | "--paused" | "--watch" => *self == Run,
+			"--seed" | "--trace" | "--cheats" | "--palette" | "--palette-override" => *self == Run || *self == Headless || *self == Screenshot,
+			"--symbols" => *self == Run || *self == Disasm || *self == Headless || *self == Screenshot,
+			"--gdb" | "--profile" | "--folded" | "--coverage" => *self == Run || *self == Headless,
+			"--frames" => *self == Headless || *self == Screenshot,
+			"--dump-palette" | "--dump-sprites" | "--draw-log" | "--record" => *self == Headless,
+			_ => false,
+		}
+	}
+}
+
+impl Options {
+	//Fills in what the command line left out with the defaults from the configuration file
+	pub fn apply_config(&mut self, config: &Config) -> () {
+		if self.command == Command::Run { //A screenshot stays 320x240 unless asked
+			self.scale = self.scale.or(Some(config.scale));
+			self.speed = self.speed.or(Some(config.speed));
+		}
+		if self.command.accepts("--palette") {
+			if self.palette.is_none() {
+				self.palette = config.palette.clone();
+			}
+			if self.palette_override.is_none() {
+				self.palette_override = config.palette_override.clone();
+			}
+		}
+	}
+}
+
+//The config file with the settings the games file has for the ROM on top
+pub fn rom_defaults(options: &Options, config: &Config, database: &GameDatabase) -> Config {
+	let mut defaults = config.clone();
+	if let Ok(crc) = games::rom_crc(&options.rom) { //A ROM that can't be read fails later with a better message
+		if let Some(game) = database.get(crc) {
+			game.apply(&mut defaults);
+		}
+	}
+	defaults
+}
+
+//The command line options for a ROM with what it left out filled in from the games file and the config file,
+//and the settings they came from
+pub fn resolve(raw: &Options, config: &Config, database: &GameDatabase) -> (Options, Config) {
+	let defaults = rom_defaults(raw, config, database);
+	let mut options = raw.clone();
+	options.apply_config(&defaults);
+	(options, defaults)
+}
+
+//The --keys file, else the bindings of the config and games files
+pub fn key_bindings(options: &Options, defaults: &Config) -> Result<KeyBindings, String> {
+	match options.keys {
+		Some(ref path) => KeyBindings::load(path),
+		None => Ok(defaults.keys.clone()),
+	}
+}
+
+fn value<'a, I: Iterator<Item = &'a String>>(args: &mut I, flag: &str) -> Result<String, String> {
+	match args.next() {
+		Some(value) => Ok(value.clone()),
+		None => Err(format!("{} needs a value", flag)),
+	}
+}
+
+fn number<T: ::std::str::FromStr>(text: &str, flag: &str) -> Result<T, String> {
+	text.trim().parse().map_err(|_| format!("{} is not a number for {}", text, flag))
+}
+
+//Without a command the arguments are the old ROM [SCALE] ones, which also took the headless options
+pub fn parse(args: &[String]) -> Result<Options, String> {
+	let mut command = None;
+	let mut positional: Vec<String> = Vec::new();
+	let mut flags: Vec<&'static str> = Vec::new();
+	let mut options = Options {
+		command: Command::Run,
+		rom: PathBuf::new(),
+		output: PathBuf::new(),
+		scale: None,
+		speed: None,
+		keys: None,
+		paused: false,
+		watch: false,
+		seed: None,
+		trace: None,
+		cheats: None,
+		symbols: None,
+		gdb: None,
+		profile: None,
+		folded: None,
+		coverage: None,
+		palette: None,
+		palette_override: None,
+		frames: None,
+		dump_palette: false,
+		dump_sprites: None,
+		draw_log: None,
+		record: None,
+	};
+	let mut args = args.iter();
+	while let Some(arg) = args.next() {
+		let flag: &'static str = match &arg[..] {
+			"-h" | "--help" => {
+				options.command = Command::Help;
+				return Ok(options);
+			},
+			"--scale" => {
+				let scale: u32 = number(&value(&mut args, arg)?, arg)?;
+				if scale == 0 {
+					return Err("--scale has to be at least 1".to_string());
+				}
+				options.scale = Some(scale);
+				"--scale"
+			},
+			"--speed" => {
+				let speed: f64 = number(&value(&mut args, arg)?, arg)?;
+				if !speed.is_finite() || speed <= 0.0 {
+					return Err("--speed has to be more than 0".to_string());
+				}
+				options.speed = Some(speed);
+				"--speed"
+			},
+			"--keys" => {
+				options.keys = Some(PathBuf::from(value(&mut args, arg)?));
+				"--keys"
+			},
+			"--paused" => {
+				options.paused = true;
+				"--paused"
+			},
+			"--watch" => {
+				options.watch = true;
+				"--watch"
+			},
+			"--seed" => {
+				options.seed = Some(number(&value(&mut args, arg)?, arg)?);
+				"--seed"
+			},
+			"--trace" => {
+				options.trace = Some(PathBuf::from(value(&mut args, arg)?));
+				"--trace"
+			},
+			"--cheats" => {
+				options.cheats = Some(PathBuf::from(value(&mut args, arg)?));
+				"--cheats"
+			},
+			"--symbols" => {
+				options.symbols = Some(PathBuf::from(value(&mut args, arg)?));
+				"--symbols"
+			},
+			"--gdb" => {
+				options.gdb = Some(number(&value(&mut args, arg)?, arg)?);
+				"--gdb"
+			},
+			"--profile" => {
+				options.profile = Some(PathBuf::from(value(&mut args, arg)?));
+				"--profile"
+			},
+			"--folded" => {
+				options.folded = Some(PathBuf::from(value(&mut args, arg)?));
+				"--folded"
+			},
+			"--coverage" => {
+				options.coverage = Some(PathBuf::from(value(&mut args, arg)?));
+				"--coverage"
+			},
+			"--palette" => {
+				options.palette = Some(value(&mut args, arg)?);
+				"--palette"
+			},
+			"--palette-override" => {
+				options.palette_override = Some(value(&mut args, arg)?);
+				"--palette-override"
+			},
+			"--frames" => {
+				options.frames = Some(number(&value(&mut args, arg)?, arg)?);
+				"--frames"
+			},
+			"--dump-palette" => {
+				options.dump_palette = true;
+				"--dump-palette"
+			},
+			"--dump-sprites" => {
+				let sheet = value(&mut args, arg)?;
+				let file = value(&mut args, arg)?;
+				options.dump_sprites = Some((sheet, PathBuf::from(file)));
+				"--dump-sprites"
+			},
+			"--draw-log" => {
+				options.draw_log = Some(PathBuf::from(value(&mut args, arg)?));
+				"--draw-log"
+			},
+			"--record" => {
+				options.record = Some(PathBuf::from(value(&mut args, arg)?));
+				"--record"
+			},
+			_ if arg.starts_with("--") => return Err(format!("{} is not an option", arg)),
+			_ => {
+				match Command::from_name(arg) {
+					Some(name) if command.is_none() && positional.is_empty() => command = Some(name),
+					_ => positional.push(arg.clone()),
+				}
+				continue;
+			},
+		};
+		flags.push(flag);
+	}
+
+	options.command = match command {
+		Some(command) => command,
+		None if options.dump_palette || options.dump_sprites.is_some() || options.draw_log.is_some() => Command::Headless,
+		None => Command::Run,
+	};
+	if options.command == Command::Help {
+		return Ok(options);
+	}
+	if command.is_none() && options.command == Command::Headless && positional.len() == 2 {
+		positional.pop(); //The old multiplier, nothing to scale without a window
+	}
+	for flag in flags {
+		if !options.command.accepts(flag) {
+			return Err(format!("{} doesn't work with {}", flag, options.command.name()));
+		}
+	}
+
+	let mut positional = positional.into_iter();
+	options.rom = match positional.next() {
+		Some(rom) => PathBuf::from(rom),
+		None if options.command == Command::Run => PathBuf::new(), //The window opens on the ROM browser
+		None => return Err("No ROM specified".to_string()),
+	};
+	match options.command {
+		Command::Run => if let Some(scale) = positional.next() {
+			options.scale = Some(number(&scale, "the scale")?);
+		},
+		Command::Screenshot => match positional.next() {
+			Some(output) => options.output = PathBuf::from(output),
+			None => return Err("screenshot needs a file to save the frame to".to_string()),
+		},
+		_ => {},
+	}
+	if let Some(extra) = positional.next() {
+		return Err(format!("{} is one argument too many", extra));
+	}
+	Ok(options)
+}
+
+//Loads the ROM and applies the options every command that runs it shares
+pub fn load_cpu(options: &Options) -> Result<Cpu, String> {
+	let mut cpu = Cpu::load(&options.rom)?;
+	if let Some(seed) = options.seed {
+		cpu.seed(seed);
+	}
+	if let Some(ref argument) = options.palette {
+		cpu.graphics.palette = palette::from_argument(argument)?;
+	}
+	if let Some(ref argument) = options.palette_override {
+		cpu.graphics.palette_override = Some(palette::from_argument(argument)?);
+	}
+	cpu.symbols = symbols(options)?;
+	if let Some(ref path) = options.trace {
+		let file = File::create(path).map_err(|e| format!("{} {}", e, path.display()))?;
+		cpu.trace = Some(Box::new(BufWriter::new(file)));
+	}
+	if options.profile.is_some() || options.folded.is_some() {
+		cpu.profile = Some(Profile::new());
+	}
+	if options.coverage.is_some() {
+		cpu.memory.coverage = Some(Coverage::new());
+	}
+	cpu.cheats = match options.cheats {
+		Some(ref path) => cheats::load(path)?,
+		None if options.command == Command::Run => match console::load_for(&options.rom) { //Only when playing
+			Ok(cheats) => cheats,
+			Err(e) => {
+				println!("{}, playing without the saved cheats", e);
+				Vec::new()
+			},
+		},
+		None => Vec::new(),
+	};
+	Ok(cpu)
+}
+
+//From --symbols, else ROM.sym next to the ROM
+pub fn symbols(options: &Options) -> Result<Symbols, String> {
+	Symbols::for_rom(&options.rom, options.symbols.as_deref())
+}
+
+fn write_text(path: &Path, text: &str) -> Result<(), String> {
+	File::create(path).and_then(|mut file| file.write_all(text.as_bytes())).map_err(|e| format!("{} {}", e, path.display()))
+}
+
+//Writes what is left of the trace, or why it stopped early
+pub fn finish_trace(cpu: &mut Cpu) -> Result<(), String> {
+	if let Some(ref mut trace) = cpu.trace {
+		trace.flush().map_err(|e| format!("Couldn't write the trace: {}", e))?;
+	}
+	match cpu.trace_error.take() {
+		Some(e) => Err(e),
+		None => Ok(()),
+	}
+}
+
+//What --profile and --folded asked for, once the run is over
+pub fn write_profile(cpu: &Cpu, options: &Options) -> Result<(), String> {
+	if let Some(ref profile) = cpu.profile {
+		if let Some(ref path) = options.profile {
+			write_text(path, &profile.report(&cpu.symbols, cpu.start))?;
+		}
+		if let Some(ref path) = options.folded {
+			write_text(path, &profile.folded(&cpu.symbols, cpu.start))?;
+		}
+	}
+	Ok(())
+}
+
+//What --coverage asked for, options.rom has to be the program cpu ran
+pub fn write_coverage(cpu: &Cpu, options: &Options) -> Result<(), String> {
+	if let (Some(coverage), Some(path)) = (cpu.memory.coverage.as_ref(), options.coverage.as_ref()) {
+		let bytes = loading::read_file(&options.rom)?;
+		let (_, program) = loading::split_rom(&options.rom, &bytes)?;
+		let lcov = matches!(path.extension().and_then(|extension| extension.to_str()), Some("info") | Some("lcov"));
+		let text = if lcov {
+			coverage.lcov(&options.rom, program.len())
+		} else {
+			coverage.annotate(&cpu.memory, program.len(), &cpu.symbols)
+		};
+		write_text(path, &text)?;
+	}
+	Ok(())
+}
+
+pub fn info(path: &Path) -> Result<String, String> {
+	let bytes = loading::read_file(path)?;
+	let (header, program) = loading::split_rom(path, &bytes)?;
+	let crc = loading::crc32(program);
+	let mut text = format!("File: {}\n", path.display());
+	match header {
+		Some(header) => {
+			text.push_str(&format!("Format: c16, spec {}.{}\n", header.version.0, header.version.1));
+			text.push_str(&format!("Size: {} bytes", program.len()));
+			if header.rom_size as usize != program.len() {
+				text.push_str(&format!(", the header says {}", header.rom_size));
+			}
+			text.push_str(&format!("\nStart: #{:04X}\n", header.start_address));
+			text.push_str(&format!("CRC32: #{:08X}", crc));
+			if header.checksum != crc {
+				text.push_str(&format!(", the header says #{:08X}", header.checksum));
+			}
+			text.push('\n');
+		},
+		None => {
+			text.push_str("Format: bin, no header\n");
+			text.push_str(&format!("Size: {} bytes\nStart: #0000\nCRC32: #{:08X}\n", program.len(), crc));
+		},
+	}
+	Ok(text)
+}
+
+pub fn disassemble(path: &Path, symbols: &Symbols) -> Result<String, String> {
+	let bytes = loading::read_file(path)?;
+	let (_, program) = loading::split_rom(path, &bytes)?;
+	let cpu = Cpu::load(path)?;
+	Ok(disasm::listing(&cpu.memory, 0, program.len() as u32, symbols))
+}
+
+fn state(cpu: &Cpu) -> String {
+	let mut text = format!("pc=#{:04X} sp=#{:04X} flags=#{:02X}", cpu.pc, cpu.sp, cpu.flags as u8);
+	for (i, rx) in cpu.rx.iter().enumerate() {
+		text.push_str(&format!(" r{:X}=#{:04X}", i, *rx as u16));
+	}
+	text
+}
+
+//What went wrong when the program couldn't go on, the trace, profile and coverage up to there are kept
+fn halted(cpu: &Cpu) -> Result<(), String> {
+	match cpu.halted {
+		Some(ref reason) => Err(format!("The program stopped: {}", reason)),
+		None => Ok(()),
+	}
+}
+
+fn headless(options: &Options) -> Result<(), String> {
+	let mut cpu = load_cpu(options)?;
+	let frames = options.frames.unwrap_or(if options.gdb.is_some() { 0 } else { 60 }); //A debugger gets it from the start
+	let log = match options.draw_log {
+		Some(ref path) => {
+			let out = File::create(path).map_err(|e| format!("{} {}", e, path.display()))?;
+			cpu.graphics.draw_log = Some(Vec::new());
+			Some(DrawLogWriter::new(BufWriter::new(out)))
+		},
+		None => None,
+	};
+	let recorder = match options.record {
+		Some(ref path) => Some(Recorder::new(path, recording::format_for(path))?),
+		None => None,
+	};
+	let mut video = (log, recorder);
+	headless::run_with_video(&mut cpu, frames, &mut video); //Lets the program unpack its graphics first
+	let (log, recorder) = video;
+	let logged = log.map(|log| log.finish()).unwrap_or(Ok(0));
+	let recorded = recorder.map(|recorder| recorder.finish()).unwrap_or(Ok(0)); //Even when the log failed
+	logged?;
+	recorded?;
+	if let Some(port) = options.gdb {
+		let mut server = gdb::Server::bind(port)?;
+		println!("Waiting for a debugger on 127.0.0.1:{}", server.port());
+		gdb::serve(&mut cpu, &mut server)?;
+	}
+	finish_trace(&mut cpu)?;
+	write_profile(&cpu, options)?;
+	write_coverage(&cpu, options)?;
+	halted(&cpu)?;
+	if options.dump_palette {
+		print!("{}", palette::to_hex_list(cpu.graphics.effective_palette()));
+	}
+	if let Some((ref sheet, ref file)) = options.dump_sprites {
+		let sheet = SpriteSheet::parse(sheet)?;
+		let image = sheet.render(&cpu.memory, cpu.graphics.effective_palette(), 16);
+		image.save(file).map_err(|e| format!("{} {}", e, file.display()))?;
+	}
+	if !options.dump_palette && options.dump_sprites.is_none() && options.draw_log.is_none() {
+		println!("{}", state(&cpu));
+	}
+	Ok(())
+}
+
+fn screenshot(options: &Options) -> Result<(), String> {
+	let mut cpu = load_cpu(options)?;
+	headless::run(&mut cpu, options.frames.unwrap_or(60));
+	finish_trace(&mut cpu)?;
+	halted(&cpu)?;
+	let image = filters::nearest(&cpu.graphics.to_image(), options.scale.unwrap_or(1));
+	image.save(&options.output).map_err(|e| format!("{} {}", e, options.output.display()))
+}
+
+//Every command but run, which needs a window
+pub fn execute(options: &Options) -> Result<(), String> {
+	match options.command {
+		Command::Help => print!("{}", USAGE),
+		Command::Info => print!("{}", info(&options.rom)?),
+		Command::Disasm => print!("{}", disassemble(&options.rom, &symbols(options)?)?),
+		Command::Headless => headless(options)?,
+		Command::Screenshot => screenshot(options)?,
+		Command::Run => return Err("run needs a window, it isn't part of the library".to_string()),
+	}
+	Ok(())
+}
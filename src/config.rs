@@ -0,0 +1,234 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use frontend::ScaleMode;
+use frontend::keys::{self, KeyBindings};
+
+//Defaults for every run, command line options win over them
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+	pub scale: u32,
+	pub scale_mode: ScaleMode,
+	pub speed: f64,
+	pub palette: Option<String>, //Preset names or palette files, like --palette and --palette-override
+	pub palette_override: Option<String>,
+	pub last_rom_dir: Option<PathBuf>, //Where the last program run came from
+	pub keys: KeyBindings,
+}
+
+//A key = value line of the config or the games file, with a value that makes sense
+#[derive(Clone, Debug, PartialEq)]
+pub enum Setting {
+	Scale(u32),
+	ScaleMode(ScaleMode),
+	Speed(f64),
+	Palette(String),
+	PaletteOverride(String),
+	LastRomDir(PathBuf),
+	Key(usize, usize, String), //The player, the button in keys::BUTTONS and its key
+	Unknown, //Ignored so older versions can read newer files
+}
+
+//None for blank lines and comments, an error for lines that aren't key = value and values that don't make sense
+pub fn read_setting(line: &str) -> Option<Result<Setting, String>> {
+	let line = line.trim();
+	if line.is_empty() || line.starts_with('#') {
+		return None;
+	}
+	let mut parts = line.splitn(2, '=');
+	let key = parts.next().unwrap().trim();
+	let value = match parts.next() {
+		Some(value) => value.trim(),
+		None => return Some(Err(format!("{} is not key = value", line))),
+	};
+	Some(match key {
+		"scale" => match value.parse() {
+			Ok(scale) if scale > 0 => Ok(Setting::Scale(scale)),
+			_ => Err(format!("{} is not a scale, it is a whole number from 1 up", line)),
+		},
+		"scale_mode" => ScaleMode::from_name(value).map(Setting::ScaleMode)
+			.ok_or(format!("{} is not a scaling mode, it is integer or fit", line)),
+		"speed" => match value.parse::<f64>() {
+			Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(Setting::Speed(speed)),
+			_ => Err(format!("{} is not a speed, it is a number above 0 like 1.5", line)),
+		},
+		"palette" => Ok(Setting::Palette(value.to_string())),
+		"palette_override" => Ok(Setting::PaletteOverride(value.to_string())),
+		"last_rom_dir" => Ok(Setting::LastRomDir(PathBuf::from(value))),
+		_ if key.starts_with("p1.") || key.starts_with("p2.") => {
+			if value.is_empty() {
+				Err(format!("{} has no key, write it as p1.up = Up", line))
+			} else {
+				keys::button_index(key).map(|(player, button)| Setting::Key(player, button, value.to_string()))
+			}
+		},
+		_ => Ok(Setting::Unknown),
+	})
+}
+
+//$XDG_CONFIG_HOME/rustchip16, falling back to ~/.config/rustchip16
+pub fn config_dir() -> Option<PathBuf> {
+	let base = match env::var_os("XDG_CONFIG_HOME") {
+		Some(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+		_ => match env::var_os("HOME") {
+			Some(home) => PathBuf::from(home).join(".config"),
+			None => return None,
+		},
+	};
+	Some(base.join("rustchip16"))
+}
+
+impl Default for Config {
+	fn default() -> Config {
+		Config::new()
+	}
+}
+
+impl Config {
+	pub fn new() -> Config {
+		Config {
+			scale: 2,
+			scale_mode: ScaleMode::Integer,
+			speed: 1.0,
+			palette: None,
+			palette_override: None,
+			last_rom_dir: None,
+			keys: KeyBindings::new(),
+		}
+	}
+
+	pub fn parse(text: &str) -> Config {
+		Config::read(text).0
+	}
+
+	//The config and what was wrong with it, lines with values that don't make sense keep the default
+	pub fn read(text: &str) -> (Config, Vec<String>) {
+		let mut config = Config::new();
+		let mut errors = Vec::new();
+		for setting in text.lines().filter_map(read_setting) {
+			match setting {
+				Ok(setting) => config.set(setting),
+				Err(e) => errors.push(e),
+			}
+		}
+		(config, errors)
+	}
+
+	pub fn set(&mut self, setting: Setting) -> () {
+		match setting {
+			Setting::Scale(scale) => self.scale = scale,
+			Setting::ScaleMode(mode) => self.scale_mode = mode,
+			Setting::Speed(speed) => self.speed = speed,
+			Setting::Palette(palette) => self.palette = Some(palette),
+			Setting::PaletteOverride(palette) => self.palette_override = Some(palette),
+			Setting::LastRomDir(dir) => self.last_rom_dir = Some(dir),
+			Setting::Key(player, button, key) => self.keys.players[player][button] = key,
+			Setting::Unknown => {},
+		}
+	}
+
+	//The value a setting is saved with, for those the frontends change
+	fn setting(&self, name: &str) -> Option<String> {
+		match name {
+			"scale_mode" => Some(self.scale_mode.name().to_string()),
+			"last_rom_dir" => self.last_rom_dir.as_ref().map(|dir| dir.display().to_string()),
+			_ => None,
+		}
+	}
+
+	//text with the lines of the named settings set to their values and the missing ones added at the end,
+	//comments, unknown keys and everything else stay as they are
+	pub fn update_text(&self, text: &str, names: &[&str]) -> String {
+		let mut found = vec![false; names.len()];
+		let mut updated = String::new();
+		for line in text.lines() {
+			let key = line.split('=').next().unwrap().trim();
+			match names.iter().position(|&name| name == key) {
+				Some(i) if line.contains('=') => {
+					found[i] = true;
+					if let Some(value) = self.setting(key) {
+						updated.push_str(&format!("{} = {}\n", key, value));
+					}
+				},
+				_ => {
+					updated.push_str(line);
+					updated.push('\n');
+				},
+			}
+		}
+		for (i, &name) in names.iter().enumerate() {
+			if let (false, Some(value)) = (found[i], self.setting(name)) {
+				updated.push_str(&format!("{} = {}\n", name, value));
+			}
+		}
+		updated
+	}
+
+	pub fn to_text(&self) -> String {
+		let mut text = format!("scale = {}\nscale_mode = {}\nspeed = {}\n", self.scale, self.scale_mode.name(), self.speed);
+		if let Some(ref palette) = self.palette {
+			text.push_str(&format!("palette = {}\n", palette));
+		}
+		if let Some(ref palette) = self.palette_override {
+			text.push_str(&format!("palette_override = {}\n", palette));
+		}
+		if let Some(ref dir) = self.last_rom_dir {
+			text.push_str(&format!("last_rom_dir = {}\n", dir.display()));
+		}
+		text.push_str(&self.keys.to_text());
+		text
+	}
+
+	pub fn load() -> Config {
+		let path = match config_dir() {
+			Some(dir) => dir.join("config"),
+			None => return Config::new(),
+		};
+		let mut text = String::new();
+		match File::open(&path) {
+			Ok(mut file) => match file.read_to_string(&mut text) {
+				Ok(_) => {
+					let (config, errors) = Config::read(&text);
+					for e in errors {
+						println!("{} in {}", e, path.display());
+					}
+					config
+				},
+				Err(e) => {
+					println!("Couldn't read {}: {}", path.display(), e);
+					Config::new()
+				},
+			},
+			Err(_) => Config::new(),
+		}
+	}
+
+	//Writes the named settings to the config file and leaves the rest of it alone
+	pub fn save(&self, names: &[&str]) -> io::Result<()> {
+		let dir = match config_dir() {
+			Some(dir) => dir,
+			None => return Err(io::Error::new(io::ErrorKind::NotFound, "No configuration directory")),
+		};
+		fs::create_dir_all(&dir)?;
+		let path = dir.join("config");
+		let mut text = String::new();
+		match File::open(&path) {
+			Ok(mut file) => { file.read_to_string(&mut text)?; },
+			Err(ref e) if e.kind() == io::ErrorKind::NotFound => {},
+			Err(e) => return Err(e),
+		}
+		let mut file = File::create(&path)?;
+		file.write_all(self.update_text(&text, names).as_bytes())
+	}
+
+	//Keeps the directory of a ROM that was just run so the browser starts there next time
+	pub fn remember_rom(&mut self, rom: &Path) -> () {
+		if let Some(dir) = rom.canonicalize().ok().and_then(|rom| rom.parent().map(|dir| dir.to_path_buf())) {
+			self.last_rom_dir = Some(dir);
+			if let Err(e) = self.save(&["last_rom_dir"]) {
+				println!("Couldn't save the configuration: {}", e);
+			}
+		}
+	}
+}
@@ -0,0 +1,175 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use config;
+use cpu::Cpu;
+use games;
+use cheats::{self, Cheat, Comparison, Search};
+
+//Text commands for the cheats and the search, from a console next to the window
+pub struct Console {
+	search: Option<Search>,
+}
+
+pub const CONSOLE_HELP: &str = "Numbers are hex, with or without # or 0x in front
+  search new                  Start a search over every byte
+  search = N                  Keep the addresses that hold N
+  search changed|unchanged    Keep the ones that changed or didn't since the last search
+  search up|down              Keep the ones that went up or down since the last search
+  cheat ADDRESS VALUE [NAME]  Keep a byte at VALUE, cheatw does the same for a word
+  uncheat ADDRESS             Stop keeping ADDRESS
+  cheats                      List the cheats
+  save                        Keep the cheats for this ROM for the next time
+  break LABEL|ADDRESS         Pause when the program gets there, P goes on
+  unbreak LABEL|ADDRESS       Remove a breakpoint
+  breaks                      List the breakpoints
+";
+
+const SHOWN: usize = 16; //Candidates listed after a search
+
+//<config dir>/cheats/CRC32 of the program
+pub fn rom_path(rom: &Path) -> Option<PathBuf> {
+	match (config::config_dir(), games::rom_crc(rom)) {
+		(Some(dir), Ok(crc)) => Some(dir.join("cheats").join(format!("{:08X}", crc))),
+		_ => None,
+	}
+}
+
+//The cheats saved for a ROM, none when it has no file
+pub fn load_for(rom: &Path) -> Result<Vec<Cheat>, String> {
+	match rom_path(rom) {
+		Some(ref path) if path.exists() => cheats::load(path),
+		_ => Ok(Vec::new()),
+	}
+}
+
+pub fn save_for(rom: &Path, cheats: &[Cheat]) -> Result<PathBuf, String> {
+	let path = match rom_path(rom) {
+		Some(path) => path,
+		None => return Err("There is no configuration directory to save cheats in".to_string()),
+	};
+	fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string())?;
+	File::create(&path).and_then(|mut file| file.write_all(cheats::to_text(cheats).as_bytes()))
+		.map_err(|e| format!("{} {}", e, path.display()))?;
+	Ok(path)
+}
+
+//A label from the symbols or a number
+fn address(cpu: &Cpu, text: &str) -> Result<u16, String> {
+	if let Some(address) = cpu.symbols.address(text) {
+		return Ok(address);
+	}
+	match number(text) {
+		Ok(address) if address <= 0xFFFF => Ok(address as u16),
+		Ok(_) => Err(format!("{} is past the end of memory", text)),
+		Err(_) => Err(format!("{} is not a label or an address", text)),
+	}
+}
+
+//Hex like the cheat files and every address printed, # or 0x in front is allowed
+fn number(text: &str) -> Result<u32, String> {
+	let digits = text.strip_prefix('#').or_else(|| text.strip_prefix("0x")).unwrap_or(text);
+	u32::from_str_radix(digits, 16).map_err(|_| format!("{} is not a number", text))
+}
+
+impl Default for Console {
+	fn default() -> Console {
+		Console::new()
+	}
+}
+
+impl Console {
+	pub fn new() -> Console {
+		Console { search: None }
+	}
+
+	//Runs a command line and gives back what to print
+	pub fn execute(&mut self, cpu: &mut Cpu, rom: &Path, line: &str) -> Result<String, String> {
+		let words: Vec<&str> = line.split_whitespace().collect();
+		match words.first().copied() {
+			None => Ok(String::new()),
+			Some("help") => Ok(CONSOLE_HELP.to_string()),
+			Some("search") => self.search(cpu, &words[1..]),
+			Some("cheat") | Some("cheatw") => {
+				if words.len() < 3 {
+					return Err(format!("{} needs an address and a value", words[0]));
+				}
+				let word = words[0] == "cheatw";
+				let address = number(words[1])?;
+				let value = number(words[2])?;
+				if address > 0xFFFF || value > if word { 0xFFFF } else { 0xFF } {
+					return Err(format!("{} {} doesn't fit", words[1], words[2]));
+				}
+				let cheat = Cheat { address: address as u16, value: value as u16, word: word, name: words[3..].join(" ") };
+				cpu.cheats.retain(|old| old.address != cheat.address);
+				cheat.apply(&mut cpu.memory); //Right away, even while paused
+				let text = cheat.to_text();
+				cpu.cheats.push(cheat);
+				Ok(text + "\n")
+			},
+			Some("uncheat") if words.len() == 2 => {
+				let address = number(words[1])?;
+				let count = cpu.cheats.len();
+				cpu.cheats.retain(|cheat| cheat.address as u32 != address);
+				if cpu.cheats.len() == count {
+					return Err(format!("There is no cheat at {}", words[1]));
+				}
+				Ok(String::new())
+			},
+			Some("cheats") => Ok(cheats::to_text(&cpu.cheats)),
+			Some("break") if words.len() == 2 => {
+				let address = address(cpu, words[1])?;
+				if !cpu.breakpoints.contains(&address) {
+					cpu.breakpoints.push(address);
+				}
+				Ok(format!("Breaks at {}\n", cpu.symbols.operand(address)))
+			},
+			Some("unbreak") if words.len() == 2 => {
+				let address = address(cpu, words[1])?;
+				let count = cpu.breakpoints.len();
+				cpu.breakpoints.retain(|&breakpoint| breakpoint != address);
+				if cpu.breakpoints.len() == count {
+					return Err(format!("There is no breakpoint at {}", words[1]));
+				}
+				Ok(String::new())
+			},
+			Some("breaks") => Ok(cpu.breakpoints.iter()
+				.map(|&address| format!("{:04X} {}\n", address, cpu.symbols.describe(address)))
+				.collect()),
+			Some("save") => {
+				let path = save_for(rom, &cpu.cheats)?;
+				Ok(format!("Saved {} cheats to {}\n", cpu.cheats.len(), path.display()))
+			},
+			Some(command) => Err(format!("{} is not a command, help lists them", command)),
+		}
+	}
+
+	fn search(&mut self, cpu: &Cpu, words: &[&str]) -> Result<String, String> {
+		let comparison = match words {
+			["new"] => {
+				self.search = Some(Search::new(&cpu.memory));
+				return Ok("Searching all 65536 bytes\n".to_string());
+			},
+			["=", value] => match number(value)? {
+				value if value <= 0xFF => Comparison::Equal(value as u8),
+				_ => return Err(format!("{} doesn't fit in a byte", value)),
+			},
+			["changed"] => Comparison::Changed,
+			["unchanged"] => Comparison::Unchanged,
+			["up"] => Comparison::Increased,
+			["down"] => Comparison::Decreased,
+			_ => return Err("search takes new, = N, changed, unchanged, up or down".to_string()),
+		};
+		let search = match (self.search.as_mut(), comparison) {
+			(Some(search), _) => search,
+			(None, Comparison::Equal(_)) => self.search.get_or_insert(Search::new(&cpu.memory)),
+			(None, _) => return Err("There is nothing to compare with yet, start with search new".to_string()),
+		};
+		search.filter(&cpu.memory, comparison);
+		let mut text = format!("{} addresses\n", search.candidates.len());
+		for &address in search.candidates.iter().take(SHOWN) {
+			text.push_str(&format!("{:04X} = {:02X}\n", address, cpu.memory.peek(address as usize) as u8));
+		}
+		Ok(text)
+	}
+}
@@ -0,0 +1,101 @@
+use std::path::Path;
+use cpu::Memory;
+use disasm;
+use symbols::Symbols;
+
+const EXECUTED: u8 = 1;
+const READ: u8 = 2;
+
+//Which bytes ran as instructions and which the program read as data, peeks by tools don't count
+pub struct Coverage {
+	flags: Vec<u8>, //EXECUTED and READ by address
+	runs: Vec<u32>, //Times the instruction at each address ran
+}
+
+impl Default for Coverage {
+	fn default() -> Coverage {
+		Coverage::new()
+	}
+}
+
+impl Coverage {
+	pub fn new() -> Coverage {
+		Coverage { flags: vec![0; 0x10000], runs: vec![0; 0x10000] }
+	}
+
+	//The 4 bytes of the instruction at pc
+	pub fn execute(&mut self, pc: usize) -> () {
+		self.runs[pc] = self.runs[pc].saturating_add(1);
+		for flags in self.flags[pc..pc + 4].iter_mut() {
+			*flags |= EXECUTED;
+		}
+	}
+
+	pub fn read(&mut self, dir: usize) -> () {
+		self.flags[dir & 0xFFFF] |= READ;
+	}
+
+	pub fn executed(&self, dir: usize) -> bool {
+		self.flags[dir] & EXECUTED != 0
+	}
+
+	pub fn was_read(&self, dir: usize) -> bool {
+		self.flags[dir] & READ != 0
+	}
+
+	pub fn runs(&self, pc: usize) -> u32 {
+		self.runs[pc]
+	}
+
+	//Bytes of the first length executed, read as data and neither
+	pub fn summary(&self, length: usize) -> (usize, usize, usize) {
+		let executed = (0..length).filter(|&dir| self.executed(dir)).count();
+		let read = (0..length).filter(|&dir| self.was_read(dir) && !self.executed(dir)).count();
+		(executed, read, length - executed - read)
+	}
+
+	//X when any byte of the instruction at address ran, D when they were only read and - when nothing touched them
+	fn mark(&self, address: usize) -> char {
+		let bytes = address..(address + 4).min(0x10000);
+		if bytes.clone().any(|dir| self.executed(dir)) {
+			'X'
+		} else if bytes.clone().any(|dir| self.was_read(dir)) {
+			'D'
+		} else {
+			'-'
+		}
+	}
+
+	//The program a line per instruction like disasm, marked X when it ran, D when it was only read as data
+	//and - when the run never got to it
+	pub fn annotate(&self, memory: &Memory, length: usize, symbols: &Symbols) -> String {
+		let (executed, read, untouched) = self.summary(length);
+		let mut text = format!("; {} of {} bytes executed, {} read as data, {} not touched\n", executed, length, read, untouched);
+		for address in (0..length).step_by(4) {
+			if let Some(label) = symbols.name(address as u16) {
+				text.push_str(&format!("   :{}\n", label));
+			}
+			text.push_str(&format!("{}  {}\n", self.mark(address), disasm::line(memory, address as u16, symbols)));
+		}
+		text
+	}
+
+	//lcov's tracefile format for rom, line N is the instruction at (N - 1) * 4 like in disasm without symbols.
+	//Instructions only read as data aren't lines
+	pub fn lcov(&self, rom: &Path, length: usize) -> String {
+		let mut text = format!("TN:\nSF:{}\n", rom.display());
+		let (mut found, mut hit) = (0, 0);
+		for address in (0..length).step_by(4) {
+			if self.mark(address) == 'D' {
+				continue;
+			}
+			found += 1;
+			if self.runs[address] > 0 {
+				hit += 1;
+			}
+			text.push_str(&format!("DA:{},{}\n", address / 4 + 1, self.runs[address]));
+		}
+		text.push_str(&format!("LF:{}\nLH:{}\nend_of_record\n", found, hit));
+		text
+	}
+}
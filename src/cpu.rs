@@ -1,10 +1,19 @@
+#[cfg(feature = "tools")]
 use image;
-use std::fs::File;
-use opcode::{to_opcode, join_bytes, separate_byte, separate_word};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::io::Write;
+use opcode::{to_opcode, join_bytes, separate_word};
 use opcode;
-use piston_window::*;
+use frontend::{VideoSink, AudioSink, InputSource};
 use std::path::Path;
-use loading::{load_bin, load_c16};
+use loading;
+use disasm;
+use cheats::Cheat;
+use symbols::Symbols;
+use profile::Profile;
+use coverage::Coverage;
+use palette::DEFAULT_PALETTE;
 
 enum Flag {
 	Carry = 1 << 1,
@@ -13,7 +22,8 @@ enum Flag {
 	Negative = 1 << 7,
 }
 
-enum Pad {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pad {
 	Up = 1,
 	Down = 2, 
 	Left = 4,
@@ -24,15 +34,38 @@ enum Pad {
 	B = 128,
 }
 
+//A CALL whose RET hasn't run yet, for debuggers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CallFrame {
+	pub slot: u16, //Where the return address is on the stack
+	pub from: u16, //Address of the CALL
+	pub target: u16,
+}
+
+pub const INSTRUCTIONS_PER_FRAME: u32 = 16666; //1MHz at 60 frames per second
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DrawCall {
+	pub x: i16,
+	pub y: i16,
+	pub address: u16,
+	pub width: u8, //In bytes, like spritew
+	pub height: u8,
+	pub hflip: bool,
+	pub vflip: bool,
+	pub collided: bool,
+}
+
 pub struct Chip16Graphics {
 	pub state: StateRegister,
+	pub draw_log: Option<Vec<DrawCall>>, //Every DRW of the current frame, when enabled
 	pub palette: [u32; 16],
+	pub palette_override: Option<[u32; 16]>, //Shown instead of palette, whatever PAL loads
 	pub screen: [u8 ; 76800], //320x240
-	size: u32,
 }
 	
 pub struct StateRegister {
-	bg: u8,
+	pub bg: u8,
 	pub spritew: u8,
 	pub spriteh: u8,
 	pub hflip: bool,
@@ -41,32 +74,103 @@ pub struct StateRegister {
 
 pub struct Memory {
 	memory: [i8; 65536],
+	written: Option<Box<[u64; 1024]>>, //A bit per byte written since clear_written, while the memory editor is open
+	pub coverage: Option<Coverage>, //Kept when a run is measured
 }
 	
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sound {
+	Stop,
+	Tone { frequency: u16, duration: u16 },
+	Generator { attack: u8, decay: u8, sustain: u8, release: u8, volume: u8, wave: u8 },
+}
+
 pub struct Cpu {
 	pub pc: u16,
+	pub start: u16, //Where the program starts, a soft reset goes back here
 	pub sp: u16,
-	rx: [i16; 16],
-	flags: i8,
+	pub rx: [i16; 16],
+	pub flags: i8,
 	pub vblank: bool,
 	pub graphics: Chip16Graphics,
 	pub memory: Memory,
+	pub sounds: Vec<Sound>, //Sound commands issued since the last frame
+	pub rng: StdRng, //RND draws from here, seed it to repeat a run
+	pub trace: Option<Box<dyn Write>>, //Gets a disassembled line for every instruction executed
+	pub trace_error: Option<String>, //Why the trace stopped, when writing it failed
+	pub busy: u32, //Instructions of the last frame that did more than wait on VBLNK
+	pub calls: Vec<CallFrame>, //Innermost last
+	pub cheats: Vec<Cheat>, //Written at every vblank
+	pub symbols: Symbols, //Labels for the disassembly, the trace and breakpoints
+	pub breakpoints: Vec<u16>,
+	pub break_hit: Option<u16>, //Where run_frame stopped in the middle of a frame
+	pub halted: Option<String>, //Why the program can't go on, nothing runs until a reset or a new program
+	pub skip_break: bool, //The next instruction runs even at a breakpoint, after a break or while VBLNK waits
+	pub profile: Option<Profile>, //Counts every instruction run when there is one
+	frame_step: u32, //Instructions of the frame run so far, a frame goes on from here after a break
 }
 
 impl Memory {
 	pub fn new() -> Memory {
-	    Memory { memory: [0; 65536] }
+	    Memory { memory: [0; 65536], written: None, coverage: None }
 	}
 	
 	pub fn read_byte(&mut self, dir: usize) -> i8 {
+		if let Some(ref mut coverage) = self.coverage {
+			coverage.read(dir);
+		}
+		self.memory[dir]
+	}
+	
+	//The instruction at pc, which counts as executed instead of read
+	pub fn fetch(&mut self, pc: usize) -> [i8; 4] {
+		if let Some(ref mut coverage) = self.coverage {
+			coverage.execute(pc);
+		}
+		[self.memory[pc], self.memory[pc + 1], self.memory[pc + 2], self.memory[pc + 3]]
+	}
+	
+	pub fn peek(&self, dir: usize) -> i8 { //For debugging tools, not the emulated program
 		self.memory[dir]
 	}
 	
+	pub fn as_slice(&self) -> &[i8] {
+		&self.memory
+	}
+	
 	pub fn write_byte(&mut self, dir: usize, value: i8) -> () {
 		self.memory[dir] = value;
+		self.mark_written(dir);
+	}
+	
+	fn mark_written(&mut self, dir: usize) -> () {
+		if let Some(ref mut written) = self.written {
+			written[dir >> 6] |= 1 << (dir & 63);
+		}
+	}
+	
+	//Only kept while something shows them, writes cost nothing more the rest of the time
+	pub fn track_writes(&mut self, on: bool) -> () {
+		if on != self.written.is_some() {
+			self.written = if on { Some(Box::new([0; 1024])) } else { None };
+		}
+	}
+	
+	pub fn was_written(&self, dir: usize) -> bool {
+		self.written.as_ref().map(|written| written[dir >> 6] & (1 << (dir & 63)) != 0).unwrap_or(false)
+	}
+	
+	pub fn clear_written(&mut self) -> () {
+		if let Some(ref mut written) = self.written {
+			**written = [0; 1024];
+		}
 	}
 	
 	pub fn read_word(&mut self, dir: usize) -> i16 {
+		if let Some(ref mut coverage) = self.coverage {
+			coverage.read(dir);
+			coverage.read(dir + 1);
+		}
 		let ll = self.memory[dir];
 		let hh = self.memory[dir + 1];
 		join_bytes(ll, hh)
@@ -76,6 +180,8 @@ impl Memory {
 		let (hh, ll) = separate_word(value);
 		self.memory[dir] = ll;
 		self.memory[dir + 1] = hh;
+		self.mark_written(dir);
+		self.mark_written(dir + 1);
 	}
 }
 
@@ -89,25 +195,20 @@ impl StateRegister {
 	}
 }
 
-impl Chip16Graphics {
-	pub fn new(multiplier: u32) -> Chip16Graphics {
-		Chip16Graphics {
-			state: StateRegister::new(),
-			palette: [0x000000, 0x000000, 0x888888, 0xBF3932, 0xDE7AAE, 0x4C3D21, 0x905F25, 0xE49452,
-				0xEAD979, 0x537A3B, 0xABD54A, 0x252E38, 0x00467F, 0x68ABCC, 0xBCDEE4, 0xFFFFFF],
-			screen: [0; 76800],
-			size: multiplier,
-		}
+impl Default for Chip16Graphics {
+	fn default() -> Chip16Graphics {
+		Chip16Graphics::new()
 	}
-	
-	#[allow(dead_code)]
-	pub fn new_test() -> Chip16Graphics {
+}
+
+impl Chip16Graphics {
+	pub fn new() -> Chip16Graphics {
 		Chip16Graphics {
 			state: StateRegister::new(),
-			palette: [0x000000, 0x000000, 0x888888, 0xBF3932, 0xDE7AAE, 0x4C3D21, 0x905F25, 0xE49452,
-				0xEAD979, 0x537A3B, 0xABD54A, 0x252E38, 0x00467F, 0x68ABCC, 0xBCDEE4, 0xFFFFFF],
+			draw_log: None,
+			palette: DEFAULT_PALETTE,
+			palette_override: None,
 			screen: [0; 76800],
-			size: 1,
 		}
 	}
 	
@@ -122,150 +223,163 @@ impl Chip16Graphics {
 		self.state.bg = byte;
 	}
 
-	pub fn drw(&mut self, mem: &mut Memory, spr_x: i16, spr_y: i16, spr_address: i16) -> bool {
-		let spritew = self.state.spritew as u32 as i32;
-		let spriteh = self.state.spriteh as u32 as i32;
-		let spr_x = spr_x as i32;
-		let spr_y = spr_y as i32;
-		if spr_x > 319  //If nothing is to be drawn, return
-			|| spr_y > 239
-			|| spritew == 0
-			|| spriteh == 0
-			|| (spr_x + spritew * 2) < 0
-			|| (spr_y + spriteh) < 0
-		{
-			return false;
+	pub fn effective_palette(&self) -> &[u32; 16] {
+		match self.palette_override {
+			Some(ref palette) => palette,
+			None => &self.palette,
 		}
+	}
 
-		let mut hit: u8 = 0;
-		for y in 0i32..spriteh {
-			for x in 0i32..spritew {
-				let mut x = x;
-				let mut y = y;
-				
-				x = x * 2;
-				if (spr_x + x) < 0 //If outside boundaries, continue to next iteration
-					|| (spr_x + x) > 319
-					|| (spr_y + y) < 0
-					|| (spr_y + y) > 239
-				{
-					continue;
-				}
+	pub fn palette_rgba(&self) -> [[u8; 4]; 16] {
+		let mut colours = [[0u8; 4]; 16];
+		for (colour, p) in colours.iter_mut().zip(self.effective_palette().iter()) {
+			*colour = [((p >> 16) & 0xFF) as u8, ((p >> 8) & 0xFF) as u8, (p & 0xFF) as u8, 255];
+		}
+		colours[0] = colours[(self.state.bg & 0xF) as usize]; //Transparent pixels show the background
+		colours
+	}
 
-				let pixels = mem.read_byte((y as u16 * spritew as u16
-					+ x as u16 / 2
-					+ spr_address as u16)
-				as usize);
-				
-				let (hh_pixel, ll_pixel) = separate_byte(pixels);
-				let odd_pixel: u8;
-				let even_pixel: u8;
-				if self.state.hflip { //Flips in case of a horizontal flip
-					odd_pixel = hh_pixel as u8;
-					even_pixel = ll_pixel as u8;
-				} else {
-					even_pixel = hh_pixel as u8;
-					odd_pixel = ll_pixel as u8;
-				}
-				
-				if self.state.hflip { //When a flip is active, move the byte to the other side
-					x = ((spritew - 1) - x / 2) * 2;
-				}
-				
-				if self.state.vflip {
-					y = (spriteh - 1) - y;
-				}
-				
-				let x = x as u32;
-				let y = y as u32;
+	pub fn write_rgba(&self, buffer: &mut [u8]) -> () { //buffer holds 320x240 RGBA pixels
+		let colours = self.palette_rgba();
+		for (rgba, pixel) in buffer.chunks_exact_mut(4).zip(self.screen.iter()) {
+			rgba.copy_from_slice(&colours[*pixel as usize]);
+		}
+	}
 
-				let screen_pos = (320 * (spr_y as u32 + y) + spr_x as u32 + x) as usize;
-				if even_pixel != 0 && screen_pos < 76800 {  //If the pixel is transparent, doesn't draw
-					hit |= self.screen[screen_pos];
-					self.screen[screen_pos] = even_pixel;
-				}
+	#[cfg(feature = "tools")]
+	pub fn to_image(&self) -> image::RgbaImage {
+		let mut frame = image::RgbaImage::new(320, 240);
+		self.write_rgba(&mut frame);
+		frame
+	}
 
-				if odd_pixel != 0 && screen_pos + 1 < 76800 {
-					hit |= self.screen[screen_pos + 1];
-					self.screen[screen_pos + 1] = odd_pixel;
-				}
-			}
+	pub fn drw(&mut self, mem: &mut Memory, spr_x: i16, spr_y: i16, spr_address: i16) -> bool {
+		let hit = self.draw_sprite(mem, spr_x, spr_y, spr_address);
+		if let Some(ref mut log) = self.draw_log {
+			log.push(DrawCall {
+				x: spr_x,
+				y: spr_y,
+				address: spr_address as u16,
+				width: self.state.spritew,
+				height: self.state.spriteh,
+				hflip: self.state.hflip,
+				vflip: self.state.vflip,
+				collided: hit,
+			});
 		}
-		hit != 0 //If different than zero, put carry
+		hit
 	}
 
-	pub fn draw_screen(&mut self, window: &mut PistonWindow, _: &RenderArgs, input: &Event) -> () {
-		let mut colours: Vec<[u8;4]> = Vec::with_capacity(16);
-		for p in self.palette.iter() {
-			let v: [u8; 4] = [ //Transforms the palette into something Piston accepts
-				((p & 0xFF0000) >> 16) as u8,
-				((p & 0x00FF00) >> 8) as u8,
-				(p & 0x0000FF) as u8,
-				255,];
-			colours.push(v);
+	//Flips mirror the sprite inside its own box, clipping is done afterwards so a sprite hanging off
+	//any edge keeps the part that is still on screen. The visible rectangle is worked out once and
+	//each visible row is a single span of the screen. Sprite data wraps around memory
+	fn draw_sprite(&mut self, mem: &mut Memory, spr_x: i16, spr_y: i16, spr_address: i16) -> bool {
+		let spritew = self.state.spritew as usize; //In bytes, two pixels each
+		let spriteh = self.state.spriteh as i32;
+		if let Some(ref mut coverage) = mem.coverage { //The whole sprite is data, even the part off screen
+			for i in 0..spritew * spriteh as usize {
+				coverage.read(spr_address as u16 as usize + i);
+			}
+		}
+		let width = spritew as i32 * 2;
+		let spr_x = spr_x as i32;
+		let spr_y = spr_y as i32;
+		let x0 = spr_x.max(0);
+		let x1 = (spr_x + width).min(320);
+		let y0 = spr_y.max(0);
+		let y1 = (spr_y + spriteh).min(240);
+		if x0 >= x1 || y0 >= y1 { //If nothing is to be drawn, return
+			return false;
 		}
 
-		let screen = self.screen.iter();
-		let size = self.size as u32;
-		
-		let mut buffer_image = image::ImageBuffer::new(320 * size, 240 * size);
-		
-		for (pixel, i) in screen.zip(0..76800u32) {
-			let y: u32 = (i / 320) as u32;
-			let x: u32 = (i % 320) as u32;
-			for j in 0..size {
-				for k in 0..size {
-					buffer_image.put_pixel(size * x + j, size * y + k,
-						image::Rgba(if *pixel == 0 {
-							colours[self.state.bg as usize]
-						} else {
-							colours[*pixel as usize]
-					}));
-				}
+		//The sprite columns that end up between x0 and x1, counted from the right when flipped
+		let first_column = if self.state.hflip { width - (x1 - spr_x) } else { x0 - spr_x } as usize;
+		let columns = (x1 - x0) as usize;
+		let memory = mem.as_slice();
+		let mut pixels = [0u8; 510];
+		let mut hit = 0u8;
+		for screen_y in y0..y1 {
+			let row = if self.state.vflip { spriteh - 1 - (screen_y - spr_y) } else { screen_y - spr_y } as usize;
+			let start = spr_address as u16 as usize + row * spritew;
+			for (pair, i) in pixels[..spritew * 2].chunks_exact_mut(2).zip(start..) {
+				let byte = memory[i & 0xFFFF] as u8;
+				pair[0] = byte >> 4;
+				pair[1] = byte & 0xF;
 			}
+
+			let source = &pixels[first_column..first_column + columns];
+			let span = &mut self.screen[(screen_y * 320 + x0) as usize..(screen_y * 320 + x1) as usize];
+			hit |= if self.state.hflip {
+				draw_span(span.iter_mut(), source.iter().rev())
+			} else {
+				draw_span(span.iter_mut(), source.iter())
+			};
 		}
-		
-		let texture = Texture::from_image(
-			&mut window.create_texture_context(),
-			&buffer_image,
-			&TextureSettings::new(),
-		).unwrap();
-		
-		window.draw_2d(input, |_c, g, _| {
-			image(&texture, _c.transform, g);
-		});
-		
+		hit != 0 //If different than zero, put carry
 	}
 }
 
+//Copies the non transparent pixels of a sprite row, returns the non zero pixels drawn over
+fn draw_span<'a, 'b, I, S>(span: I, source: S) -> u8
+	where I: Iterator<Item = &'a mut u8>, S: Iterator<Item = &'b u8>
+{
+	let mut hit = 0u8;
+	for (pixel, colour) in span.zip(source) {
+		let drawn = ((*colour != 0) as u8).wrapping_neg(); //0xFF unless transparent, then nothing changes
+		hit |= *pixel & drawn;
+		*pixel = (*pixel & !drawn) | *colour;
+	}
+	hit
+}
+
 impl Cpu {
-	pub fn new(file_path: &Path, multiplier: u32) -> Cpu {
-		let mut file = match File::open(&file_path) {
-		    Ok(file) => file,
-   		    Err(e) => panic!("{} {}", e.to_string(), file_path.display()),
-		};
-		
-		let mut cpu = Cpu {pc: 0, sp: 0xFDF0, rx: [0; 16], flags: 0,
-			vblank: false, graphics: Chip16Graphics::new(multiplier),
-			memory: Memory::new(),
-		};
-		let ext = file_path.extension().unwrap();
-		match ext.to_str() {
-			Some("bin") => load_bin(&mut file, &mut cpu),
-			Some("c16") => load_c16(&mut file, &mut cpu),
-			_ => panic!("The file is not a valid extension"),
+	pub fn new(file_path: &Path) -> Cpu {
+		match Cpu::load(file_path) {
+			Ok(cpu) => cpu,
+			Err(e) => panic!("{}", e),
 		}
-		cpu
 	}
 	
-	#[allow(dead_code)]
+	pub fn load(file_path: &Path) -> Result<Cpu, String> {
+		let mut cpu = Cpu::new_test();
+		loading::load(file_path, &mut cpu)?;
+		Ok(cpu)
+	}
+	
 	pub fn new_test() -> Cpu {// "Virgin" cpu for testing
-		Cpu {pc: 0, sp: 0xFDF0, rx: [0; 16], flags: 0,
-			vblank: false, graphics: Chip16Graphics::new_test(),
-			memory: Memory::new(),
+		Cpu {pc: 0, start: 0, sp: 0xFDF0, rx: [0; 16], flags: 0,
+			vblank: false, graphics: Chip16Graphics::new(),
+			memory: Memory::new(), sounds: Vec::new(),
+			rng: StdRng::from_os_rng(), trace: None, trace_error: None, busy: 0,
+			calls: Vec::new(), cheats: Vec::new(), symbols: Symbols::new(),
+			breakpoints: Vec::new(), break_hit: None, halted: None, skip_break: false, profile: None, frame_step: 0,
+		}
+	}
+	
+	//Runs the program again from the start, memory keeps what it has like after a reset button
+	pub fn soft_reset(&mut self) -> () {
+		self.pc = self.start;
+		self.sp = 0xFDF0;
+		self.rx = [0; 16];
+		self.flags = 0;
+		self.vblank = false;
+		self.sounds.clear();
+		self.calls.clear();
+		self.break_hit = None;
+		self.halted = None;
+		self.skip_break = false;
+		self.frame_step = 0;
+		self.graphics.clear();
+		self.graphics.state = StateRegister::new();
+		if let Some(ref mut log) = self.graphics.draw_log {
+			log.clear();
 		}
 	}
 	
+	pub fn seed(&mut self, seed: u64) -> () {
+		self.rng = StdRng::seed_from_u64(seed);
+	}
+	
 	#[allow(dead_code)]
 	pub fn add_opcode(&mut self, op: opcode::Opcode, byte1: i8, byte2: i8, byte3: i8) -> () {
 		let pc = self.pc as usize; //Ability to add instructions for testing
@@ -280,7 +394,7 @@ impl Cpu {
 	pub fn start_test(&mut self, instructions_to_execute: i8) -> () {
 		self.pc = 0; //Ability to specify how many opcodes you want executed, for testing
 		for _ in 0..instructions_to_execute {
-			self.step()
+			self.step().unwrap();
 		}
 	}
 	
@@ -335,6 +449,22 @@ impl Cpu {
 		self.sp = self.sp + 2;
 	}
 	
+	//Frames at or above sp were returned from or overwritten, programs can move sp without a RET
+	fn drop_calls_above_sp(&mut self) -> () {
+		let sp = self.sp;
+		self.calls.retain(|frame| frame.slot < sp);
+	}
+	
+	pub fn enter_call(&mut self, from: u16, target: u16) -> () {
+		self.drop_calls_above_sp();
+		let sp = self.sp;
+		self.calls.push(CallFrame { slot: sp, from: from, target: target });
+	}
+	
+	pub fn leave_call(&mut self) -> () {
+		self.drop_calls_above_sp();
+	}
+	
 	pub fn pushall(&mut self) -> () {
 		let vec = self.rx;
 		for rx in vec.iter() {
@@ -412,8 +542,8 @@ impl Cpu {
 		}
 	}
 	
-	pub fn check_flags(&self, index: i8) -> bool {
-		match index {
+	pub fn check_flags(&self, index: i8) -> Result<bool, String> {
+		Ok(match index {
 			0 => self.has_zero(),
 			1 => !self.has_zero(),
 			2 => self.has_negative(),
@@ -429,98 +559,92 @@ impl Cpu {
 			0xC => self.has_overflow() == self.has_negative(),
 			0xD => self.has_overflow() != self.has_negative(),
 			0xE => (self.has_overflow() != self.has_negative()) || self.has_zero(),
-			_ => panic!("Failed to find flag: {}", index),
-		}
+			_ => return Err(format!("{:X} is not a condition", index)),
+		})
 	}
 	
-	pub fn step(&mut self) -> () {
+	//An instruction that can't run leaves PC on it and says why
+	pub fn step(&mut self) -> Result<(), String> {
 		if self.pc >= 0xFFFC {
-			panic!("The instruction pointer has run out of memory to read");
+			return Err(format!("PC got to #{:04X}, there is no instruction left in memory", self.pc));
 		}
 		let pc = self.pc as usize;
-		let op = self.memory.read_byte(pc);
-
-		let op: opcode::Opcode = to_opcode(op);
-		let byte1 = self.memory.read_byte(pc + 1);
-		let byte2 = self.memory.read_byte(pc + 2);
-		let byte3 = self.memory.read_byte(pc + 3);
+		let failed = match self.trace {
+			Some(ref mut trace) => writeln!(trace, "{}", disasm::labelled_line(&self.memory, self.pc, &self.symbols)).err(),
+			None => None,
+		};
+		if let Some(e) = failed { //The program goes on without it
+			self.trace = None;
+			self.trace_error = Some(format!("Couldn't write the trace: {}", e));
+		}
+		let [op, byte1, byte2, byte3] = self.memory.fetch(pc);
+		let op: opcode::Opcode = to_opcode(op).map_err(|e| format!("{} at #{:04X}", e, pc))?;
+		if let Some(ref mut profile) = self.profile {
+			profile.record(self.pc, op == opcode::Opcode::Vblnk && !self.vblank, &self.calls);
+		}
 		self.pc = self.pc + 4;
-		op.execute(self, byte1, byte2, byte3);
+		if let Err(e) = op.execute(self, byte1, byte2, byte3) {
+			self.pc = pc as u16;
+			return Err(format!("{} at #{:04X}", e, pc));
+		}
+		Ok(())
 	}
 
-	pub fn start_program(&mut self, mut window: &mut PistonWindow) -> () {
-		let mut vblank_dt: u64 = 0;
-		let mut controller1: u16 = 0;
-		let mut controller2: u16 = 0;
-		while let Some(e) = window.next() {
-			
-			if let Some(u) = e.update_args() {
-				if vblank_dt < 16666 {
-					for _ in 0..(u.dt * 1000000.0) as u64 {
-						self.step();
-						self.vblank = false;
-					}
-					vblank_dt += (u.dt * 1000000.0) as u64;
-				}
-			}
-			
-			if let Some(r) = e.render_args() {
-				if vblank_dt >= 16666 {
-					self.graphics.draw_screen(&mut window, &r, &e);
-					self.vblank = true;
-					vblank_dt -= 16666;
-					self.memory.write_word(0xFFF0, controller1 as i16);
-					self.memory.write_word(0xFFF2, controller2 as i16);
-				}
-			}
+	pub fn run_frame<I, V, A>(&mut self, input: &mut I, video: &mut V, audio: &mut A) -> ()
+		where I: InputSource, V: VideoSink, A: AudioSink {
+		self.run_for(INSTRUCTIONS_PER_FRAME, input, video, audio);
+	}
 
-			if let Some(Button::Keyboard(key)) = e.press_args() {
-				match key {
-					Key::NumPad7 => controller1 |= Pad::A as u16,//A1
-					Key::NumPad9 => controller1 |= Pad::B as u16,//B1
-					Key::Right => controller1 |= Pad::Right as u16,//Right1
-					Key::Up => controller1 |= Pad::Up as u16,//Up1
-					Key::Down => controller1 |= Pad::Down as u16,//Down1
-					Key::Left => controller1 |= Pad::Left as u16,//Left1
-					Key::RShift => controller1 |= Pad::Select as u16,//Select1
-					Key::Return => controller1 |= Pad::Start as u16,//Start1
-				
-					Key::H => controller2 |= Pad::A as u16,//A2
-					Key::J => controller2 |= Pad::B as u16,//B2
-					Key::D => controller2 |= Pad::Right as u16,//Right2
-					Key::W => controller2 |= Pad::Up as u16,//Up2
-					Key::S => controller2 |= Pad::Down as u16,//Down2
-					Key::A => controller2 |= Pad::Left as u16,//Left2
-					Key::LCtrl => controller2 |= Pad::Select as u16,//Select2
-					Key::Space => controller2 |= Pad::Start as u16,//Start2
-				
-					_ => {},
-				}
+	//Up to count instructions of the frame, the vblank comes when they finish it
+	pub fn run_for<I, V, A>(&mut self, count: u32, input: &mut I, video: &mut V, audio: &mut A) -> ()
+		where I: InputSource, V: VideoSink, A: AudioSink {
+		if self.frame_step == 0 {
+			self.memory.clear_written();
+			self.busy = 0;
+		}
+		self.break_hit = None;
+		if self.halted.is_some() {
+			return;
+		}
+		let end = self.frame_step.saturating_add(count).min(INSTRUCTIONS_PER_FRAME);
+		while self.frame_step < end {
+			let pc = self.pc;
+			if !self.skip_break && self.breakpoints.contains(&pc) {
+				self.break_hit = Some(pc); //The rest of the frame runs on the next call
+				self.skip_break = true; //Starting with the instruction it stopped at
+				return;
 			}
-			
-			if let Some(Button::Keyboard(key)) = e.release_args() {
-				match key {
-					Key::NumPad7 => controller1 &= !(Pad::A as u16),//A1
-					Key::NumPad9 => controller1 &= !(Pad::B as u16),//B1
-					Key::Right => controller1 &= !(Pad::Right as u16),//Right1
-					Key::Up => controller1 &= !(Pad::Up as u16),//Up1
-					Key::Down => controller1 &= !(Pad::Down as u16),//Down1
-					Key::Left => controller1 &= !(Pad::Left as u16),//Left1
-					Key::RShift => controller1 &= !(Pad::Select as u16),//Select1
-					Key::Return => controller1 &= !(Pad::Start as u16),//Start1
-				
-					Key::H => controller2 &= !(Pad::A as u16),//A2
-					Key::J => controller2 &= !(Pad::B as u16),//B2
-					Key::D => controller2 &= !(Pad::Right as u16),//Right2
-					Key::W => controller2 &= !(Pad::Up as u16),//Up2
-					Key::S => controller2 &= !(Pad::Down as u16),//Down2
-					Key::A => controller2 &= !(Pad::Left as u16),//Left2
-					Key::LCtrl => controller2 &= !(Pad::Select as u16),//Select2
-					Key::Space => controller2 &= !(Pad::Start as u16),//Start2
-				
-					_ => {},
-				}
+			if let Err(e) = self.step() {
+				self.halted = Some(e);
+				return;
 			}
+			self.frame_step += 1;
+			self.vblank = false;
+			self.skip_break = self.pc == pc; //VBLNK waits by running itself again, it only breaks once
+			if self.pc != pc {
+				self.busy += 1;
+			}
+		}
+		if self.frame_step < INSTRUCTIONS_PER_FRAME {
+			return;
+		}
+		self.frame_step = 0;
+		if let Some(ref mut profile) = self.profile {
+			profile.frames += 1;
+		}
+		for sound in self.sounds.drain(..) {
+			audio.play(&sound);
+		}
+		video.present(&self.graphics);
+		if let Some(ref mut log) = self.graphics.draw_log {
+			log.clear();
+		}
+		self.vblank = true;
+		let (controller1, controller2) = input.controllers();
+		self.memory.write_word(0xFFF0, controller1 as i16);
+		self.memory.write_word(0xFFF2, controller2 as i16);
+		for cheat in self.cheats.iter() {
+			cheat.apply(&mut self.memory);
 		}
 	}
 }
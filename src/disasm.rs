@@ -0,0 +1,80 @@
+use cpu::Memory;
+use opcode::Opcode;
+use opcode::Opcode::*;
+use symbols::Symbols;
+
+//Jx and Cx conditions in the order of their index
+const CONDITIONS: [&str; 16] = ["z", "nz", "n", "nn", "p", "o", "no", "a", "ae", "b", "be", "g", "ge", "l", "le", "x"];
+
+fn mnemonic(op: Opcode) -> String { //Add2 and Add are both add, they only differ in operands
+	let name = format!("{:?}", op).to_lowercase();
+	name.trim_end_matches('2').to_string()
+}
+
+//One instruction in the syntax of the spec, None when the first byte isn't an opcode.
+//Jumps, calls and memory addresses show as labels when there is one
+pub fn instruction(bytes: [u8; 4], symbols: &Symbols) -> Option<String> {
+	let op = Opcode::from_byte(bytes[0])?;
+	let x = bytes[1] & 0xF;
+	let y = bytes[1] >> 4;
+	let z = bytes[2] & 0xF;
+	let hhll = ((bytes[3] as u16) << 8) | bytes[2] as u16;
+	let address = symbols.operand(hhll);
+	let text = match op {
+		Nop | Cls | Vblnk | Snd0 | Ret | Pushall | Popall | Pushf | Popf => mnemonic(op),
+		Bgc => format!("bgc {}", z),
+		Spr | Snd1 | Snd2 | Snd3 => format!("{} #{:04X}", format!("{:?}", op).to_lowercase(), hhll),
+		Jmp | Jmc | Call | Pal => format!("{} {}", format!("{:?}", op).to_lowercase(), address),
+		Drw => format!("drw r{:X}, r{:X}, {}", x, y, address),
+		Drw2 => format!("drw r{:X}, r{:X}, r{:X}", x, y, z),
+		Flip => format!("flip {}, {}", (bytes[3] > 1) as u8, bytes[3] & 1),
+		Snp => format!("snp r{:X}, {}", x, address),
+		Sng => format!("sng #{:02X}, #{:02X}{:02X}", bytes[1], bytes[2], bytes[3]),
+		Jx => format!("j{} {}", CONDITIONS[x as usize], address),
+		Cx => format!("c{} {}", CONDITIONS[x as usize], address),
+		Jme => format!("jme r{:X}, r{:X}, {}", x, y, address),
+		Ldi2 => format!("ldi sp, #{:04X}", hhll),
+		Ldm | Stm => format!("{} r{:X}, {}", mnemonic(op), x, address),
+		Rnd | Ldi | Addi | Subi | Cmpi | Andi | Tsti | Ori | Xori | Muli | Divi | Modi | Remi | Noti | Negi =>
+			format!("{} r{:X}, #{:04X}", mnemonic(op), x, hhll),
+		Ldm2 | Mov | Stm2 | Add | Sub | Cmp | And | Tst | Or | Xor | Mul | Div | Mod | Rem | Shl2 | Shr2 | Sar2 | Not2 | Neg2 =>
+			format!("{} r{:X}, r{:X}", mnemonic(op), x, y),
+		Add2 | Sub2 | And2 | Or2 | Xor2 | Mul2 | Div2 | Mod2 | Rem2 => format!("{} r{:X}, r{:X}, r{:X}", mnemonic(op), x, y, z),
+		Shl | Shr | Sar => format!("{} r{:X}, {}", mnemonic(op), x, z),
+		Jmp2 | Call2 | Push | Pop | Pal2 | Not | Neg => format!("{} r{:X}", mnemonic(op), x),
+	};
+	Some(text)
+}
+
+//ADDRESS  BYTES  INSTRUCTION, bytes that aren't an instruction show as db
+pub fn line(memory: &Memory, address: u16, symbols: &Symbols) -> String {
+	let mut bytes = [0u8; 4];
+	for (i, byte) in bytes.iter_mut().enumerate() {
+		*byte = memory.peek(address.wrapping_add(i as u16) as usize) as u8;
+	}
+	let text = match instruction(bytes, symbols) {
+		Some(text) => text,
+		None => format!("db #{:02X}, #{:02X}, #{:02X}, #{:02X}", bytes[0], bytes[1], bytes[2], bytes[3]),
+	};
+	format!("{:04X}  {:02X} {:02X} {:02X} {:02X}  {}", address, bytes[0], bytes[1], bytes[2], bytes[3], text)
+}
+
+//A line for an address, after a :label line when it has one like in the source
+pub fn labelled_line(memory: &Memory, address: u16, symbols: &Symbols) -> String {
+	match symbols.name(address) {
+		Some(label) => format!(":{}\n{}", label, line(memory, address, symbols)),
+		None => line(memory, address, symbols),
+	}
+}
+
+//Every instruction from start to start + length, a line each
+pub fn listing(memory: &Memory, start: u16, length: u32, symbols: &Symbols) -> String {
+	let mut text = String::new();
+	let mut offset = 0;
+	while offset < length {
+		text.push_str(&labelled_line(memory, (start as u32 + offset) as u16, symbols));
+		text.push('\n');
+		offset += 4;
+	}
+	text
+}
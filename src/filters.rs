@@ -0,0 +1,364 @@
+use image::{Rgba, RgbaImage};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+	None,
+	Nearest(u32),
+	Bilinear(u32),
+	Scanlines,
+	Crt,
+	Scale2x,
+	Scale3x,
+	Hq2x,
+}
+
+impl Filter {
+	pub fn from_name(name: &str) -> Option<Filter> {
+		match name {
+			"none" => Some(Filter::None),
+			"nearest" => Some(Filter::Nearest(2)),
+			"bilinear" => Some(Filter::Bilinear(2)),
+			"scanlines" => Some(Filter::Scanlines),
+			"crt" => Some(Filter::Crt),
+			"scale2x" => Some(Filter::Scale2x),
+			"scale3x" => Some(Filter::Scale3x),
+			"hq2x" => Some(Filter::Hq2x),
+			_ => None,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Filter::None => "none",
+			Filter::Nearest(_) => "nearest",
+			Filter::Bilinear(_) => "bilinear",
+			Filter::Scanlines => "scanlines",
+			Filter::Crt => "crt",
+			Filter::Scale2x => "scale2x",
+			Filter::Scale3x => "scale3x",
+			Filter::Hq2x => "hq2x",
+		}
+	}
+
+	pub fn next(&self) -> Filter {
+		match *self {
+			Filter::None => Filter::Bilinear(2),
+			Filter::Nearest(_) => Filter::Bilinear(2),
+			Filter::Bilinear(_) => Filter::Scanlines,
+			Filter::Scanlines => Filter::Crt,
+			Filter::Crt => Filter::Scale2x,
+			Filter::Scale2x => Filter::Scale3x,
+			Filter::Scale3x => Filter::Hq2x,
+			Filter::Hq2x => Filter::None,
+		}
+	}
+
+	pub fn factor(&self) -> u32 { //How many output pixels a source pixel becomes on each side
+		match *self {
+			Filter::None => 1,
+			Filter::Nearest(factor) | Filter::Bilinear(factor) => factor.max(1),
+			Filter::Scanlines | Filter::Scale2x | Filter::Hq2x => 2,
+			Filter::Crt | Filter::Scale3x => 3,
+		}
+	}
+
+	pub fn apply(&self, src: &RgbaImage) -> RgbaImage {
+		let mut out = RgbaImage::new(0, 0);
+		self.apply_into(src, &mut out);
+		out
+	}
+
+	//Like apply, reusing out so a frontend doesn't allocate every frame
+	pub fn apply_into(&self, src: &RgbaImage, out: &mut RgbaImage) -> () {
+		let factor = self.factor();
+		if out.dimensions() != (src.width() * factor, src.height() * factor) {
+			*out = RgbaImage::new(src.width() * factor, src.height() * factor);
+		}
+		match *self {
+			Filter::None => out.copy_from_slice(src),
+			Filter::Nearest(factor) => nearest_into(src, factor.max(1), out),
+			Filter::Bilinear(factor) => bilinear_into(src, factor.max(1), out),
+			Filter::Scanlines => scanlines_into(src, out),
+			Filter::Crt => crt_into(src, out),
+			Filter::Scale2x => scale2x_into(src, out),
+			Filter::Scale3x => scale3x_into(src, out),
+			Filter::Hq2x => hq2x_into(src, out),
+		}
+	}
+}
+
+//Every pixel of out from its coordinates
+fn fill<F: Fn(u32, u32) -> Rgba<u8>>(out: &mut RgbaImage, pixel: F) -> () {
+	for (x, y, out) in out.enumerate_pixels_mut() {
+		*out = pixel(x, y);
+	}
+}
+
+//The filters on their own give a new image
+fn scaled<F: Fn(&RgbaImage, &mut RgbaImage)>(src: &RgbaImage, factor: u32, filter: F) -> RgbaImage {
+	let mut out = RgbaImage::new(src.width() * factor, src.height() * factor);
+	filter(src, &mut out);
+	out
+}
+
+fn at(src: &RgbaImage, x: i64, y: i64) -> Rgba<u8> { //Clamps to the edges
+	let x = x.max(0).min(src.width() as i64 - 1) as u32;
+	let y = y.max(0).min(src.height() as i64 - 1) as u32;
+	*src.get_pixel(x, y)
+}
+
+fn darken(pixel: Rgba<u8>, numerator: u32, denominator: u32) -> Rgba<u8> {
+	Rgba([
+		(pixel[0] as u32 * numerator / denominator) as u8,
+		(pixel[1] as u32 * numerator / denominator) as u8,
+		(pixel[2] as u32 * numerator / denominator) as u8,
+		pixel[3],
+	])
+}
+
+pub fn nearest(src: &RgbaImage, factor: u32) -> RgbaImage {
+	scaled(src, factor, |src, out| nearest_into(src, factor, out))
+}
+
+fn nearest_into(src: &RgbaImage, factor: u32, out: &mut RgbaImage) -> () {
+	fill(out, |x, y| *src.get_pixel(x / factor, y / factor));
+}
+
+pub fn bilinear(src: &RgbaImage, factor: u32) -> RgbaImage {
+	scaled(src, factor, |src, out| bilinear_into(src, factor, out))
+}
+
+fn bilinear_into(src: &RgbaImage, factor: u32, out: &mut RgbaImage) -> () {
+	fill(out, |x, y| {
+		//Sample at the centre of the output pixel, in source coordinates
+		let sx = (x as f64 + 0.5) / factor as f64 - 0.5;
+		let sy = (y as f64 + 0.5) / factor as f64 - 0.5;
+		let x0 = sx.floor();
+		let y0 = sy.floor();
+		let fx = sx - x0;
+		let fy = sy - y0;
+		let (x0, y0) = (x0 as i64, y0 as i64);
+		let tl = at(src, x0, y0);
+		let tr = at(src, x0 + 1, y0);
+		let bl = at(src, x0, y0 + 1);
+		let br = at(src, x0 + 1, y0 + 1);
+		let mut out = [0u8; 4];
+		for c in 0..4 {
+			let top = tl[c] as f64 * (1.0 - fx) + tr[c] as f64 * fx;
+			let bottom = bl[c] as f64 * (1.0 - fx) + br[c] as f64 * fx;
+			out[c] = (top * (1.0 - fy) + bottom * fy).round() as u8;
+		}
+		Rgba(out)
+	});
+}
+
+pub fn scanlines(src: &RgbaImage) -> RgbaImage {
+	scaled(src, 2, scanlines_into)
+}
+
+fn scanlines_into(src: &RgbaImage, out: &mut RgbaImage) -> () {
+	fill(out, |x, y| {
+		let pixel = *src.get_pixel(x / 2, y / 2);
+		if y % 2 == 1 {
+			darken(pixel, 1, 2)
+		} else {
+			pixel
+		}
+	});
+}
+
+//Barrel distortion, an RGB aperture grille and scanlines on a 3x image
+pub fn crt(src: &RgbaImage) -> RgbaImage {
+	scaled(src, 3, crt_into)
+}
+
+fn crt_into(src: &RgbaImage, out: &mut RgbaImage) -> () {
+	let width = src.width() * 3;
+	let height = src.height() * 3;
+	const CURVATURE: f64 = 0.08;
+	fill(out, |x, y| {
+		let u = (x as f64 + 0.5) / width as f64 * 2.0 - 1.0; //-1 to 1 from the centre
+		let v = (y as f64 + 0.5) / height as f64 * 2.0 - 1.0;
+		let r2 = u * u + v * v;
+		let u = u * (1.0 + CURVATURE * r2);
+		let v = v * (1.0 + CURVATURE * r2);
+		if u.abs() > 1.0 || v.abs() > 1.0 {
+			return Rgba([0, 0, 0, 255]);
+		}
+		let sx = ((u + 1.0) / 2.0 * src.width() as f64) as i64;
+		let sy = ((v + 1.0) / 2.0 * src.height() as f64) as i64;
+		let pixel = at(src, sx, sy);
+
+		let mut out = darken(pixel, 3, 4); //Every channel keeps some light so colours don't go dark
+		let lit = (x % 3) as usize; //The sub pixel column of the grille
+		out[lit] = pixel[lit];
+		if y % 3 == 2 {
+			out = darken(out, 2, 3);
+		}
+		out
+	});
+}
+
+pub fn scale2x(src: &RgbaImage) -> RgbaImage {
+	scaled(src, 2, scale2x_into)
+}
+
+fn scale2x_into(src: &RgbaImage, out: &mut RgbaImage) -> () {
+	for y in 0..src.height() {
+		for x in 0..src.width() {
+			let (xi, yi) = (x as i64, y as i64);
+			let b = at(src, xi, yi - 1);
+			let d = at(src, xi - 1, yi);
+			let e = at(src, xi, yi);
+			let f = at(src, xi + 1, yi);
+			let h = at(src, xi, yi + 1);
+			let e0 = if d == b && b != f && d != h { d } else { e };
+			let e1 = if b == f && b != d && f != h { f } else { e };
+			let e2 = if d == h && d != b && h != f { d } else { e };
+			let e3 = if h == f && d != h && b != f { f } else { e };
+			out.put_pixel(x * 2, y * 2, e0);
+			out.put_pixel(x * 2 + 1, y * 2, e1);
+			out.put_pixel(x * 2, y * 2 + 1, e2);
+			out.put_pixel(x * 2 + 1, y * 2 + 1, e3);
+		}
+	}
+}
+
+pub fn scale3x(src: &RgbaImage) -> RgbaImage {
+	scaled(src, 3, scale3x_into)
+}
+
+fn scale3x_into(src: &RgbaImage, out: &mut RgbaImage) -> () {
+	for y in 0..src.height() {
+		for x in 0..src.width() {
+			let (xi, yi) = (x as i64, y as i64);
+			let a = at(src, xi - 1, yi - 1);
+			let b = at(src, xi, yi - 1);
+			let c = at(src, xi + 1, yi - 1);
+			let d = at(src, xi - 1, yi);
+			let e = at(src, xi, yi);
+			let f = at(src, xi + 1, yi);
+			let g = at(src, xi - 1, yi + 1);
+			let h = at(src, xi, yi + 1);
+			let i = at(src, xi + 1, yi + 1);
+			let mut block = [e; 9];
+			if b != h && d != f {
+				block[0] = if d == b { d } else { e };
+				block[1] = if (d == b && e != c) || (b == f && e != a) { b } else { e };
+				block[2] = if b == f { f } else { e };
+				block[3] = if (d == b && e != g) || (d == h && e != a) { d } else { e };
+				block[5] = if (b == f && e != i) || (h == f && e != c) { f } else { e };
+				block[6] = if d == h { d } else { e };
+				block[7] = if (d == h && e != i) || (h == f && e != g) { h } else { e };
+				block[8] = if h == f { f } else { e };
+			}
+			for (n, pixel) in block.iter().enumerate() {
+				out.put_pixel(x * 3 + n as u32 % 3, y * 3 + n as u32 / 3, *pixel);
+			}
+		}
+	}
+}
+
+//The blending rule of the top left quarter for every pattern of neighbours that differ from the centre, bit 0 for A up to bit 7 for I.
+//The other quarters use it on the pattern turned by ROTATE, as in byuu's compact form of Maxim Stepin's hq2x
+const HQ2X_RULES: [u8; 256] = [
+	4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 15, 12, 5, 3, 17, 13,
+	4, 4, 6, 18, 4, 4, 6, 18, 5, 3, 12, 12, 5, 3, 1, 12,
+	4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 17, 13, 5, 3, 16, 14,
+	4, 4, 6, 18, 4, 4, 6, 18, 5, 3, 16, 12, 5, 3, 1, 14,
+	4, 4, 6, 2, 4, 4, 6, 2, 5, 19, 12, 12, 5, 19, 16, 12,
+	4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 16, 12, 5, 3, 16, 12,
+	4, 4, 6, 2, 4, 4, 6, 2, 5, 19, 1, 12, 5, 19, 1, 14,
+	4, 4, 6, 2, 4, 4, 6, 18, 5, 3, 16, 12, 5, 19, 1, 14,
+	4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 15, 12, 5, 3, 17, 13,
+	4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 16, 12, 5, 3, 16, 12,
+	4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 17, 13, 5, 3, 16, 14,
+	4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 16, 13, 5, 3, 1, 14,
+	4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 16, 12, 5, 3, 16, 13,
+	4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 16, 12, 5, 3, 1, 12,
+	4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 16, 12, 5, 3, 1, 14,
+	4, 4, 6, 2, 4, 4, 6, 2, 5, 3, 1, 12, 5, 3, 1, 14,
+];
+
+//A pattern seen from the next quarter clockwise, where C takes the place of A
+fn rotate(n: u8) -> u8 {
+	((n >> 2) & 0x11) | ((n << 2) & 0x88) | ((n & 0x01) << 5) | ((n & 0x08) << 3) | ((n & 0x10) >> 3) | ((n & 0x80) >> 5)
+}
+
+//Colours are different when they are far apart in YUV, like hq2x compares them
+fn differ(a: Rgba<u8>, b: Rgba<u8>) -> bool {
+	let yuv = |p: Rgba<u8>| {
+		let (r, g, b) = (p[0] as i32, p[1] as i32, p[2] as i32);
+		((r + g + b) >> 2, (r - b) >> 2, (-r + 2 * g - b) >> 3)
+	};
+	let ((y1, u1, v1), (y2, u2, v2)) = (yuv(a), yuv(b));
+	(y1 - y2).abs() > 0x30 || (u1 - u2).abs() > 0x07 || (v1 - v2).abs() > 0x06
+}
+
+//Weights for e and the other colours in sixteenths
+fn mix(e: Rgba<u8>, weight: u32, others: &[(Rgba<u8>, u32)]) -> Rgba<u8> {
+	let mut out = [0u8; 4];
+	for c in 0..4 {
+		let sum = others.iter().fold(e[c] as u32 * weight, |sum, &(pixel, w)| sum + pixel[c] as u32 * w);
+		out[c] = (sum / 16) as u8;
+	}
+	Rgba(out)
+}
+
+//A quarter of the output by its rule, a is the corner it sits in, b and d the sides next to it, f and h the sides away from it
+fn hq2x_quarter(rule: u8, e: Rgba<u8>, a: Rgba<u8>, b: Rgba<u8>, d: Rgba<u8>, f: Rgba<u8>, h: Rgba<u8>) -> Rgba<u8> {
+	let corner = mix(e, 12, &[(a, 4)]);
+	match rule {
+		1 => corner,
+		2 => mix(e, 12, &[(d, 4)]),
+		3 => mix(e, 12, &[(b, 4)]),
+		4 => mix(e, 8, &[(d, 4), (b, 4)]),
+		5 => mix(e, 8, &[(a, 4), (b, 4)]),
+		6 => mix(e, 8, &[(a, 4), (d, 4)]),
+		12 | 15 if !differ(b, d) => mix(e, 8, &[(d, 4), (b, 4)]),
+		13 | 17 if !differ(b, d) => mix(e, 4, &[(d, 6), (b, 6)]),
+		14 if !differ(b, d) => mix(e, 14, &[(d, 1), (b, 1)]),
+		16 if !differ(b, d) => mix(e, 12, &[(d, 2), (b, 2)]),
+		15..=17 => corner,
+		18 if !differ(b, f) => mix(e, 10, &[(b, 4), (d, 2)]),
+		18 => mix(e, 12, &[(d, 4)]),
+		19 if !differ(d, h) => mix(e, 10, &[(d, 4), (b, 2)]),
+		19 => mix(e, 12, &[(b, 4)]),
+		_ => e,
+	}
+}
+
+//Smooths edges by blending each quarter of a pixel with the neighbours that look like part of the same shape
+pub fn hq2x(src: &RgbaImage) -> RgbaImage {
+	scaled(src, 2, hq2x_into)
+}
+
+fn hq2x_into(src: &RgbaImage, out: &mut RgbaImage) -> () {
+	for y in 0..src.height() {
+		for x in 0..src.width() {
+			let (xi, yi) = (x as i64, y as i64);
+			let a = at(src, xi - 1, yi - 1);
+			let b = at(src, xi, yi - 1);
+			let c = at(src, xi + 1, yi - 1);
+			let d = at(src, xi - 1, yi);
+			let e = at(src, xi, yi);
+			let f = at(src, xi + 1, yi);
+			let g = at(src, xi - 1, yi + 1);
+			let h = at(src, xi, yi + 1);
+			let i = at(src, xi + 1, yi + 1);
+			let mut pattern = [a, b, c, d, f, g, h, i].iter().enumerate()
+				.fold(0u8, |pattern, (bit, &pixel)| pattern | ((differ(e, pixel) as u8) << bit));
+			let top_left = hq2x_quarter(HQ2X_RULES[pattern as usize], e, a, b, d, f, h);
+			pattern = rotate(pattern);
+			let top_right = hq2x_quarter(HQ2X_RULES[pattern as usize], e, c, f, b, h, d);
+			pattern = rotate(pattern);
+			let bottom_right = hq2x_quarter(HQ2X_RULES[pattern as usize], e, i, h, f, d, b);
+			pattern = rotate(pattern);
+			let bottom_left = hq2x_quarter(HQ2X_RULES[pattern as usize], e, g, d, h, b, f);
+			out.put_pixel(x * 2, y * 2, top_left);
+			out.put_pixel(x * 2 + 1, y * 2, top_right);
+			out.put_pixel(x * 2 + 1, y * 2 + 1, bottom_right);
+			out.put_pixel(x * 2, y * 2 + 1, bottom_left);
+		}
+	}
+}
@@ -0,0 +1,68 @@
+use cpu::{Chip16Graphics, Cpu, Sound};
+use frontend::{VideoSink, AudioSink, InputSource};
+
+pub struct NullVideo {
+	pub frames: u64,
+}
+
+pub struct NullAudio;
+
+pub struct NullInput {
+	pub controller1: u16,
+	pub controller2: u16,
+}
+
+impl Default for NullVideo {
+	fn default() -> NullVideo {
+		NullVideo::new()
+	}
+}
+
+impl NullVideo {
+	pub fn new() -> NullVideo {
+		NullVideo { frames: 0 }
+	}
+}
+
+impl Default for NullInput {
+	fn default() -> NullInput {
+		NullInput::new()
+	}
+}
+
+impl NullInput {
+	pub fn new() -> NullInput {
+		NullInput { controller1: 0, controller2: 0 }
+	}
+}
+
+impl VideoSink for NullVideo {
+	fn present(&mut self, _: &Chip16Graphics) -> () {
+		self.frames += 1;
+	}
+}
+
+impl AudioSink for NullAudio {
+	fn play(&mut self, _: &Sound) -> () {}
+}
+
+impl InputSource for NullInput {
+	fn controllers(&mut self) -> (u16, u16) {
+		(self.controller1, self.controller2)
+	}
+}
+
+pub fn run(cpu: &mut Cpu, frames: u64) -> () {
+	run_with_video(cpu, frames, &mut NullVideo::new());
+}
+
+//Stops early when the program halts, cpu.halted says why
+pub fn run_with_video<V: VideoSink>(cpu: &mut Cpu, frames: u64, video: &mut V) -> () {
+	let mut input = NullInput::new();
+	for _ in 0..frames {
+		if cpu.halted.is_some() {
+			break;
+		}
+		cpu.run_frame(&mut input, video, &mut NullAudio);
+	}
+}
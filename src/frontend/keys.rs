@@ -0,0 +1,97 @@
+use cpu::Pad;
+use std::path::Path;
+use loading::read_file;
+
+//Controller buttons in the order the bindings keep them, with the names used in key files
+pub const BUTTONS: [(&str, Pad); 8] = [
+	("up", Pad::Up),
+	("down", Pad::Down),
+	("left", Pad::Left),
+	("right", Pad::Right),
+	("select", Pad::Select),
+	("start", Pad::Start),
+	("a", Pad::A),
+	("b", Pad::B),
+];
+
+//The player and the place in BUTTONS of p1.BUTTON or p2.BUTTON
+pub fn button_index(button: &str) -> Result<(usize, usize), String> {
+	let player = if button.starts_with("p1.") {
+		0
+	} else if button.starts_with("p2.") {
+		1
+	} else {
+		return Err(format!("{} is not a button, they go from p1.up to p2.b", button));
+	};
+	match BUTTONS.iter().position(|&(name, _)| name == &button[3..]) {
+		Some(i) => Ok((player, i)),
+		None => Err(format!("{} is not a button, they go from p1.up to p2.b", button)),
+	}
+}
+
+//Key names for both controllers, the frontend turns them into its own keys
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBindings {
+	pub players: [[String; 8]; 2],
+}
+
+impl Default for KeyBindings {
+	fn default() -> KeyBindings {
+		KeyBindings::new()
+	}
+}
+
+impl KeyBindings {
+	pub fn new() -> KeyBindings {
+		KeyBindings {
+			players: [
+				["Up", "Down", "Left", "Right", "RShift", "Return", "NumPad7", "NumPad9"].map(|key| key.to_string()),
+				["W", "S", "A", "D", "LCtrl", "Space", "H", "J"].map(|key| key.to_string()),
+			],
+		}
+	}
+
+	//Lines like p1.up = Up, buttons that aren't listed keep their default key
+	pub fn parse(text: &str) -> Result<KeyBindings, String> {
+		let mut bindings = KeyBindings::new();
+		for line in text.lines() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let mut parts = line.splitn(2, '=');
+			let button = parts.next().unwrap().trim();
+			let key = match parts.next() {
+				Some(key) if !key.trim().is_empty() => key.trim(),
+				_ => return Err(format!("{} has no key, write it as p1.up = Up", line)),
+			};
+			bindings.set(button, key)?;
+		}
+		Ok(bindings)
+	}
+
+	//Binds p1.BUTTON or p2.BUTTON to key
+	pub fn set(&mut self, button: &str, key: &str) -> Result<(), String> {
+		let (player, i) = button_index(button)?;
+		self.players[player][i] = key.to_string();
+		Ok(())
+	}
+
+	pub fn load(path: &Path) -> Result<KeyBindings, String> {
+		let bytes = read_file(path)?;
+		match String::from_utf8(bytes) {
+			Ok(text) => KeyBindings::parse(&text).map_err(|e| format!("{} in {}", e, path.display())),
+			Err(_) => Err(format!("{} is not a text file", path.display())),
+		}
+	}
+
+	pub fn to_text(&self) -> String {
+		let mut text = String::new();
+		for (player, keys) in self.players.iter().enumerate() {
+			for (key, &(button, _)) in keys.iter().zip(BUTTONS.iter()) {
+				text.push_str(&format!("p{}.{} = {}\n", player + 1, button, key));
+			}
+		}
+		text
+	}
+}
@@ -0,0 +1,93 @@
+use cpu::{Chip16Graphics, Sound};
+
+pub mod headless;
+#[cfg(feature = "tools")]
+pub mod keys;
+#[cfg(feature = "tools")]
+pub mod terminal;
+#[cfg(feature = "piston")]
+pub mod piston;
+
+//The core calls these once per frame, a frontend implements the ones it supports
+pub trait VideoSink {
+	fn present(&mut self, graphics: &Chip16Graphics) -> ();
+}
+
+//Frames go to both, like a draw log and a recording
+impl<A: VideoSink, B: VideoSink> VideoSink for (A, B) {
+	fn present(&mut self, graphics: &Chip16Graphics) -> () {
+		self.0.present(graphics);
+		self.1.present(graphics);
+	}
+}
+
+impl<V: VideoSink> VideoSink for Option<V> {
+	fn present(&mut self, graphics: &Chip16Graphics) -> () {
+		if let Some(ref mut video) = *self {
+			video.present(graphics);
+		}
+	}
+}
+
+pub trait AudioSink {
+	fn play(&mut self, sound: &Sound) -> ();
+}
+
+pub trait InputSource {
+	fn controllers(&mut self) -> (u16, u16); //Pad bits for controller 1 and 2
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScaleMode {
+	Integer, //Largest whole multiple that fits
+	Fit, //Fills the window keeping the aspect ratio
+}
+
+//Where the 320x240 image goes inside a window, the rest is letterboxed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+	pub x: f64,
+	pub y: f64,
+	pub scale: f64,
+}
+
+impl ScaleMode {
+	pub fn from_name(name: &str) -> Option<ScaleMode> {
+		match name {
+			"integer" => Some(ScaleMode::Integer),
+			"fit" => Some(ScaleMode::Fit),
+			_ => None,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match *self {
+			ScaleMode::Integer => "integer",
+			ScaleMode::Fit => "fit",
+		}
+	}
+
+	pub fn next(&self) -> ScaleMode {
+		match *self {
+			ScaleMode::Integer => ScaleMode::Fit,
+			ScaleMode::Fit => ScaleMode::Integer,
+		}
+	}
+
+	pub fn viewport(&self, width: f64, height: f64) -> Viewport {
+		self.viewport_of(width, height, 320.0, 240.0)
+	}
+
+	//The same for an image of content_width x content_height Chip16 pixels, like the screen with a debug panel beside it
+	pub fn viewport_of(&self, width: f64, height: f64, content_width: f64, content_height: f64) -> Viewport {
+		let mut scale = (width / content_width).min(height / content_height);
+		if *self == ScaleMode::Integer && scale >= 1.0 {
+			scale = scale.floor();
+		}
+		Viewport {
+			x: ((width - content_width * scale) / 2.0).floor(),
+			y: ((height - content_height * scale) / 2.0).floor(),
+			scale: scale,
+		}
+	}
+}
@@ -0,0 +1,635 @@
+use image;
+use piston_window::*;
+use cpu::{Chip16Graphics, Cpu, Pad};
+use config::Config;
+use browser::{self, Browser};
+use cli::{self, Options};
+use games::GameDatabase;
+use watch::Watcher;
+use std::path::Path;
+use std::io::{self, BufRead};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use console::Console;
+use gdb;
+use filters;
+use palette;
+use overlay;
+use hud::{self, Hud};
+use panel;
+use hexedit::{self, HexEditor};
+use viewer::SpriteViewer;
+use recording::{self, Recorder, RecordFormat};
+use frontend::{VideoSink, InputSource, ScaleMode};
+use frontend::headless::NullAudio;
+use frontend::keys::{self, KeyBindings};
+
+pub struct PistonVideo {
+	pub scale_mode: ScaleMode,
+	pub filter: filters::Filter,
+	pub recorder: Option<Recorder>,
+	pub inspect_draws: bool, //Outlines every DRW of the frame, red when it collided
+	hud: Option<Vec<String>>, //Drawn over a copy of the frame, so it can change while paused
+	panel: Option<Vec<String>>, //Registers and stack, to the right of the frame
+	editor: Option<image::RgbaImage>, //The memory editor, to the right of the panel
+	viewer: Option<image::RgbaImage>, //The sprite viewer, to the right of everything
+	frame: image::RgbaImage, //Only filtered, the GPU does the scaling
+	source: image::RgbaImage, //The screen before the filter, kept so frames don't allocate
+	dirty: bool,
+	texture_context: Option<G2dTextureContext>,
+	texture: Option<G2dTexture>,
+}
+
+pub struct PistonInput {
+	bindings: Vec<(Key, usize, u16)>, //A key, the controller it belongs to and the pad bit it holds
+	controllers: [u16; 2],
+}
+
+//The names key files use are the ones of piston's Key
+macro_rules! key_names {
+	($($key:ident),*) => {
+		fn key_from_name(name: &str) -> Option<Key> {
+			$(if name.eq_ignore_ascii_case(stringify!($key)) {
+				return Some(Key::$key);
+			})*
+			None
+		}
+	}
+}
+
+key_names!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+	D0, D1, D2, D3, D4, D5, D6, D7, D8, D9,
+	NumPad0, NumPad1, NumPad2, NumPad3, NumPad4, NumPad5, NumPad6, NumPad7, NumPad8, NumPad9,
+	Up, Down, Left, Right, Space, Return, Tab, Backspace, LShift, RShift, LCtrl, RCtrl, LAlt, RAlt,
+	Comma, Period, Slash, Semicolon, Minus, Equals);
+
+impl PistonVideo {
+	pub fn new(scale_mode: ScaleMode) -> PistonVideo {
+		PistonVideo {
+			scale_mode: scale_mode,
+			filter: filters::Filter::None,
+			recorder: None,
+			inspect_draws: false,
+			hud: None,
+			panel: None,
+			editor: None,
+			viewer: None,
+			frame: image::RgbaImage::new(320, 240),
+			source: image::RgbaImage::new(320, 240),
+			dirty: false,
+			texture_context: None,
+			texture: None,
+		}
+	}
+
+	pub fn draw(&mut self, window: &mut PistonWindow, input: &Event) -> () {
+		if self.texture_context.is_none() {
+			self.texture_context = Some(window.create_texture_context());
+		}
+		let texture_context = self.texture_context.as_mut().unwrap();
+
+		let factor = self.frame.width() / 320;
+		let composed;
+		let frame = if self.hud.is_none() && self.panel.is_none() && self.editor.is_none() && self.viewer.is_none() {
+			&self.frame
+		} else {
+			let mut copy = self.frame.clone();
+			if let Some(ref lines) = self.hud {
+				hud::draw(&mut copy, lines, factor);
+			}
+			if let Some(ref lines) = self.panel {
+				copy = panel::beside(&copy, lines, factor);
+			}
+			if let Some(ref editor) = self.editor {
+				copy = panel::join(&copy, editor);
+			}
+			if let Some(ref viewer) = self.viewer {
+				copy = panel::join(&copy, viewer);
+			}
+			composed = copy;
+			&composed
+		};
+		let texture_size = self.texture.as_ref().map(|texture| texture.get_size());
+		if texture_size != Some(frame.dimensions()) { //Filters change the size of the frame
+			self.texture = Some(Texture::from_image(
+				texture_context,
+				frame,
+				&TextureSettings::new().filter(Filter::Nearest),
+			).unwrap());
+		} else if self.dirty {
+			self.texture.as_mut().unwrap().update(texture_context, frame).unwrap();
+		}
+		self.dirty = false;
+
+		let texture = self.texture.as_ref().unwrap();
+		let scale_mode = self.scale_mode;
+		let factor = factor as f64;
+		let (width, height) = (frame.width() as f64 / factor, frame.height() as f64 / factor);
+		window.draw_2d(input, |c, g, device| {
+			texture_context.encoder.flush(device);
+			let view = c.get_view_size();
+			let viewport = scale_mode.viewport_of(view[0], view[1], width, height);
+			let scale = viewport.scale / factor;
+			clear([0.0, 0.0, 0.0, 1.0], g); //Letterbox
+			image(texture, c.transform.trans(viewport.x, viewport.y).scale(scale, scale), g);
+		});
+	}
+
+	pub fn set_hud(&mut self, lines: Option<Vec<String>>) -> () {
+		if lines != self.hud {
+			self.hud = lines;
+			self.dirty = true;
+		}
+	}
+
+	pub fn set_editor(&mut self, image: Option<image::RgbaImage>) -> () {
+		if image.is_some() || self.editor.is_some() {
+			self.editor = image;
+			self.dirty = true;
+		}
+	}
+
+	pub fn set_viewer(&mut self, image: Option<image::RgbaImage>) -> () {
+		if image.is_some() || self.viewer.is_some() {
+			self.viewer = image;
+			self.dirty = true;
+		}
+	}
+
+	pub fn set_panel(&mut self, lines: Option<Vec<String>>) -> () {
+		if lines != self.panel {
+			self.panel = lines;
+			self.dirty = true;
+		}
+	}
+
+	//Shows an image that isn't a frame of the program, like the ROM browser
+	pub fn show(&mut self, image: image::RgbaImage) -> () {
+		self.frame = image;
+		self.dirty = true;
+	}
+
+	pub fn toggle_recording(&mut self, format: RecordFormat) -> () {
+		if self.recorder.is_some() {
+			self.stop_recording();
+			return;
+		}
+		let path = recording::default_path(format);
+		match Recorder::new(&path, format) {
+			Ok(recorder) => {
+				println!("Recording to {}", path.display());
+				self.recorder = Some(recorder);
+			},
+			Err(e) => println!("Couldn't start recording: {}", e),
+		}
+	}
+
+	pub fn stop_recording(&mut self) -> () {
+		if let Some(recorder) = self.recorder.take() {
+			let path = recorder.path.clone();
+			match recorder.finish() {
+				Ok(frames) => println!("Recorded {} frames to {}", frames, path.display()),
+				Err(e) => println!("Recording to {} failed: {}", path.display(), e),
+			}
+		}
+	}
+
+	pub fn reset(&mut self) -> () { //Textures belong to a window, a new window needs new ones
+		self.texture_context = None;
+		self.texture = None;
+	}
+}
+
+impl VideoSink for PistonVideo {
+	fn present(&mut self, graphics: &Chip16Graphics) -> () {
+		if self.filter == filters::Filter::None {
+			if self.frame.dimensions() != (320, 240) {
+				self.frame = image::RgbaImage::new(320, 240);
+			}
+			graphics.write_rgba(&mut self.frame);
+		} else {
+			graphics.write_rgba(&mut self.source);
+			self.filter.apply_into(&self.source, &mut self.frame);
+		}
+		if self.inspect_draws {
+			if let Some(ref log) = graphics.draw_log {
+				overlay::draw_calls(&mut self.frame, log, self.filter.factor());
+			}
+		}
+		self.dirty = true;
+		if let Some(ref mut recorder) = self.recorder {
+			recorder.present(graphics); //Recordings are unfiltered
+		}
+	}
+}
+
+impl PistonInput {
+	pub fn new(bindings: &KeyBindings) -> Result<PistonInput, String> {
+		let mut keys = Vec::new();
+		for (player, names) in bindings.players.iter().enumerate() {
+			for (name, &(button, pad)) in names.iter().zip(keys::BUTTONS.iter()) {
+				match key_from_name(name) {
+					Some(key) => keys.push((key, player, pad as u16)),
+					None => return Err(format!("{} is not a key, it is bound to p{}.{}", name, player + 1, button)),
+				}
+			}
+		}
+		Ok(PistonInput { bindings: keys, controllers: [0, 0] })
+	}
+
+	pub fn handle_event(&mut self, e: &Event) -> () {
+		if let Some(Button::Keyboard(key)) = e.press_args() {
+			for &(bound, player, pad) in self.bindings.iter() {
+				if bound == key {
+					self.controllers[player] |= pad;
+				}
+			}
+		}
+
+		if let Some(Button::Keyboard(key)) = e.release_args() {
+			for &(bound, player, pad) in self.bindings.iter() {
+				if bound == key {
+					self.controllers[player] &= !pad;
+				}
+			}
+		}
+	}
+}
+
+impl InputSource for PistonInput {
+	fn controllers(&mut self) -> (u16, u16) {
+		(self.controllers[0], self.controllers[1])
+	}
+}
+
+pub fn build_window(multiplier: u32, fullscreen: bool) -> PistonWindow {
+	WindowSettings::new("RustChip16", [320 * multiplier, 240 * multiplier])
+		.exit_on_esc(true)
+		.resizable(true)
+		.fullscreen(fullscreen)
+		.build()
+		.unwrap()
+}
+
+//Microseconds between frames
+fn frame_interval(options: &Options) -> u64 {
+	((16666.0 / options.speed.unwrap_or(1.0)) as u64).max(1)
+}
+
+//The options and controls of a ROM the browser launches, its own games entry and palette instead of the first ROM's.
+//--symbols and --cheats files belong to the ROM of the command line
+fn launch_options(raw: &Options, rom: &Path, config: &Config, database: &GameDatabase) -> Result<(Options, PistonInput), String> {
+	let mut raw = raw.clone();
+	if rom != raw.rom {
+		raw.symbols = None;
+		raw.cheats = None;
+	}
+	raw.rom = rom.to_path_buf();
+	let (options, defaults) = cli::resolve(&raw, config, database);
+	let input = PistonInput::new(&cli::key_bindings(&options, &defaults)?)?;
+	Ok((options, input))
+}
+
+//raw are the options of the command line before the config and games files filled them in, options are those for its ROM.
+//Returns the options of the program that was running at the end, the browser may have launched another
+pub fn start_program(cpu: &mut Cpu, window: &mut PistonWindow, multiplier: u32, config: &mut Config, database: &GameDatabase,
+	mut input: PistonInput, raw: &Options, options: &Options) -> Options {
+	let mut options = options.clone(); //Those of the program running, the browser can change it
+	let mut video = PistonVideo::new(config.scale_mode);
+	let mut audio = NullAudio;
+	let mut vblank_dt: u64 = 0;
+	let mut frame_time = frame_interval(&options);
+	let mut paused = options.paused;
+	let mut fullscreen = false;
+	let mut loaded = !options.rom.as_os_str().is_empty();
+	let mut browser: Option<Browser> = None;
+	let mut watcher = if options.watch && loaded { Some(Watcher::new(&options.rom)) } else { None };
+	let mut watch_dt: u64 = 0;
+	let mut hud: Option<Hud> = None;
+	let mut show_panel = false;
+	let mut editor: Option<HexEditor> = None;
+	let mut viewer: Option<SpriteViewer> = None;
+	let commands = console_input();
+	let mut console = Console::new();
+	let mut debugger = match options.gdb.map(gdb::Server::bind) {
+		Some(Ok(server)) => {
+			println!("A debugger can attach on 127.0.0.1:{}", server.port());
+			Some(server)
+		},
+		Some(Err(e)) => {
+			println!("Couldn't wait for a debugger: {}", e);
+			None
+		},
+		None => None,
+	};
+	if !loaded {
+		open_browser(&mut browser, &mut video, config);
+	}
+	while let Some(e) = window.next() {
+		if e.press_args().is_none() { //Presses go to the browser and the editor first, the controllers get what they leave
+			input.handle_event(&e);
+		}
+
+		if let Some(Button::Keyboard(key)) = e.press_args() {
+			if key == Key::F1 {
+				if browser.is_none() {
+					open_browser(&mut browser, &mut video, config);
+				} else if loaded {
+					browser = None;
+					video.present(&cpu.graphics);
+				}
+				continue;
+			}
+			if let Some(mut menu) = browser.take() {
+				let mut launch = None;
+				match key {
+					Key::Up => menu.move_by(-1),
+					Key::Down => menu.move_by(1),
+					Key::PageUp => menu.move_by(-20),
+					Key::PageDown => menu.move_by(20),
+					Key::Backspace => menu.parent(),
+					Key::Return => launch = menu.activate(),
+					_ => {},
+				}
+				if let Some(chosen) = launch {
+					let launched = launch_options(raw, &chosen, config, database)
+						.and_then(|(new, controls)| reload(cpu, &new, false).map(|_| (new, controls)));
+					match launched {
+						Ok((new, controls)) => {
+							options = new;
+							input = controls;
+							frame_time = frame_interval(&options);
+							loaded = true;
+							vblank_dt = 0;
+							if options.watch {
+								watcher = Some(Watcher::new(&options.rom));
+							}
+							config.remember_rom(&options.rom);
+							if cpu.profile.is_some() || cpu.memory.coverage.is_some() {
+								println!("Profiling and coverage start again for {}", options.rom.display());
+							}
+							video.present(&cpu.graphics);
+							continue;
+						},
+						Err(e) => menu.error = Some(e),
+					}
+				}
+				video.show(menu.render());
+				browser = Some(menu);
+				continue;
+			}
+			if let (Key::Tab, Some(ref mut editor)) = (key, editor.as_mut()) { //Paused or not
+				editor.follow = editor.follow.next();
+				continue;
+			}
+			if paused && editor.as_mut().map(|editor| edit_memory(editor, cpu, key)).unwrap_or(false) {
+				continue;
+			}
+			if paused && viewer.as_mut().map(|viewer| resize_sprites(viewer, cpu, key)).unwrap_or(false) {
+				continue;
+			}
+			input.handle_event(&e);
+			match key {
+				Key::F2 => hud = if hud.is_some() { None } else { Some(Hud::new()) },
+				Key::F3 => {
+					video.inspect_draws = !video.inspect_draws;
+					cpu.graphics.draw_log = if video.inspect_draws { Some(Vec::new()) } else { None };
+				},
+				Key::F4 => show_panel = !show_panel,
+				Key::F5 if loaded => {
+					cpu.soft_reset();
+					video.present(&cpu.graphics); //Shows the cleared screen even when paused
+					println!("Soft reset");
+				},
+				Key::F6 if loaded => {
+					match reload(cpu, &options, true) {
+						Ok(()) => {
+							video.present(&cpu.graphics);
+							println!("Reloaded {}", options.rom.display());
+						},
+						Err(e) => println!("Couldn't reload {}: {}", options.rom.display(), e),
+					}
+				},
+				Key::F7 => video.toggle_recording(RecordFormat::PngSequence),
+				Key::F8 => {
+					video.filter = video.filter.next();
+					println!("Filter: {}", video.filter.name());
+				},
+				Key::F9 => video.toggle_recording(RecordFormat::Gif),
+				Key::F10 => {
+					video.scale_mode = video.scale_mode.next();
+					config.scale_mode = video.scale_mode;
+					if let Err(e) = config.save(&["scale_mode"]) {
+						println!("Couldn't save the configuration: {}", e);
+					}
+				},
+				Key::F11 => {
+					fullscreen = !fullscreen;
+					*window = build_window(multiplier, fullscreen);
+					video.reset();
+					continue;
+				},
+				Key::F12 => print!("{}", palette::to_hex_list(cpu.graphics.effective_palette())),
+				Key::M => editor = if editor.is_some() { None } else { Some(HexEditor::new()) },
+				Key::V => viewer = if viewer.is_some() { None } else { Some(SpriteViewer::new()) },
+				Key::P => {
+					paused = !paused;
+					println!("{}", if paused { "Paused" } else { "Resumed" });
+				},
+				_ => {},
+			}
+		}
+
+		if let Some(u) = e.update_args() {
+			while let Ok(line) = commands.try_recv() {
+				if !loaded {
+					println!("Choose a ROM first");
+					continue;
+				}
+				match console.execute(cpu, &options.rom, &line) {
+					Ok(text) => print!("{}", text),
+					Err(e) => println!("{}", e),
+				}
+			}
+			if let (Some(server), true) = (debugger.as_mut(), loaded) {
+				let attached = server.connected();
+				if let Err(e) = server.poll(cpu, &mut input, &mut video, &mut audio) {
+					println!("Debugger: {}", e);
+				}
+				match (attached, server.connected()) {
+					(false, true) => println!("Debugger attached"),
+					(true, false) => println!("Debugger detached"),
+					_ => {},
+				}
+				if server.connected() { //It pauses and resumes instead of P
+					paused = !server.running();
+				}
+			}
+			watch_dt += (u.dt * 1000000.0) as u64;
+			if watch_dt >= 250000 { //Four times a second is quick enough after a build
+				watch_dt = 0;
+				if watcher.as_mut().map(|watcher| watcher.changed()).unwrap_or(false) {
+					match reload(cpu, &options, true) {
+						Ok(()) => {
+							if browser.is_none() {
+								video.present(&cpu.graphics);
+							}
+							println!("{} changed, reloaded it", options.rom.display());
+						},
+						Err(e) => println!("{} changed but didn't load: {}", options.rom.display(), e),
+					}
+				}
+			}
+			if paused || browser.is_some() || !loaded {
+				vblank_dt = 0;
+			} else {
+				vblank_dt += (u.dt * 1000000.0) as u64;
+			}
+			cpu.memory.track_writes(editor.is_some()); //A reload brings a new Memory
+			let mut frames = 0;
+			while vblank_dt >= frame_time {
+				if frames == 8 { //Too far behind to catch up, drop the time instead of freezing
+					vblank_dt = 0;
+					break;
+				}
+				cpu.run_frame(&mut input, &mut video, &mut audio);
+				vblank_dt -= frame_time;
+				frames += 1;
+				if let Some(pc) = cpu.break_hit {
+					paused = true;
+					vblank_dt = 0;
+					println!("Break at {}, P goes on", cpu.symbols.describe(pc));
+					break;
+				}
+				if let Some(ref reason) = cpu.halted {
+					paused = true;
+					vblank_dt = 0;
+					println!("The program stopped: {}, F5 resets", reason);
+					break;
+				}
+			}
+			if let Some(ref mut hud) = hud {
+				hud.tick((u.dt * 1000000.0) as u64, 0, frames);
+			}
+			video.set_hud(match hud {
+				Some(ref hud) if browser.is_none() => Some(hud.lines(cpu, input.controllers())),
+				_ => None,
+			});
+			video.set_panel(if show_panel && browser.is_none() { Some(panel::lines(cpu)) } else { None });
+			let scale = video.filter.factor();
+			video.set_editor(match editor {
+				Some(ref mut editor) if browser.is_none() => {
+					editor.sync(cpu);
+					Some(editor.render(cpu, paused, scale))
+				},
+				_ => None,
+			});
+			video.set_viewer(match viewer {
+				Some(ref mut viewer) if browser.is_none() => {
+					if let Some(ref editor) = editor { //The editor's cursor moves it
+						viewer.address = editor.cursor;
+					}
+					Some(viewer.render(cpu, scale))
+				},
+				_ => None,
+			});
+		}
+
+		if e.render_args().is_some() {
+			if let Some(ref mut hud) = hud {
+				hud.tick(0, 1, 0);
+			}
+			video.draw(window, &e);
+		}
+	}
+	video.stop_recording(); //Finish the file instead of losing it
+	options
+}
+
+fn hex_digit(key: Key) -> Option<u8> {
+	let digits = [Key::D0, Key::D1, Key::D2, Key::D3, Key::D4, Key::D5, Key::D6, Key::D7, Key::D8, Key::D9,
+		Key::A, Key::B, Key::C, Key::D, Key::E, Key::F];
+	let numpad = [Key::NumPad0, Key::NumPad1, Key::NumPad2, Key::NumPad3, Key::NumPad4,
+		Key::NumPad5, Key::NumPad6, Key::NumPad7, Key::NumPad8, Key::NumPad9];
+	digits.iter().position(|&digit| digit == key)
+		.or_else(|| numpad.iter().position(|&digit| digit == key))
+		.map(|digit| digit as u8)
+}
+
+//Keys of the memory editor while paused, false for the ones it leaves to the rest
+fn edit_memory(editor: &mut HexEditor, cpu: &mut Cpu, key: Key) -> bool {
+	let page = (hexedit::ROWS * 8) as i32;
+	match key {
+		Key::Left => editor.move_by(-1),
+		Key::Right => editor.move_by(1),
+		Key::Up => editor.move_by(-8),
+		Key::Down => editor.move_by(8),
+		Key::PageUp => editor.move_by(-page),
+		Key::PageDown => editor.move_by(page),
+		Key::Home => editor.go_to(0x0000),
+		Key::End => editor.go_to(0xFFF0), //The controllers
+		_ => match hex_digit(key) {
+			Some(digit) => {
+				editor.type_digit(cpu, digit);
+			},
+			None => return false,
+		},
+	}
+	true
+}
+
+//Keys of the sprite viewer while paused, Backspace goes back to the size of SPR
+fn resize_sprites(viewer: &mut SpriteViewer, cpu: &Cpu, key: Key) -> bool {
+	match key {
+		Key::Comma => viewer.resize(cpu, -1, 0),
+		Key::Period => viewer.resize(cpu, 1, 0),
+		Key::Minus => viewer.resize(cpu, 0, -1),
+		Key::Equals => viewer.resize(cpu, 0, 1),
+		Key::Backspace => viewer.size = None,
+		_ => return false,
+	}
+	true
+}
+
+//Builds the CPU again from the file with the options of its ROM, F3 stays on and --trace goes on in the same file.
+//Reloading the same program keeps the cheats, even the ones that weren't saved, the profile and the coverage
+fn reload(cpu: &mut Cpu, options: &Options, same: bool) -> Result<(), String> {
+	let mut new = cli::load_cpu(&Options { trace: None, ..options.clone() })?; //Creating the file again would empty it
+	new.trace = cpu.trace.take();
+	new.graphics.draw_log = cpu.graphics.draw_log.as_ref().map(|_| Vec::new());
+	if same {
+		new.cheats = cpu.cheats.clone();
+		new.breakpoints = cpu.breakpoints.clone();
+		new.profile = cpu.profile.take();
+		new.memory.coverage = cpu.memory.coverage.take();
+	}
+	*cpu = new;
+	Ok(())
+}
+
+//Lines typed in the terminal, for the cheat console
+fn console_input() -> Receiver<String> {
+	let (sender, receiver) = mpsc::channel();
+	thread::spawn(move || {
+		let stdin = io::stdin();
+		for line in stdin.lock().lines() {
+			match line {
+				Ok(line) => if sender.send(line).is_err() {
+					break;
+				},
+				Err(_) => break,
+			}
+		}
+	});
+	receiver
+}
+
+fn open_browser(browser: &mut Option<Browser>, video: &mut PistonVideo, config: &Config) -> () {
+	match Browser::open(&browser::start_dir(config)) {
+		Ok(menu) => {
+			video.show(menu.render());
+			*browser = Some(menu);
+		},
+		Err(e) => println!("Couldn't open the ROM browser: {}", e),
+	}
+}
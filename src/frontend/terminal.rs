@@ -0,0 +1,271 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+use cpu::{Chip16Graphics, Cpu, Pad};
+use frontend::{VideoSink, InputSource};
+use frontend::headless::NullAudio;
+
+const HOLD_FRAMES: u8 = 8; //Terminals don't report releases, so a press is held for this many frames
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TermKey {
+	Up,
+	Down,
+	Left,
+	Right,
+	Char(u8),
+}
+
+pub struct TerminalVideo {
+	columns: u32,
+	rows: u32,
+	last: Vec<String>, //The rows on the terminal
+}
+
+pub struct TerminalInput {
+	keys: Receiver<u8>,
+	pending: Vec<u8>,
+	waited: bool, //What is pending already waited a frame for the rest of its escape sequence
+	held: [[u8; 8]; 2], //Frames left for every Pad bit of both controllers
+	pub quit: bool,
+}
+
+impl TerminalVideo {
+	pub fn new(columns: u32, rows: u32) -> TerminalVideo {
+		TerminalVideo { columns: columns, rows: rows, last: Vec::new() }
+	}
+}
+
+impl VideoSink for TerminalVideo {
+	fn present(&mut self, graphics: &Chip16Graphics) -> () {
+		let rows = render_rows(graphics, self.columns, self.rows);
+		let changed = redraw(&self.last, &rows);
+		if !changed.is_empty() {
+			let stdout = io::stdout();
+			let mut out = stdout.lock();
+			let _ = write!(out, "{}", changed);
+			let _ = out.flush();
+		}
+		self.last = rows;
+	}
+}
+
+//Only the rows that changed, each after moving the cursor to it, SSH links are slow
+pub fn redraw(last: &[String], rows: &[String]) -> String {
+	let mut text = String::new();
+	for (i, row) in rows.iter().enumerate() {
+		if last.get(i) != Some(row) {
+			text.push_str(&format!("\x1b[{};1H{}", i + 1, row));
+		}
+	}
+	text
+}
+
+fn average(graphics: &Chip16Graphics, x0: u32, y0: u32, x1: u32, y1: u32) -> (u32, u32, u32) {
+	let (mut r, mut g, mut b, mut count) = (0, 0, 0, 0);
+	for y in y0..y1 {
+		for x in x0..x1 {
+			let mut pixel = graphics.screen[(320 * y + x) as usize];
+			if pixel == 0 {
+				pixel = graphics.state.bg;
+			}
+			let colour = graphics.effective_palette()[pixel as usize];
+			r += (colour >> 16) & 0xFF;
+			g += (colour >> 8) & 0xFF;
+			b += colour & 0xFF;
+			count += 1;
+		}
+	}
+	if count == 0 {
+		return (0, 0, 0);
+	}
+	(r / count, g / count, b / count)
+}
+
+pub fn render(graphics: &Chip16Graphics, columns: u32, rows: u32) -> String {
+	render_rows(graphics, columns, rows).iter().map(|row| format!("{}\r\n", row)).collect()
+}
+
+//Every character cell shows two vertically stacked pixels, the upper half block takes the
+//top one as foreground and the bottom one as background
+pub fn render_rows(graphics: &Chip16Graphics, columns: u32, rows: u32) -> Vec<String> {
+	let columns = columns.clamp(1, 320);
+	let rows = rows.clamp(1, 120);
+	let mut lines = Vec::with_capacity(rows as usize);
+	for row in 0..rows {
+		let mut line = String::with_capacity((columns * 40) as usize);
+		for column in 0..columns {
+			let x0 = column * 320 / columns;
+			let x1 = (column + 1) * 320 / columns;
+			let top = average(graphics, x0, (row * 2) * 240 / (rows * 2), x1, (row * 2 + 1) * 240 / (rows * 2));
+			let bottom = average(graphics, x0, (row * 2 + 1) * 240 / (rows * 2), x1, (row * 2 + 2) * 240 / (rows * 2));
+			line.push_str(&format!("\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+				top.0, top.1, top.2, bottom.0, bottom.1, bottom.2));
+		}
+		line.push_str("\x1b[0m");
+		lines.push(line);
+	}
+	lines
+}
+
+pub fn parse_keys(bytes: &[u8]) -> (Vec<TermKey>, usize) {
+	let mut keys = Vec::new();
+	let mut i = 0;
+	while i < bytes.len() {
+		if bytes[i] == 0x1B {
+			if i + 2 >= bytes.len() { //Wait for the rest of the escape sequence
+				break;
+			}
+			if bytes[i + 1] == b'[' || bytes[i + 1] == b'O' {
+				match bytes[i + 2] {
+					b'A' => keys.push(TermKey::Up),
+					b'B' => keys.push(TermKey::Down),
+					b'C' => keys.push(TermKey::Right),
+					b'D' => keys.push(TermKey::Left),
+					_ => {},
+				}
+				i += 3;
+				continue;
+			}
+		}
+		keys.push(TermKey::Char(bytes[i]));
+		i += 1;
+	}
+	(keys, i)
+}
+
+fn pad_for(key: TermKey) -> Option<(usize, Pad)> {
+	match key {
+		TermKey::Up => Some((0, Pad::Up)),
+		TermKey::Down => Some((0, Pad::Down)),
+		TermKey::Left => Some((0, Pad::Left)),
+		TermKey::Right => Some((0, Pad::Right)),
+		TermKey::Char(b'z') => Some((0, Pad::A)),
+		TermKey::Char(b'x') => Some((0, Pad::B)),
+		TermKey::Char(b'\t') => Some((0, Pad::Select)),
+		TermKey::Char(b'\r') => Some((0, Pad::Start)),
+
+		TermKey::Char(b'w') => Some((1, Pad::Up)),
+		TermKey::Char(b's') => Some((1, Pad::Down)),
+		TermKey::Char(b'a') => Some((1, Pad::Left)),
+		TermKey::Char(b'd') => Some((1, Pad::Right)),
+		TermKey::Char(b'h') => Some((1, Pad::A)),
+		TermKey::Char(b'j') => Some((1, Pad::B)),
+		TermKey::Char(b'c') => Some((1, Pad::Select)),
+		TermKey::Char(b' ') => Some((1, Pad::Start)),
+		_ => None,
+	}
+}
+
+impl Default for TerminalInput {
+	fn default() -> TerminalInput {
+		TerminalInput::new()
+	}
+}
+
+impl TerminalInput {
+	pub fn new() -> TerminalInput {
+		let (sender, receiver) = channel();
+		thread::spawn(move || {
+			let stdin = io::stdin();
+			for byte in stdin.lock().bytes() {
+				match byte {
+					Ok(byte) => if sender.send(byte).is_err() { break; },
+					Err(_) => break,
+				}
+			}
+		});
+		TerminalInput::from_receiver(receiver)
+	}
+
+	pub fn from_receiver(keys: Receiver<u8>) -> TerminalInput {
+		TerminalInput { keys: keys, pending: Vec::new(), waited: false, held: [[0; 8]; 2], quit: false }
+	}
+}
+
+impl InputSource for TerminalInput {
+	fn controllers(&mut self) -> (u16, u16) {
+		for held in self.held.iter_mut() {
+			for frames in held.iter_mut() {
+				*frames = frames.saturating_sub(1);
+			}
+		}
+
+		while let Ok(byte) = self.keys.try_recv() {
+			self.pending.push(byte);
+		}
+		let (mut keys, used) = parse_keys(&self.pending);
+		self.pending.drain(..used);
+		if self.pending.is_empty() || !self.waited {
+			self.waited = !self.pending.is_empty();
+		} else { //Nothing came after it in a frame, it was Esc and not the start of an arrow
+			keys.extend(self.pending.drain(..).map(TermKey::Char));
+			self.waited = false;
+		}
+		for key in keys {
+			match key {
+				TermKey::Char(b'q') | TermKey::Char(0x03) => self.quit = true, //q or Ctrl-C
+				_ => if let Some((controller, pad)) = pad_for(key) {
+					let bit = (pad as u16).trailing_zeros() as usize;
+					self.held[controller][bit] = HOLD_FRAMES;
+				},
+			}
+		}
+
+		let mut controllers = [0u16; 2];
+		for (controller, held) in controllers.iter_mut().zip(self.held.iter()) {
+			for (bit, frames) in held.iter().enumerate() {
+				if *frames > 0 {
+					*controller |= 1 << bit;
+				}
+			}
+		}
+		(controllers[0], controllers[1])
+	}
+}
+
+fn stty(args: &[&str]) -> () {
+	let _ = Command::new("stty").args(args).stderr(Stdio::null()).status();
+}
+
+//Raw mode and a hidden cursor for as long as it lives, dropping it puts the terminal back even on a panic
+struct RawTerminal;
+
+impl RawTerminal {
+	fn enter() -> RawTerminal {
+		stty(&["raw", "-echo"]);
+		print!("\x1b[2J\x1b[?25l"); //Clear and hide the cursor
+		RawTerminal
+	}
+}
+
+impl Drop for RawTerminal {
+	fn drop(&mut self) -> () {
+		print!("\x1b[0m\x1b[?25h\r\n");
+		let _ = io::stdout().flush();
+		stty(&["-raw", "echo"]);
+	}
+}
+
+//Until q, or until the program halts and cpu.halted says why
+pub fn start_program(cpu: &mut Cpu, columns: u32, rows: u32) -> () {
+	let mut video = TerminalVideo::new(columns, rows);
+	let mut input = TerminalInput::new();
+	let mut audio = NullAudio;
+	let _terminal = RawTerminal::enter();
+
+	let frame_time = Duration::from_micros(16666);
+	let mut next_frame = Instant::now();
+	while !input.quit && cpu.halted.is_none() {
+		cpu.run_frame(&mut input, &mut video, &mut audio);
+		next_frame += frame_time;
+		let now = Instant::now();
+		if next_frame > now {
+			thread::sleep(next_frame - now);
+		} else {
+			next_frame = now;
+		}
+	}
+}
@@ -0,0 +1,181 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use config::{self, Config, Setting};
+use frontend::keys::BUTTONS;
+use loading;
+
+//Overrides for one program, what is left out comes from the config file
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameSettings {
+	pub scale: Option<u32>,
+	pub speed: Option<f64>,
+	pub palette: Option<String>,
+	pub palette_override: Option<String>,
+	pub keys: Vec<(usize, usize, String)>, //The player, the button in keys::BUTTONS and its key
+}
+
+//Settings for every program that has some, by the CRC32 of the program
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameDatabase {
+	pub games: Vec<(u32, GameSettings)>,
+}
+
+//The CRC32 of what goes in memory, which is the one in the header of a .c16 file
+pub fn rom_crc(path: &Path) -> Result<u32, String> {
+	let bytes = loading::read_file(path)?;
+	let (_, program) = loading::split_rom(path, &bytes)?;
+	Ok(loading::crc32(program))
+}
+
+impl Default for GameSettings {
+	fn default() -> GameSettings {
+		GameSettings::new()
+	}
+}
+
+impl GameSettings {
+	pub fn new() -> GameSettings {
+		GameSettings { scale: None, speed: None, palette: None, palette_override: None, keys: Vec::new() }
+	}
+
+	pub fn apply(&self, config: &mut Config) -> () {
+		if let Some(scale) = self.scale {
+			config.scale = scale;
+		}
+		if let Some(speed) = self.speed {
+			config.speed = speed;
+		}
+		if self.palette.is_some() {
+			config.palette = self.palette.clone();
+		}
+		if self.palette_override.is_some() {
+			config.palette_override = self.palette_override.clone();
+		}
+		for &(player, button, ref key) in self.keys.iter() {
+			config.keys.players[player][button] = key.clone();
+		}
+	}
+}
+
+impl Default for GameDatabase {
+	fn default() -> GameDatabase {
+		GameDatabase::new()
+	}
+}
+
+impl GameDatabase {
+	pub fn new() -> GameDatabase {
+		GameDatabase { games: Vec::new() }
+	}
+
+	pub fn parse(text: &str) -> GameDatabase {
+		GameDatabase::read(text).0
+	}
+
+	//A [CRC32] line in hex starts the settings of a program, the rest are key = value lines like in the config file.
+	//Returns what was wrong with it too, lines with values that don't make sense are left out
+	pub fn read(text: &str) -> (GameDatabase, Vec<String>) {
+		let mut database = GameDatabase::new();
+		let mut errors = Vec::new();
+		let mut current: Option<usize> = None;
+		let mut broken = false; //Whatever follows a broken section is skipped
+		for line in text.lines() {
+			let line = line.trim();
+			if line.starts_with('[') {
+				current = line.find(']').and_then(|end| u32::from_str_radix(&line[1..end], 16).ok())
+					.map(|crc| database.entry(crc));
+				broken = current.is_none();
+				if broken {
+					errors.push(format!("{} is not a [CRC32] line, the settings under it are skipped", line));
+				}
+				continue;
+			}
+			let setting = match config::read_setting(line) {
+				Some(setting) if !broken => setting,
+				_ => continue,
+			};
+			let game = match current {
+				Some(i) => &mut database.games[i].1,
+				None => {
+					errors.push(format!("{} comes before any [CRC32] line", line));
+					continue;
+				},
+			};
+			match setting {
+				Ok(Setting::Scale(scale)) => game.scale = Some(scale),
+				Ok(Setting::Speed(speed)) => game.speed = Some(speed),
+				Ok(Setting::Palette(palette)) => game.palette = Some(palette),
+				Ok(Setting::PaletteOverride(palette)) => game.palette_override = Some(palette),
+				Ok(Setting::Key(player, button, key)) => game.keys.push((player, button, key)),
+				Ok(Setting::Unknown) => {},
+				Ok(_) => errors.push(format!("{} is the same for every program, it goes in the config file", line)),
+				Err(e) => errors.push(e),
+			}
+		}
+		(database, errors)
+	}
+
+	fn entry(&mut self, crc: u32) -> usize {
+		match self.games.iter().position(|&(game, _)| game == crc) {
+			Some(i) => i,
+			None => {
+				self.games.push((crc, GameSettings::new()));
+				self.games.len() - 1
+			},
+		}
+	}
+
+	pub fn get(&self, crc: u32) -> Option<&GameSettings> {
+		self.games.iter().find(|&&(game, _)| game == crc).map(|(_, settings)| settings)
+	}
+
+	pub fn to_text(&self) -> String {
+		let mut text = String::new();
+		for (crc, game) in self.games.iter() {
+			text.push_str(&format!("[{:08X}]\n", crc));
+			if let Some(scale) = game.scale {
+				text.push_str(&format!("scale = {}\n", scale));
+			}
+			if let Some(speed) = game.speed {
+				text.push_str(&format!("speed = {}\n", speed));
+			}
+			if let Some(ref palette) = game.palette {
+				text.push_str(&format!("palette = {}\n", palette));
+			}
+			if let Some(ref palette) = game.palette_override {
+				text.push_str(&format!("palette_override = {}\n", palette));
+			}
+			for &(player, button, ref key) in game.keys.iter() {
+				text.push_str(&format!("p{}.{} = {}\n", player + 1, BUTTONS[button].0, key));
+			}
+			text.push('\n');
+		}
+		text
+	}
+
+	//<config dir>/games, empty when there is none
+	pub fn load() -> GameDatabase {
+		let path = match config::config_dir() {
+			Some(dir) => dir.join("games"),
+			None => return GameDatabase::new(),
+		};
+		let mut text = String::new();
+		match File::open(&path) {
+			Ok(mut file) => match file.read_to_string(&mut text) {
+				Ok(_) => {
+					let (database, errors) = GameDatabase::read(&text);
+					for e in errors {
+						println!("{} in {}", e, path.display());
+					}
+					database
+				},
+				Err(e) => {
+					println!("Couldn't read {}: {}", path.display(), e);
+					GameDatabase::new()
+				},
+			},
+			Err(_) => GameDatabase::new(),
+		}
+	}
+}
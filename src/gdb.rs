@@ -0,0 +1,357 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+use cpu::Cpu;
+use frontend::{VideoSink, AudioSink, InputSource};
+use frontend::headless::{NullVideo, NullAudio, NullInput};
+
+//GDB's remote serial protocol on a local port. Registers are 16 bits each, little endian,
+//in the order r0 to rF, pc, sp and flags
+pub const REGISTERS: usize = 19;
+const PC: usize = 16;
+const SP: usize = 17;
+const FLAGS: usize = 18;
+
+const TRAP: &str = "S05"; //Stopped at a breakpoint or after a step
+const INTERRUPTED: &str = "S02"; //Ctrl-C
+const HALTED: &str = "S04"; //An instruction that can't run, the program goes no further
+
+//Waits for a debugger without blocking, a frontend calls poll between frames
+pub struct Server {
+	listener: TcpListener,
+	client: Option<Client>,
+}
+
+struct Client {
+	stream: TcpStream,
+	buffer: Vec<u8>,
+	acks: bool, //Until the debugger asks for QStartNoAckMode
+	running: bool, //After c, until a breakpoint or Ctrl-C
+	breakpoints: Vec<u16>, //The ones it set, they go away with it
+}
+
+fn checksum(data: &[u8]) -> u8 {
+	data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+//$data#checksum
+pub fn packet(data: &str) -> Vec<u8> {
+	format!("${}#{:02x}", data, checksum(data.as_bytes())).into_bytes()
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+	bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_hex_bytes(text: &str) -> Option<Vec<u8>> {
+	if !text.len().is_multiple_of(2) {
+		return None;
+	}
+	(0..text.len() / 2).map(|i| u8::from_str_radix(text.get(2 * i..2 * i + 2)?, 16).ok()).collect()
+}
+
+fn number(text: &str) -> Option<u32> {
+	u32::from_str_radix(text, 16).ok()
+}
+
+//ADDRESS,LENGTH with both in hex, the whole range has to be in memory
+fn range(text: &str) -> Option<(u16, u32)> {
+	let mut parts = text.splitn(2, ',');
+	let address = number(parts.next()?)?;
+	let length = number(parts.next()?)?;
+	if address > 0xFFFF || address.checked_add(length)? > 0x10000 {
+		return None;
+	}
+	Some((address as u16, length))
+}
+
+pub fn register(cpu: &Cpu, index: usize) -> u16 {
+	match index {
+		PC => cpu.pc,
+		SP => cpu.sp,
+		FLAGS => cpu.flags as u8 as u16,
+		_ => cpu.rx[index] as u16,
+	}
+}
+
+pub fn set_register(cpu: &mut Cpu, index: usize, value: u16) -> () {
+	match index {
+		PC => cpu.pc = value,
+		SP => cpu.sp = value,
+		FLAGS => cpu.flags = value as u8 as i8,
+		_ => cpu.rx[index] = value as i16,
+	}
+}
+
+fn register_hex(value: u16) -> String {
+	hex_bytes(&[value as u8, (value >> 8) as u8])
+}
+
+fn parse_register(text: &str) -> Option<u16> {
+	let bytes = parse_hex_bytes(text)?;
+	if bytes.len() != 2 {
+		return None;
+	}
+	Some(((bytes[1] as u16) << 8) | bytes[0] as u16)
+}
+
+impl Server {
+	//Only on 127.0.0.1, port 0 takes any free one
+	pub fn bind(port: u16) -> Result<Server, String> {
+		let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("{} on port {}", e, port))?;
+		listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+		Ok(Server { listener: listener, client: None })
+	}
+
+	pub fn port(&self) -> u16 {
+		self.listener.local_addr().map(|address| address.port()).unwrap_or(0)
+	}
+
+	pub fn connected(&self) -> bool {
+		self.client.is_some()
+	}
+
+	//Whether the program should run, a debugger stops it while it looks around
+	pub fn running(&self) -> bool {
+		self.client.as_ref().map(|client| client.running).unwrap_or(true)
+	}
+
+	//Takes a debugger that is waiting, answers what it sent and tells it when a breakpoint stopped the program
+	pub fn poll<I, V, A>(&mut self, cpu: &mut Cpu, input: &mut I, video: &mut V, audio: &mut A) -> Result<(), String>
+		where I: InputSource, V: VideoSink, A: AudioSink {
+		if self.client.is_none() {
+			match self.listener.accept() {
+				Ok((stream, _)) => {
+					stream.set_nodelay(true).map_err(|e| e.to_string())?;
+					self.client = Some(Client { stream: stream, buffer: Vec::new(), acks: true, running: false, breakpoints: Vec::new() });
+				},
+				Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+				Err(e) => return Err(e.to_string()),
+			}
+		}
+		let result = match self.client {
+			Some(ref mut client) => client.poll(cpu, input, video, audio),
+			None => Ok(true),
+		};
+		if result != Ok(true) { //Detached or gone, nobody is left to clear its breakpoints
+			if let Some(client) = self.client.take() {
+				cpu.breakpoints.retain(|breakpoint| !client.breakpoints.contains(breakpoint));
+				cpu.break_hit = None;
+			}
+		}
+		result.map(|_| ())
+	}
+}
+
+impl Client {
+	//False once the debugger is gone
+	fn poll<I, V, A>(&mut self, cpu: &mut Cpu, input: &mut I, video: &mut V, audio: &mut A) -> Result<bool, String>
+		where I: InputSource, V: VideoSink, A: AudioSink {
+		if self.running && cpu.halted.is_some() {
+			self.running = false;
+			self.send(HALTED)?;
+		} else if self.running && cpu.break_hit.take().is_some() {
+			self.running = false;
+			self.send(TRAP)?;
+		}
+		if !self.receive()? {
+			return Ok(false);
+		}
+		loop {
+			match self.buffer.first().cloned() {
+				None => return Ok(true),
+				Some(b'$') => {},
+				Some(0x03) => {
+					self.buffer.remove(0);
+					if self.running {
+						self.running = false;
+						self.send(INTERRUPTED)?;
+					}
+					continue;
+				},
+				Some(_) => { //Acks and noise between packets
+					self.buffer.remove(0);
+					continue;
+				},
+			}
+			let end = match self.buffer.iter().position(|&byte| byte == b'#') {
+				Some(end) if self.buffer.len() >= end + 3 => end,
+				_ => return Ok(true), //The rest hasn't arrived yet
+			};
+			let bytes: Vec<u8> = self.buffer.drain(..end + 3).collect();
+			let data = &bytes[1..end];
+			let sum = String::from_utf8_lossy(&bytes[end + 1..]).to_string();
+			if u8::from_str_radix(&sum, 16).ok() != Some(checksum(data)) {
+				if self.acks {
+					self.write(b"-")?;
+				}
+				continue;
+			}
+			if self.acks {
+				self.write(b"+")?;
+			}
+			let command = String::from_utf8_lossy(data).to_string();
+			match &command[..] {
+				"D" => {
+					self.send("OK")?;
+					return Ok(false);
+				},
+				"k" => return Ok(false),
+				_ => {},
+			}
+			if let Some(reply) = self.handle(cpu, &command, input, video, audio) {
+				self.send(&reply)?;
+			}
+			if command == "QStartNoAckMode" {
+				self.acks = false;
+			}
+		}
+	}
+
+	//Reads what has arrived without waiting, false when the debugger hung up
+	fn receive(&mut self) -> Result<bool, String> {
+		let mut chunk = [0u8; 4096];
+		self.stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+		let result = loop {
+			match self.stream.read(&mut chunk) {
+				Ok(0) => break Ok(false),
+				Ok(count) => self.buffer.extend_from_slice(&chunk[..count]),
+				Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(true),
+				Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+				Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => break Ok(false),
+				Err(e) => break Err(e.to_string()),
+			}
+		};
+		self.stream.set_nonblocking(false).map_err(|e| e.to_string())?;
+		result
+	}
+
+	fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+		self.stream.write_all(bytes).map_err(|e| e.to_string())
+	}
+
+	fn send(&mut self, data: &str) -> Result<(), String> {
+		self.write(&packet(data))
+	}
+
+	//The reply to a packet, None for c which answers when the program stops
+	fn handle<I, V, A>(&mut self, cpu: &mut Cpu, command: &str, input: &mut I, video: &mut V, audio: &mut A) -> Option<String>
+		where I: InputSource, V: VideoSink, A: AudioSink {
+		let error = "E01".to_string();
+		let (kind, rest) = command.split_at(command.chars().next().map(|c| c.len_utf8()).unwrap_or(0));
+		Some(match kind {
+			"?" => if cpu.halted.is_some() { HALTED } else { TRAP }.to_string(),
+			"g" => (0..REGISTERS).map(|i| register_hex(register(cpu, i))).collect(),
+			"G" => {
+				if rest.len() != REGISTERS * 4 {
+					return Some(error);
+				}
+				let values: Option<Vec<u16>> = (0..REGISTERS).map(|i| parse_register(rest.get(i * 4..i * 4 + 4)?)).collect();
+				match values {
+					Some(values) => {
+						for (i, &value) in values.iter().enumerate() {
+							set_register(cpu, i, value);
+						}
+						"OK".to_string()
+					},
+					None => error,
+				}
+			},
+			"p" => match number(rest) {
+				Some(index) if (index as usize) < REGISTERS => register_hex(register(cpu, index as usize)),
+				_ => error,
+			},
+			"P" => {
+				let mut parts = rest.splitn(2, '=');
+				match (parts.next().and_then(number), parts.next().and_then(parse_register)) {
+					(Some(index), Some(value)) if (index as usize) < REGISTERS => {
+						set_register(cpu, index as usize, value);
+						"OK".to_string()
+					},
+					_ => error,
+				}
+			},
+			"m" => match range(rest) {
+				Some((address, length)) => (0..length)
+					.map(|i| format!("{:02x}", cpu.memory.peek(address as usize + i as usize) as u8))
+					.collect(),
+				None => error,
+			},
+			"M" => {
+				let mut parts = rest.splitn(2, ':');
+				match (parts.next().and_then(range), parts.next().and_then(parse_hex_bytes)) {
+					(Some((address, length)), Some(ref bytes)) if bytes.len() == length as usize => {
+						for (i, &byte) in bytes.iter().enumerate() {
+							cpu.memory.write_byte(address as usize + i, byte as i8);
+						}
+						"OK".to_string()
+					},
+					_ => error,
+				}
+			},
+			"Z" | "z" => {
+				let parts: Vec<&str> = rest.split(',').collect();
+				if parts.len() < 2 || (parts[0] != "0" && parts[0] != "1") { //Hardware breakpoints work the same
+					return Some(String::new());
+				}
+				match number(parts[1]) {
+					Some(address) if address <= 0xFFFF => {
+						let address = address as u16;
+						cpu.breakpoints.retain(|&breakpoint| breakpoint != address);
+						self.breakpoints.retain(|&breakpoint| breakpoint != address);
+						if kind == "Z" {
+							cpu.breakpoints.push(address);
+							self.breakpoints.push(address);
+						}
+						"OK".to_string()
+					},
+					_ => error,
+				}
+			},
+			"s" | "c" => {
+				if let Some(address) = number(rest) {
+					cpu.pc = address as u16;
+				}
+				cpu.skip_break = true; //Even when a breakpoint is where it stopped
+				if kind == "s" {
+					cpu.run_for(1, input, video, audio);
+					if cpu.halted.is_some() { HALTED } else { TRAP }.to_string()
+				} else {
+					cpu.break_hit = None;
+					self.running = true; //The frontend runs frames until a breakpoint
+					return None;
+				}
+			},
+			"H" => "OK".to_string(),
+			_ => match command {
+				"qAttached" => "1".to_string(),
+				"qC" => "QC1".to_string(),
+				"qfThreadInfo" => "m1".to_string(),
+				"qsThreadInfo" => "l".to_string(),
+				"QStartNoAckMode" => "OK".to_string(),
+				_ if command.starts_with("qSupported") => "PacketSize=4000;QStartNoAckMode+".to_string(),
+				_ => String::new(), //Not supported
+			},
+		})
+	}
+}
+
+//Runs the program headless for a debugger until it detaches
+pub fn serve(cpu: &mut Cpu, server: &mut Server) -> Result<(), String> {
+	let (mut input, mut video) = (NullInput::new(), NullVideo::new());
+	let mut attached = false;
+	loop {
+		server.poll(cpu, &mut input, &mut video, &mut NullAudio)?;
+		if server.connected() {
+			attached = true;
+		} else if attached {
+			return Ok(());
+		}
+		if server.connected() && server.running() {
+			cpu.run_frame(&mut input, &mut video, &mut NullAudio);
+		} else {
+			thread::sleep(Duration::from_millis(1));
+		}
+	}
+}
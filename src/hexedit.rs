@@ -0,0 +1,153 @@
+use image::{Rgba, RgbaImage};
+use cpu::Cpu;
+use font::{self, GLYPH_WIDTH, GLYPH_HEIGHT};
+use overlay;
+
+//In Chip16 pixels, ADDR then 8 bytes in hex and as text
+const COLUMNS: u32 = 4 + 1 + 8 * 3 + 8;
+pub const WIDTH: u32 = COLUMNS * GLYPH_WIDTH + 4;
+const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 1;
+pub const ROWS: u32 = 240 / LINE_HEIGHT - 1; //Under the title
+const BYTES_PER_ROW: u32 = 8;
+
+const BACKGROUND: Rgba<u8> = Rgba([0x10, 0x18, 0x18, 255]);
+const CURSOR: Rgba<u8> = Rgba([0x30, 0x40, 0x7F, 255]);
+const TEXT: Rgba<u8> = Rgba([0xC0, 0xC0, 0xC0, 255]);
+const WRITTEN: Rgba<u8> = Rgba([0xFF, 0xD0, 0x40, 255]);
+const DIM: Rgba<u8> = Rgba([0x80, 0x90, 0x90, 255]);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Follow {
+	Nothing,
+	Pc,
+	Sp,
+}
+
+pub struct HexEditor {
+	pub top: u16, //Address of the first row, a multiple of 8
+	pub cursor: u16,
+	pub follow: Follow,
+	high: Option<u8>, //The first digit typed of a byte
+}
+
+impl Follow {
+	pub fn next(&self) -> Follow {
+		match *self {
+			Follow::Nothing => Follow::Pc,
+			Follow::Pc => Follow::Sp,
+			Follow::Sp => Follow::Nothing,
+		}
+	}
+}
+
+impl Default for HexEditor {
+	fn default() -> HexEditor {
+		HexEditor::new()
+	}
+}
+
+impl HexEditor {
+	pub fn new() -> HexEditor {
+		HexEditor { top: 0, cursor: 0, follow: Follow::Nothing, high: None }
+	}
+
+	//Moves the cursor to what it follows
+	pub fn sync(&mut self, cpu: &Cpu) -> () {
+		match self.follow {
+			Follow::Pc => self.place(cpu.pc),
+			Follow::Sp => self.place(cpu.sp),
+			Follow::Nothing => {},
+		}
+	}
+
+	//Puts the cursor on an address and scrolls so its row shows
+	fn place(&mut self, address: u16) -> () {
+		self.cursor = address;
+		let row = address as u32 / BYTES_PER_ROW;
+		let top = self.top as u32 / BYTES_PER_ROW;
+		let last = 0x10000 / BYTES_PER_ROW - ROWS;
+		let top = if row < top { row } else if row >= top + ROWS { row + 1 - ROWS } else { top };
+		self.top = (top.min(last) * BYTES_PER_ROW) as u16;
+	}
+
+	//Moving by hand stops following, what was typed of a byte is dropped
+	pub fn move_by(&mut self, delta: i32) -> () {
+		let address = (self.cursor as i32 + delta).clamp(0, 0xFFFF);
+		self.follow = Follow::Nothing;
+		self.high = None;
+		self.place(address as u16);
+	}
+
+	pub fn go_to(&mut self, address: u16) -> () {
+		self.follow = Follow::Nothing;
+		self.high = None;
+		self.place(address);
+	}
+
+	//A hex digit, every second one writes the byte and moves on. Returns the address written
+	pub fn type_digit(&mut self, cpu: &mut Cpu, digit: u8) -> Option<u16> {
+		self.follow = Follow::Nothing; //Or the cursor would jump away in the middle of a byte
+		match self.high.take() {
+			None => {
+				self.high = Some(digit & 0xF);
+				None
+			},
+			Some(high) => {
+				let address = self.cursor;
+				cpu.memory.write_byte(address as usize, ((high << 4) | (digit & 0xF)) as i8);
+				self.place(address.saturating_add(1));
+				Some(address)
+			},
+		}
+	}
+
+	pub fn title(&self, editing: bool) -> String {
+		let follow = match self.follow {
+			Follow::Nothing => "",
+			Follow::Pc => ", follows PC",
+			Follow::Sp => ", follows SP",
+		};
+		format!("#{:04X}{}{}", self.cursor, follow, if editing { ", editing" } else { "" })
+	}
+
+	//The editor as an image to go beside the screen, scale is the size of a Chip16 pixel
+	pub fn render(&self, cpu: &Cpu, editing: bool, scale: u32) -> RgbaImage {
+		let scale = scale.max(1);
+		let mut image = RgbaImage::from_pixel(WIDTH * scale, 240 * scale, BACKGROUND);
+		let cell = (GLYPH_WIDTH * scale) as i64;
+		let text = |image: &mut RgbaImage, column: u32, row: u32, string: &str, colour: Rgba<u8>| {
+			let (x, y) = (2 * scale + column * GLYPH_WIDTH * scale, scale + row * LINE_HEIGHT * scale);
+			font::draw_text(image, x as i64, y as i64, string, colour, scale);
+		};
+		text(&mut image, 0, 0, &self.title(editing), DIM);
+		for row in 0..ROWS {
+			let start = self.top as u32 + row * BYTES_PER_ROW;
+			if start > 0xFFFF {
+				break;
+			}
+			text(&mut image, 0, row + 1, &format!("{:04X}", start), DIM);
+			for i in 0..BYTES_PER_ROW {
+				let address = (start + i) as u16;
+				let byte = cpu.memory.peek(address as usize) as u8;
+				let hex_column = 5 + i * 3;
+				let ascii_column = 5 + BYTES_PER_ROW * 3 + i;
+				if address == self.cursor {
+					let y = ((1 + (row + 1) * LINE_HEIGHT) * scale) as i64 - scale as i64;
+					for &(column, width) in [(hex_column, 2), (ascii_column, 1)].iter() {
+						let x = (2 * scale + column * GLYPH_WIDTH * scale) as i64;
+						overlay::fill(&mut image, x, y, width * cell, (LINE_HEIGHT * scale) as i64, CURSOR);
+					}
+				}
+				let colour = if cpu.memory.was_written(address as usize) { WRITTEN } else { TEXT };
+				let hex = match self.high {
+					Some(high) if address == self.cursor => format!("{:X}_", high),
+					_ => format!("{:02X}", byte),
+				};
+				text(&mut image, hex_column, row + 1, &hex, colour);
+				let c = if (0x20..0x7F).contains(&byte) { byte as char } else { '.' };
+				text(&mut image, ascii_column, row + 1, &c.to_string(), colour);
+			}
+		}
+		image
+	}
+}
@@ -0,0 +1,75 @@
+use image::{Rgba, RgbaImage};
+use cpu::{Cpu, INSTRUCTIONS_PER_FRAME};
+use font::{self, GLYPH_HEIGHT};
+use frontend::keys;
+use overlay;
+
+const BACKGROUND: Rgba<u8> = Rgba([0x00, 0x00, 0x00, 0xA0]);
+const TEXT: Rgba<u8> = Rgba([0xFF, 0xFF, 0x60, 255]);
+
+//Rates are counted over a second so they don't flicker
+pub struct Hud {
+	pub fps: f64, //Frames the window showed
+	pub vblank_rate: f64, //Frames the emulator ran
+	elapsed: u64, //Microseconds
+	rendered: u32,
+	vblanks: u32,
+}
+
+//The buttons held in a controller bitmask, like Up+A
+pub fn pad_names(bits: u16) -> String {
+	let names: Vec<String> = keys::BUTTONS.iter()
+		.filter(|&&(_, pad)| bits & pad as u16 != 0)
+		.map(|&(_, pad)| format!("{:?}", pad))
+		.collect();
+	if names.is_empty() { "-".to_string() } else { names.join("+") }
+}
+
+impl Default for Hud {
+	fn default() -> Hud {
+		Hud::new()
+	}
+}
+
+impl Hud {
+	pub fn new() -> Hud {
+		Hud { fps: 0.0, vblank_rate: 0.0, elapsed: 0, rendered: 0, vblanks: 0 }
+	}
+
+	//dt in microseconds, with the frames shown and run in that time
+	pub fn tick(&mut self, dt: u64, rendered: u32, vblanks: u32) -> () {
+		self.elapsed += dt;
+		self.rendered += rendered;
+		self.vblanks += vblanks;
+		if self.elapsed >= 1000000 {
+			let seconds = self.elapsed as f64 / 1000000.0;
+			self.fps = self.rendered as f64 / seconds;
+			self.vblank_rate = self.vblanks as f64 / seconds;
+			self.elapsed = 0;
+			self.rendered = 0;
+			self.vblanks = 0;
+		}
+	}
+
+	pub fn lines(&self, cpu: &Cpu, controllers: (u16, u16)) -> Vec<String> {
+		vec![
+			format!("FPS {:.1}  VBLANK {:.1}/s", self.fps, self.vblank_rate),
+			format!("Instructions {}/{} per vblank", cpu.busy, INSTRUCTIONS_PER_FRAME),
+			format!("PC #{:04X}  SP #{:04X}", cpu.pc, cpu.sp),
+			format!("P1 {:04X} {}", controllers.0, pad_names(controllers.0)),
+			format!("P2 {:04X} {}", controllers.1, pad_names(controllers.1)),
+		]
+	}
+}
+
+//Text in the top left corner over a dark box, scale is the size of a Chip16 pixel in the frame
+pub fn draw(frame: &mut RgbaImage, lines: &[String], scale: u32) -> () {
+	let scale = scale.max(1);
+	let line_height = (GLYPH_HEIGHT + 1) * scale;
+	let width = lines.iter().map(|line| font::text_width(line, scale)).max().unwrap_or(0) + 3 * scale;
+	let height = lines.len() as u32 * line_height + 2 * scale;
+	overlay::fill(frame, 0, 0, width as i64, height as i64, BACKGROUND);
+	for (i, line) in lines.iter().enumerate() {
+		font::draw_text(frame, 2 * scale as i64, (2 * scale + i as u32 * line_height) as i64, line, TEXT, scale);
+	}
+}
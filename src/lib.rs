@@ -0,0 +1,2585 @@
+#[cfg(feature = "tools")]
+extern crate image;
+extern crate rand;
+#[cfg(feature = "piston")]
+extern crate piston_window;
+
+//The core, what runs a program and the traits a frontend implements
+pub mod cpu;
+pub mod opcode;
+pub mod loading;
+pub mod frontend;
+pub mod palette;
+pub mod disasm;
+pub mod cheats;
+pub mod symbols;
+pub mod profile;
+pub mod coverage;
+
+//The command line, the windows and terminal frontends and the debugging tools, with the image files and sockets they need
+#[cfg(feature = "tools")]
+pub mod config;
+#[cfg(feature = "tools")]
+pub mod filters;
+#[cfg(feature = "tools")]
+pub mod recording;
+#[cfg(feature = "tools")]
+pub mod viewer;
+#[cfg(feature = "tools")]
+pub mod overlay;
+#[cfg(feature = "tools")]
+pub mod cli;
+#[cfg(feature = "tools")]
+pub mod games;
+#[cfg(feature = "tools")]
+pub mod font;
+#[cfg(feature = "tools")]
+pub mod browser;
+#[cfg(feature = "tools")]
+pub mod watch;
+#[cfg(feature = "tools")]
+pub mod hud;
+#[cfg(feature = "tools")]
+pub mod panel;
+#[cfg(feature = "tools")]
+pub mod hexedit;
+#[cfg(feature = "tools")]
+pub mod console;
+#[cfg(feature = "tools")]
+pub mod gdb;
+
+#[cfg(all(test, feature = "tools"))]
+mod tests {
+	#![allow(overflowing_literals, dead_code)]
+	use cpu::{Cpu, Sound, DrawCall, Chip16Graphics, Memory, Pad, CallFrame};
+	use opcode::Opcode;
+	use frontend::headless::{NullVideo, NullInput, NullAudio};
+	use frontend::terminal;
+	use frontend::{InputSource, ScaleMode, Viewport};
+	use config::Config;
+	use filters::{self, Filter};
+	use image::{imageops, Rgba, RgbaImage};
+	use image::AnimationDecoder;
+	use image::codecs::gif::GifDecoder;
+	use recording::{self, Recorder, RecordFormat};
+	use palette;
+	use viewer::{self, SpriteSheet, SpriteViewer};
+	use overlay::{self, DrawLogWriter};
+	use font;
+	use browser::{self, Browser};
+	use watch::Watcher;
+	use hud::{self, Hud};
+	use panel;
+	use hexedit::{self, HexEditor, Follow};
+	use cheats::{self, Cheat};
+	use console::Console;
+	use frontend::headless;
+	use std::env;
+	use std::fs::{self, File};
+	use std::io::BufReader;
+	use std::sync::mpsc::channel;
+	use rand::{Rng, SeedableRng};
+	use rand::rngs::StdRng;
+	use cli::{self, Command};
+	use disasm;
+	use symbols::{self, Symbols};
+	use gdb;
+	use profile::{Profile, Routine};
+	use coverage::Coverage;
+	use std::net::TcpStream;
+	use std::time::Duration;
+	use loading;
+	use frontend::keys::KeyBindings;
+	use games::{self, GameDatabase};
+	use std::io::{Read, Write};
+	use std::path::{Path, PathBuf};
+	
+	fn image_from_rows(rows: &[&str]) -> RgbaImage { //R, W and K pixels, for small golden images
+		RgbaImage::from_fn(rows[0].len() as u32, rows.len() as u32, |x, y| {
+			match rows[y as usize].as_bytes()[x as usize] {
+				b'R' => Rgba([255, 0, 0, 255]),
+				b'W' => Rgba([255, 255, 255, 255]),
+				_ => Rgba([0, 0, 0, 255]),
+			}
+		})
+	}
+	
+	fn stage_1op_test(op: Opcode, byte1: i8, byte2: i8, byte3: i8) -> Cpu {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(op, byte1, byte2, byte3);
+		cpu.start_test(1);
+		cpu
+	}
+
+	#[test]
+	fn new_cpu() -> () {
+		let cpu = stage_1op_test(Opcode::Nop, 0, 0, 0);
+		assert_eq!(cpu.pc, 4);
+	}
+
+	#[test]
+	fn jmp() -> () {
+		let cpu = stage_1op_test(Opcode::Jmp, 0, 0x10, 0x20);
+		assert_eq!(cpu.pc, 0x2010);
+	}
+	
+	#[test]
+	fn flip() -> () {
+		let cpu0 = stage_1op_test(Opcode::Flip, 0, 0, 0);
+		let cpu1 = stage_1op_test(Opcode::Flip, 0, 0, 1);
+		let cpu2 = stage_1op_test(Opcode::Flip, 0, 0, 2);
+		let cpu3 = stage_1op_test(Opcode::Flip, 0, 0, 3);
+		assert_eq!(cpu0.graphics.state.hflip, false);
+		assert_eq!(cpu0.graphics.state.vflip, false);
+		
+		assert_eq!(cpu1.graphics.state.hflip, false);
+		assert_eq!(cpu1.graphics.state.vflip, true);
+		
+		assert_eq!(cpu2.graphics.state.hflip, true);
+		assert_eq!(cpu2.graphics.state.vflip, false);
+		
+		assert_eq!(cpu3.graphics.state.hflip, true);
+		assert_eq!(cpu3.graphics.state.vflip, true);
+	}
+	
+	#[test]
+	fn flags() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.put_carry(true);
+		cpu.put_zero(true);
+		cpu.put_overflow(true);
+		cpu.put_negative(true);
+		assert!(cpu.has_carry() && cpu.has_zero() && cpu.has_overflow() && cpu.has_negative());
+		cpu.put_carry(false);
+		cpu.put_zero(false);
+		cpu.put_overflow(false);
+		cpu.put_negative(false);
+		assert!(!(cpu.has_carry() || cpu.has_zero() || cpu.has_overflow() || cpu.has_negative()));
+	}
+	
+	#[test]
+	fn jmc() -> () {
+		let mut cpu0 = Cpu::new_test();
+		cpu0.put_carry(true);
+		cpu0.add_opcode(Opcode::Jmc, 0, 0x10, 0x20);
+		cpu0.start_test(1);
+		assert_eq!(cpu0.pc, 0x2010);
+		
+		let mut cpu1 = Cpu::new_test();
+		cpu1.put_carry(false);
+		cpu1.add_opcode(Opcode::Jmc, 0, 0x10, 0x20);
+		cpu1.start_test(1);
+		assert_eq!(cpu1.pc, 0x4);
+	}
+	
+	#[test]
+	fn jx_flags() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.put_zero(true);
+		if !cpu.check_flags(0).unwrap() {
+			panic!("{}", 0);
+		}
+		
+		cpu.put_zero(false);
+		if !cpu.check_flags(1).unwrap() {
+			panic!("{}", 1);
+		}
+		
+		cpu.put_negative(true);
+		if !cpu.check_flags(2).unwrap() {
+			panic!("{}", 2);
+		}
+		
+		cpu.put_negative(false);
+		if !cpu.check_flags(3).unwrap() {
+			panic!("{}", 3);
+		}
+		
+		cpu.put_zero(false);
+		cpu.put_negative(false);
+		if !cpu.check_flags(4).unwrap() {
+			panic!("{}", 4);
+		}
+		
+		cpu.put_overflow(true);
+		if !cpu.check_flags(5).unwrap() {
+			panic!("{}", 5);
+		}
+		
+		cpu.put_overflow(false);
+		if !cpu.check_flags(6).unwrap() {
+			panic!("{}", 6);
+		}
+		
+		cpu.put_carry(false);
+		cpu.put_zero(false);
+		if !cpu.check_flags(7).unwrap() {
+			panic!("{}", 7);
+		}
+		
+		if !cpu.check_flags(8).unwrap() {
+			panic!("{}", 8);
+		}
+		
+		cpu.put_carry(true);
+		if !cpu.check_flags(9).unwrap() {
+			panic!("{}", 9);
+		}
+		
+		cpu.put_carry(true);
+		if !cpu.check_flags(10).unwrap() {
+			panic!("{}", 10);
+		}
+		
+		cpu.put_negative(true);
+		cpu.put_overflow(true);
+		cpu.put_zero(false);
+		if !cpu.check_flags(11).unwrap() {
+			panic!("{}", 11);
+		}
+		
+		cpu.put_negative(false);
+		cpu.put_overflow(false);
+		cpu.put_zero(false);
+		if !cpu.check_flags(11).unwrap() {
+			panic!("{}", 11);
+		}
+		
+		cpu.put_negative(true);
+		cpu.put_overflow(true);
+		cpu.put_zero(true);
+		if cpu.check_flags(11).unwrap() {
+			panic!("{}", 11);
+		}
+		
+		cpu.put_negative(true);
+		cpu.put_overflow(true);
+		if !cpu.check_flags(12).unwrap() {
+			panic!("{}", 12);
+		}
+		
+		cpu.put_negative(false);
+		cpu.put_overflow(false);
+		if !cpu.check_flags(12).unwrap() {
+			panic!("{}", 12);
+		}
+		
+		cpu.put_negative(false);
+		cpu.put_overflow(true);
+		if cpu.check_flags(12).unwrap() {
+			panic!("{}", 12);
+		}
+		
+		cpu.put_overflow(true);
+		cpu.put_negative(false);
+		if !cpu.check_flags(13).unwrap() {
+			panic!("{}", 13);
+		}
+		
+		cpu.put_overflow(true);
+		cpu.put_negative(true);
+		if cpu.check_flags(13).unwrap() {
+			panic!("{}", 13);
+		}
+		
+		cpu.put_overflow(true);
+		cpu.put_negative(false);
+		cpu.put_zero(false);
+		if !cpu.check_flags(14).unwrap() {
+			panic!("{}", 14);
+		}
+		
+		cpu.put_overflow(true);
+		cpu.put_negative(true);
+		cpu.put_zero(false);
+		if cpu.check_flags(14).unwrap() {
+			panic!("{}", 14);
+		}
+		
+		cpu.put_overflow(false);
+		cpu.put_negative(false);
+		cpu.put_zero(true);
+		if !cpu.check_flags(14).unwrap() {
+			panic!("{}", 14);
+		}
+	}
+	
+	#[test]
+	fn jme() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.set_rx(5, 300);
+		cpu.set_rx(6, 300);
+		cpu.add_opcode(Opcode::Jme, 0x56, 0x10, 0x20);
+		cpu.start_test(1);
+		assert_eq!(cpu.pc, 0x2010);
+
+		cpu.pc = 0;
+		cpu.set_rx(5, 300);
+		cpu.set_rx(6, 299);
+		cpu.add_opcode(Opcode::Jme, 0x56, 0x10, 0x20);
+		cpu.start_test(1);
+		assert_eq!(cpu.pc, 0x4);
+	}
+	
+	#[test]
+	fn call() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Call, 0, 0x10, 0x20);
+		cpu.pc = 0x2010;
+		cpu.add_opcode(Opcode::Ret, 0, 0, 0);
+		cpu.start_test(1);
+		assert_eq!(cpu.pc, 0x2010);
+		assert_eq!(cpu.sp, 0xFDF2);
+		assert_eq!(cpu.memory.read_word(0xFDF0), 0x4);
+		
+		cpu.step().unwrap();
+		
+		assert_eq!(cpu.pc, 0x4);
+	}
+	
+	#[test]
+	fn ldi() -> () {
+		let mut cpu = stage_1op_test(Opcode::Ldi, 5, 0x10, 0x20);
+		assert_eq!(cpu.get_rx(5), 0x2010);
+	}
+	
+	#[test]
+	fn ldisp() -> () {
+		let cpu = stage_1op_test(Opcode::Ldi2, 0, 0x10, 0x20);
+		assert_eq!(cpu.sp, 0x2010);
+	}
+	
+	#[test]
+	fn ldm() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.memory.write_word(0x20, 10000);
+		cpu.add_opcode(Opcode::Ldm, 5, 0x20, 00);
+		cpu.start_test(1);
+		
+		assert_eq!(10000, cpu.get_rx(5));
+	}
+	
+	#[test]
+	fn ldm2() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.memory.write_word(0x20, 10000);
+		cpu.set_rx(4, 0x20); 
+		cpu.add_opcode(Opcode::Ldm2, 0x45, 0, 00);
+		cpu.start_test(1);
+		
+		assert_eq!(10000, cpu.get_rx(5));
+	}
+	
+	#[test]
+	fn mov() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.set_rx(4, 0x20); 
+		cpu.add_opcode(Opcode::Mov, 0x45, 0, 00);
+		cpu.start_test(1);
+		
+		assert_eq!(0x20, cpu.get_rx(5));
+	}
+	
+	#[test]
+	fn stm() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.set_rx(4, 0x1050);
+		cpu.add_opcode(Opcode::Stm, 4, 0x10, 0x20);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.memory.read_word(0x2010), 0x1050);
+	}
+	
+	#[test]
+	fn stm2() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.set_rx(4, 0x1050);
+		cpu.set_rx(5, 0x20);
+		cpu.add_opcode(Opcode::Stm2, 0x54, 0, 0);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.memory.read_word(0x20), 0x1050);
+	}
+	
+	#[test]
+	fn addi() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Addi, 5, 0x05, 0x20);
+		cpu.add_opcode(Opcode::Addi, 5, 0xFB, 0xDF); //This is negative 0x2005
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(5), 0x2005);
+		cpu.step().unwrap();
+		assert_eq!(cpu.get_rx(5), 0);
+		assert!(cpu.has_zero());
+	}
+	
+	#[test]
+	fn add() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Add, 0x65, 0, 0);
+		cpu.set_rx(5, 300);
+		cpu.set_rx(6, 300);
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(5), 600);
+		assert!(!cpu.has_zero() && !cpu.has_negative() && !cpu.has_overflow() && !cpu.has_carry());
+	}
+	
+	#[test]
+	fn add2() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Add2, 0x65, 7, 0);
+		cpu.set_rx(5, 300);
+		cpu.set_rx(6, 300);
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(7), 600);
+		assert!(!cpu.has_zero() && !cpu.has_negative() && !cpu.has_overflow() && !cpu.has_carry());
+	}
+	
+	#[test]
+	fn add_flags() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.set_rx(5, -30000);
+		cpu.add_opcode(Opcode::Addi, 5, 0xD0, 0x8A);
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(5), 5536);
+		assert!(cpu.has_carry());
+		assert!(cpu.has_overflow());
+		
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Addi, 5, 0xD0, 0x8A);
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(5), -30000);
+		assert!(cpu.has_negative());
+	}
+	
+	#[test]
+	fn subi() -> () {
+		let mut cpu = stage_1op_test(Opcode::Subi, 5, 0x10, 0x20);
+		assert_eq!(cpu.get_rx(5), -0x2010);
+		assert!(cpu.has_negative());
+		assert!(cpu.has_carry());
+		assert!(!cpu.has_overflow());
+		assert!(!cpu.has_zero());
+	}
+	
+	#[test]
+	fn sub() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.set_rx(5, 0x2222);
+		cpu.set_rx(6, 0x1111);
+		cpu.add_opcode(Opcode::Sub, 0x65, 0, 0);
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(5), 0x1111);
+	}
+	
+	#[test]
+	fn cmpi_flags() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Cmpi, 5, 0x10, 0x7F);
+		cpu.set_rx(5, -30000);
+		cpu.start_test(1);
+		assert!(!cpu.has_negative());
+		assert!(!cpu.has_carry());
+		assert!(cpu.has_overflow());
+		assert!(!cpu.has_zero());
+	}
+	
+	#[test]
+	fn andi() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Andi, 15, 0b101, 0b101);
+		cpu.set_rx(15, 0b011000000110);
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(15), 0b010000000100);
+		assert!(!cpu.has_negative() && !cpu.has_zero());
+	}
+	
+	#[test]
+	fn and() -> () {
+		let mut cpu1 = Cpu::new_test();
+		cpu1.add_opcode(Opcode::And, 0x65, 0, 0);
+		cpu1.set_rx(5, 1);
+		cpu1.set_rx(6, 2);
+		cpu1.start_test(1);
+		assert_eq!(cpu1.get_rx(5), 0);
+		assert!(!cpu1.has_negative() && cpu1.has_zero());
+		
+		let mut cpu2 = Cpu::new_test();
+		cpu2.add_opcode(Opcode::And2, 0x65, 7, 0);
+		cpu2.set_rx(5, 0xFFFF);
+		cpu2.set_rx(6, 0xF00F);
+		cpu2.start_test(1);
+		assert_eq!(cpu2.get_rx(7), 0xF00F);
+		assert!(cpu2.has_negative() && !cpu2.has_zero());
+	}
+	
+	#[test]
+	fn ori() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Ori, 15, 0xAB, 0x00);
+		cpu.set_rx(15, 0xAB00);
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(15), 0xABAB);
+		assert!(cpu.has_negative() && !cpu.has_zero());
+	}
+	
+	#[test]
+	fn or() -> () {
+		let mut cpu1 = Cpu::new_test();
+		cpu1.add_opcode(Opcode::Or, 0x65, 0, 0);
+		cpu1.set_rx(5, 1);
+		cpu1.set_rx(6, 2);
+		cpu1.start_test(1);
+		assert_eq!(cpu1.get_rx(5), 3);
+		assert!(!cpu1.has_negative() && !cpu1.has_zero());
+		
+		let mut cpu2 = Cpu::new_test();
+		cpu2.add_opcode(Opcode::Or2, 0x65, 7, 0);
+		cpu2.set_rx(5, 0x0FF0);
+		cpu2.set_rx(6, 0xF00F);
+		cpu2.start_test(1);
+		assert_eq!(cpu2.get_rx(7), 0xFFFF);
+		assert!(cpu2.has_negative() && !cpu2.has_zero());
+	}
+	
+	#[test]
+	fn xori() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Xori, 15, 0xAB, 0x01);
+		cpu.set_rx(15, 0xAB00);
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(15), 0xAAAB);
+		assert!(cpu.has_negative() && !cpu.has_zero());
+	}
+	
+	#[test]
+	fn xor() -> () {
+		let mut cpu1 = Cpu::new_test();
+		cpu1.add_opcode(Opcode::Xor, 0x65, 0, 0);
+		cpu1.set_rx(5, 2);
+		cpu1.set_rx(6, 2);
+		cpu1.start_test(1);
+		assert_eq!(cpu1.get_rx(5), 0);
+		assert!(!cpu1.has_negative() && cpu1.has_zero());
+		
+		let mut cpu2 = Cpu::new_test();
+		cpu2.add_opcode(Opcode::Xor2, 0x65, 7, 0);
+		cpu2.set_rx(5, 0x0FFF);
+		cpu2.set_rx(6, 0xF00F);
+		cpu2.start_test(1);
+		assert_eq!(cpu2.get_rx(7), 0xFFF0);
+		assert!(cpu2.has_negative() && !cpu2.has_zero());
+	}
+	
+	#[test]
+	fn muli() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Muli, 5, 6, 0);
+		cpu.set_rx(5, 10);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.get_rx(5), 60);
+		assert!(!cpu.has_carry() && !cpu.has_negative() && !cpu.has_zero());
+	}
+	
+	#[test]
+	fn mul() -> () {
+		let mut cpu1 = Cpu::new_test();
+		cpu1.add_opcode(Opcode::Mul, 0x65, 0, 0);
+		cpu1.set_rx(5, 250);
+		cpu1.set_rx(6, 0);
+		cpu1.start_test(1);
+		assert_eq!(cpu1.get_rx(5), 0);
+		assert!(!cpu1.has_negative() && cpu1.has_zero() && !cpu1.has_carry());
+		
+		let mut cpu2 = Cpu::new_test();
+		cpu2.add_opcode(Opcode::Mul2, 0x65, 7, 0);
+		cpu2.set_rx(5, 250);
+		cpu2.set_rx(6, 250);
+		cpu2.start_test(1);
+		assert_eq!(cpu2.get_rx(7), -3036);
+		assert!(cpu2.has_negative());
+		assert!(!cpu2.has_carry());
+		assert!(!cpu2.has_zero());
+	}
+	
+	#[test]
+	fn divi() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Divi, 5, 6, 0);
+		cpu.set_rx(5, 61);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.get_rx(5), 10);
+		assert!(cpu.has_carry() && !cpu.has_negative() && !cpu.has_zero());
+	}
+	
+	#[test]
+	fn div() -> () {
+		let mut cpu1 = Cpu::new_test();
+		cpu1.add_opcode(Opcode::Div, 0x65, 0, 0);
+		cpu1.set_rx(5, 250);
+		cpu1.set_rx(6, 260);
+		cpu1.start_test(1);
+		assert_eq!(cpu1.get_rx(5), 0);
+		assert!(!cpu1.has_negative() && cpu1.has_zero() && cpu1.has_carry());
+		
+		let mut cpu2 = Cpu::new_test();
+		cpu2.add_opcode(Opcode::Div2, 0x65, 7, 0);
+		cpu2.set_rx(5, -250);
+		cpu2.set_rx(6, 250);
+		cpu2.start_test(1);
+		assert_eq!(cpu2.get_rx(7), -1);
+		assert!(cpu2.has_negative());
+		assert!(!cpu2.has_carry());
+		assert!(!cpu2.has_zero());
+	}
+	
+	#[test]
+	fn modi() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Modi, 5, 6, 0);
+		cpu.set_rx(5, 61);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.get_rx(5), 1);
+		assert!(!cpu.has_negative() && !cpu.has_zero());
+	}
+	
+	#[test]
+	fn mod1() -> () {
+		let mut cpu1 = Cpu::new_test();
+		cpu1.add_opcode(Opcode::Mod, 0x65, 0, 0);
+		cpu1.set_rx(5, 250);
+		cpu1.set_rx(6, 260);
+		cpu1.start_test(1);
+		assert_eq!(cpu1.get_rx(5), 250);
+		assert!(!cpu1.has_negative() && !cpu1.has_zero());
+		
+		let mut cpu2 = Cpu::new_test();
+		cpu2.add_opcode(Opcode::Mod2, 0x65, 7, 0);
+		cpu2.set_rx(5, -240);
+		cpu2.set_rx(6, 250);
+		cpu2.start_test(1);
+		assert_eq!(cpu2.get_rx(7), 10);
+		assert!(!cpu2.has_negative());
+		assert!(!cpu2.has_zero());
+	}
+	
+	#[test]
+	fn remi() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Remi, 5, 6, 0);
+		cpu.set_rx(5, 61);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.get_rx(5), 1);
+		assert!(!cpu.has_negative() && !cpu.has_zero());
+	}
+	
+	#[test]
+	fn rem() -> () {
+		let mut cpu1 = Cpu::new_test();
+		cpu1.add_opcode(Opcode::Rem, 0x65, 0, 0);
+		cpu1.set_rx(5, 260);
+		cpu1.set_rx(6, 260);
+		cpu1.start_test(1);
+		assert_eq!(cpu1.get_rx(5), 0);
+		assert!(!cpu1.has_negative() && cpu1.has_zero());
+		
+		let mut cpu2 = Cpu::new_test();
+		cpu2.add_opcode(Opcode::Rem2, 0x65, 7, 0);
+		cpu2.set_rx(5, -240);
+		cpu2.set_rx(6, 250);
+		cpu2.start_test(1);
+		assert_eq!(cpu2.get_rx(7), -240);
+		assert!(cpu2.has_negative());
+		assert!(!cpu2.has_zero());
+	}
+	
+	#[test]
+	fn shl() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Shl, 5, 3, 0);
+		cpu.set_rx(5, 1);
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(5), 1<<3);
+		assert!(!cpu.has_zero());
+		assert!(!cpu.has_negative());
+	}
+	
+	#[test]
+	fn shr() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Shr, 5, 3, 0);
+		cpu.set_rx(5, 0xF000);
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(5), (0xF000u16 >> 3) as i16);
+		assert!(!cpu.has_zero());
+		assert!(!cpu.has_negative());
+	}
+	
+	#[test]
+	fn sar() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Sar, 5, 4, 0);
+		cpu.set_rx(5, 0xF000);
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(5), 0xFF00);
+		assert!(!cpu.has_zero());
+		assert!(cpu.has_negative());
+	}
+	
+	#[test]
+	fn shl2() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Shl2, 0x65, 0, 0);
+		cpu.set_rx(5, 1);
+		cpu.set_rx(6, 3);
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(5), 1<<3);
+		assert!(!cpu.has_zero());
+		assert!(!cpu.has_negative());
+	}
+	
+	#[test]
+	fn shr2() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Shr2, 0x65, 0, 0);
+		cpu.set_rx(5, 0xF000);
+		cpu.set_rx(6, 4);
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(5),0x0F00);
+		assert!(!cpu.has_zero());
+		assert!(!cpu.has_negative());
+	}
+	
+	#[test]
+	fn sar2() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Sar2, 0x65, 0, 0);
+		cpu.set_rx(5, 0xF000);
+		cpu.set_rx(6, 4);
+		cpu.start_test(1);
+		assert_eq!(cpu.get_rx(5),0xFF00);
+		assert!(!cpu.has_zero());
+		assert!(cpu.has_negative());
+	}
+	
+	#[test]
+	fn push_pop_stack() -> () {
+		let mut cpu = Cpu::new_test();
+		let sp = cpu.sp;
+		assert_eq!(sp, 0xFDF0);
+		cpu.add_opcode(Opcode::Push, 0x4, 0, 0);
+		cpu.add_opcode(Opcode::Pop, 0x5, 0, 0);
+		cpu.set_rx(4, 1000);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.sp, 0xFDF2);
+		assert_eq!(cpu.memory.read_word(sp as u16 as usize), 1000);
+		
+		cpu.step().unwrap();
+		
+		assert_eq!(cpu.get_rx(5), 1000);
+	}
+	
+	#[test]
+	fn pushall_popall() -> () {
+		let mut cpu = Cpu::new_test();
+		let sp = cpu.sp;
+		assert_eq!(sp, 0xFDF0);
+		cpu.add_opcode(Opcode::Pushall, 0, 0, 0);
+		cpu.add_opcode(Opcode::Popall, 0, 0, 0);
+		
+		cpu.set_rx(0, 1);
+		cpu.set_rx(1, 2);
+		cpu.set_rx(2, 3);
+		cpu.set_rx(3, 4);
+		cpu.set_rx(4, 5);
+		cpu.set_rx(5, 6);
+		cpu.set_rx(6, 7);
+		cpu.set_rx(7, 8);
+		cpu.set_rx(8, 9);
+		cpu.set_rx(9, 10);
+		cpu.set_rx(10, 11);
+		cpu.set_rx(11, 12);
+		cpu.set_rx(12, 13);
+		cpu.set_rx(13, 14);
+		cpu.set_rx(14, 15);
+		cpu.set_rx(15, 16);
+		
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.sp, 0xFDF0 + 32);
+		assert_eq!(cpu.memory.read_word(sp as usize), 1);
+		assert_eq!(cpu.memory.read_word(0xFDF0 + 30), 16);
+		
+		cpu.set_rx(0, 10);
+		cpu.set_rx(1, 20);
+		cpu.set_rx(2, 30);
+		cpu.set_rx(3, 40);
+		cpu.set_rx(4, 50);
+		cpu.set_rx(5, 60);
+		cpu.set_rx(6, 70);
+		cpu.set_rx(7, 80);
+		cpu.set_rx(8, 90);
+		cpu.set_rx(9, 100);
+		cpu.set_rx(10, 110);
+		cpu.set_rx(11, 120);
+		cpu.set_rx(12, 130);
+		cpu.set_rx(13, 140);
+		cpu.set_rx(14, 150);
+		cpu.set_rx(15, 160);
+		
+		cpu.step().unwrap();
+		assert_eq!(cpu.sp, 0xFDF0);
+		
+		assert_eq!(cpu.get_rx(0), 1);
+		assert_eq!(cpu.get_rx(1), 2);
+		assert_eq!(cpu.get_rx(2), 3);
+		assert_eq!(cpu.get_rx(3), 4);
+		assert_eq!(cpu.get_rx(4), 5);
+		assert_eq!(cpu.get_rx(5), 6);
+		assert_eq!(cpu.get_rx(6), 7);
+		assert_eq!(cpu.get_rx(7), 8);
+		assert_eq!(cpu.get_rx(8), 9);
+		assert_eq!(cpu.get_rx(9), 10);
+		assert_eq!(cpu.get_rx(10), 11);
+		assert_eq!(cpu.get_rx(11), 12);
+		assert_eq!(cpu.get_rx(12), 13);
+		assert_eq!(cpu.get_rx(13), 14);
+		assert_eq!(cpu.get_rx(14), 15);
+		assert_eq!(cpu.get_rx(15), 16);
+	}
+	
+	#[test]
+	fn pushf_popf() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Pushf, 0, 0, 0);
+		cpu.add_opcode(Opcode::Popf, 0, 0, 0);
+		
+		cpu.put_carry(true);
+		
+		cpu.start_test(1);
+		assert_eq!(cpu.sp, 0xFDF2);
+		
+		cpu.put_carry(false);
+		cpu.step().unwrap();
+		
+		assert!(cpu.has_carry());
+		assert_eq!(cpu.sp, 0xFDF0);
+	}
+	
+	#[test]
+	fn palette() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Pal, 0, 0x00, 0x0D);
+		cpu.memory.write_byte(0xD00, 0xFF);//first color
+		cpu.memory.write_byte(0xD01, 0xFF);
+		cpu.memory.write_byte(0xD02, 0xFF);
+		
+		cpu.memory.write_byte(0xD03, 0xEE);//second color
+		cpu.memory.write_byte(0xD04, 0xEE);
+		cpu.memory.write_byte(0xD05, 0xEE);
+		
+		cpu.memory.write_byte(0xD06, 0xDD);//third color
+		cpu.memory.write_byte(0xD07, 0xDD);
+		cpu.memory.write_byte(0xD08, 0xDD);
+		
+		cpu.memory.write_byte(0xD09, 0xCC);//fourth color
+		cpu.memory.write_byte(0xD0A, 0xCC);
+		cpu.memory.write_byte(0xD0B, 0xCC);
+		
+		cpu.memory.write_byte(0xD0C, 0xBB);//fifth color
+		cpu.memory.write_byte(0xD0D, 0xBB);
+		cpu.memory.write_byte(0xD0E, 0xBB);
+		
+		cpu.memory.write_byte(0xD00 + 45, 0x11);//sixteenth color
+		cpu.memory.write_byte(0xD00 + 46, 0x11);
+		cpu.memory.write_byte(0xD00 + 47, 0x11);
+		
+		assert_eq!(cpu.graphics.palette[0], 0);
+		assert_eq!(cpu.graphics.palette[1], 0);
+		assert_eq!(cpu.graphics.palette[2], 0x888888u32);
+		assert_eq!(cpu.graphics.palette[15], 0xFFFFFFu32);
+		
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.graphics.palette[0], 0xFFFFFFu32);
+		assert_eq!(cpu.graphics.palette[1], 0xEEEEEEu32);
+		assert_eq!(cpu.graphics.palette[2], 0xDDDDDDu32);
+		assert_eq!(cpu.graphics.palette[3], 0xCCCCCCu32);
+		assert_eq!(cpu.graphics.palette[4], 0xBBBBBBu32);
+		assert_eq!(cpu.graphics.palette[15], 0x111111u32);
+	}
+	
+	#[test]
+	fn noti() -> () {
+		let mut cpu = stage_1op_test(Opcode::Noti, 5, 6, 0);
+		assert_eq!(cpu.get_rx(5), !6);
+	}
+	
+	#[test]
+	fn negi() -> () {
+		let mut cpu = stage_1op_test(Opcode::Negi, 5, 6, 0);
+		assert_eq!(cpu.get_rx(5), -6);
+	}
+	
+	#[test]
+	fn not() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Not, 5, 0, 0);
+		
+		cpu.set_rx(5, 10);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.get_rx(5), !10);
+		assert!(!cpu.has_zero() && cpu.has_negative());
+		
+		cpu.set_rx(5, 0);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.get_rx(5), !0);
+		assert!(!cpu.has_zero() && cpu.has_negative());
+		
+		
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Not2, 0x05, 0, 0);
+		
+		cpu.set_rx(0, 10);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.get_rx(5), !10);
+		assert!(!cpu.has_zero() && cpu.has_negative());
+		
+		cpu.set_rx(0, !0);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.get_rx(5), 0);
+		assert!(cpu.has_zero() && !cpu.has_negative());
+	}
+	
+	#[test]
+	fn neg() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Neg, 5, 0, 0);
+		
+		cpu.set_rx(5, 10);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.get_rx(5), -10);
+		assert!(!cpu.has_zero() && cpu.has_negative());
+		
+		cpu.set_rx(5, 0);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.get_rx(5), -0);
+		assert!(cpu.has_zero() && !cpu.has_negative());
+		
+		
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Neg2, 0x05, 0, 0);
+		
+		cpu.set_rx(0, 10);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.get_rx(5), -10);
+		assert!(!cpu.has_zero() && cpu.has_negative());
+		
+		cpu.set_rx(0, -0);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.get_rx(5), 0);
+		assert!(cpu.has_zero() && !cpu.has_negative());
+	}
+	
+	#[test]
+	fn drw_noflip() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Spr, 0, 5, 10);
+		cpu.add_opcode(Opcode::Drw, 0x65, 0x55, 0x55);
+		cpu.set_rx(5, 10);
+		cpu.set_rx(6, 20);
+		cpu.flip(false, false);
+		
+		cpu.memory.write_byte(0x5555, 0xFE);
+		cpu.memory.write_byte(0x5555 + 1, 0xDC);
+		cpu.memory.write_byte(0x5555 + 2, 0xBA);
+		cpu.memory.write_byte(0x5555 + 5, 0x98);
+		cpu.memory.write_byte(0x5555 + 5 + 1, 0x76);
+		cpu.memory.write_byte(0x5555 + 5 * 2, 0x54);
+		
+		cpu.start_test(2);
+		
+		assert_eq!(cpu.graphics.state.spritew, 5);
+		assert_eq!(cpu.graphics.state.spriteh, 10);
+		
+		let screen = cpu.graphics.screen; //320x240
+		
+		assert_eq!(screen[320 * 20 + 10], 0xF);
+		assert_eq!(screen[320 * 20 + 11], 0xE);
+		assert_eq!(screen[320 * 20 + 12], 0xD);
+		assert_eq!(screen[320 * 20 + 13], 0xC);
+		assert_eq!(screen[320 * 20 + 14], 0xB);
+		assert_eq!(screen[320 * 20 + 15], 0xA);
+		
+		assert_eq!(screen[320 * 21 + 10], 0x9);
+		assert_eq!(screen[320 * 21 + 11], 0x8);
+		assert_eq!(screen[320 * 21 + 12], 0x7);
+		assert_eq!(screen[320 * 21 + 13], 0x6);
+		
+		assert_eq!(screen[320 * 22 + 10], 0x5);
+		assert_eq!(screen[320 * 22 + 11], 0x4);
+	}
+	
+	#[test]
+	fn drw_noflipv_fliph() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Spr, 0, 5, 10);
+		cpu.add_opcode(Opcode::Drw, 0x65, 0x55, 0x55);
+		cpu.set_rx(5, 10);
+		cpu.set_rx(6, 20);
+		cpu.flip(true, false);
+		
+		cpu.memory.write_byte(0x5555, 0xFE);
+		cpu.memory.write_byte(0x5555 + 1, 0xDC);
+		cpu.memory.write_byte(0x5555 + 2, 0xBA);
+		cpu.memory.write_byte(0x5555 + 5, 0x98);
+		cpu.memory.write_byte(0x5555 + 5 + 1, 0x76);
+		cpu.memory.write_byte(0x5555 + 5 * 2, 0x54);
+		
+		cpu.start_test(2);
+		
+		assert_eq!(cpu.graphics.state.spritew, 5);
+		assert_eq!(cpu.graphics.state.spriteh, 10);
+		
+		let screen = cpu.graphics.screen; //320x240
+		
+		assert_eq!(screen[320 * 20 + 19], 0xF);
+		assert_eq!(screen[320 * 20 + 18], 0xE);
+		assert_eq!(screen[320 * 20 + 17], 0xD);
+		assert_eq!(screen[320 * 20 + 16], 0xC);
+		assert_eq!(screen[320 * 20 + 15], 0xB);
+		assert_eq!(screen[320 * 20 + 14], 0xA);
+
+		assert_eq!(screen[320 * 21 + 19], 0x9);
+		assert_eq!(screen[320 * 21 + 18], 0x8);
+		assert_eq!(screen[320 * 21 + 17], 0x7);
+		assert_eq!(screen[320 * 21 + 16], 0x6);
+		
+		assert_eq!(screen[320 * 22 + 19], 0x5);
+		assert_eq!(screen[320 * 22 + 18], 0x4);
+	}
+	
+	#[test]
+	fn drw_nofliph_flipv() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Spr, 0, 5, 10);
+		cpu.add_opcode(Opcode::Drw, 0x65, 0x55, 0x55);
+		cpu.set_rx(5, 10);
+		cpu.set_rx(6, 20);
+		cpu.flip(false, true);
+		
+		cpu.memory.write_byte(0x5555, 0xFE);
+		cpu.memory.write_byte(0x5555 + 1, 0xDC);
+		cpu.memory.write_byte(0x5555 + 2, 0xBA);
+		cpu.memory.write_byte(0x5555 + 5, 0x98);
+		cpu.memory.write_byte(0x5555 + 5 + 1, 0x76);
+		cpu.memory.write_byte(0x5555 + 5 * 2, 0x54);
+		
+		cpu.start_test(2);
+		
+		assert_eq!(cpu.graphics.state.spritew, 5);
+		assert_eq!(cpu.graphics.state.spriteh, 10);
+		
+		let screen = cpu.graphics.screen; //320x240
+		
+		assert_eq!(screen[320 * 29 + 10], 0xF);
+		assert_eq!(screen[320 * 29 + 11], 0xE);
+		assert_eq!(screen[320 * 29 + 12], 0xD);
+		assert_eq!(screen[320 * 29 + 13], 0xC);
+		assert_eq!(screen[320 * 29 + 14], 0xB);
+		assert_eq!(screen[320 * 29 + 15], 0xA);
+
+		assert_eq!(screen[320 * 28 + 10], 0x9);
+		assert_eq!(screen[320 * 28 + 11], 0x8);
+		assert_eq!(screen[320 * 28 + 12], 0x7);
+		assert_eq!(screen[320 * 28 + 13], 0x6);
+		
+		assert_eq!(screen[320 * 27 + 10], 0x5);
+		assert_eq!(screen[320 * 27 + 11], 0x4);
+	}
+	
+	#[test]
+	fn drw_flip() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Spr, 0, 5, 10);
+		cpu.add_opcode(Opcode::Drw, 0x65, 0x55, 0x55);
+		cpu.set_rx(5, 10);
+		cpu.set_rx(6, 20);
+		cpu.flip(true, true);
+		
+		cpu.memory.write_byte(0x5555, 0xFE);
+		cpu.memory.write_byte(0x5555 + 1, 0xDC);
+		cpu.memory.write_byte(0x5555 + 2, 0xBA);
+		cpu.memory.write_byte(0x5555 + 5, 0x98);
+		cpu.memory.write_byte(0x5555 + 5 + 1, 0x76);
+		cpu.memory.write_byte(0x5555 + 5 * 2, 0x54);
+		
+		cpu.start_test(2);
+		
+		assert_eq!(cpu.graphics.state.spritew, 5);
+		assert_eq!(cpu.graphics.state.spriteh, 10);
+		
+		let screen = cpu.graphics.screen; //320x240
+		
+		assert_eq!(screen[320 * 29 + 19], 0xF);
+		assert_eq!(screen[320 * 29 + 18], 0xE);
+		assert_eq!(screen[320 * 29 + 17], 0xD);
+		assert_eq!(screen[320 * 29 + 16], 0xC);
+		assert_eq!(screen[320 * 29 + 15], 0xB);
+		assert_eq!(screen[320 * 29 + 14], 0xA);
+
+		assert_eq!(screen[320 * 28 + 19], 0x9);
+		assert_eq!(screen[320 * 28 + 18], 0x8);
+		assert_eq!(screen[320 * 28 + 17], 0x7);
+		assert_eq!(screen[320 * 28 + 16], 0x6);
+		
+		assert_eq!(screen[320 * 27 + 19], 0x5);
+		assert_eq!(screen[320 * 27 + 18], 0x4);
+	}
+	
+	#[test]
+	fn run_frame() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Vblnk, 0, 0, 0);
+		cpu.add_opcode(Opcode::Addi, 5, 1, 0);
+		cpu.add_opcode(Opcode::Jmp, 0, 0, 0);
+		cpu.pc = 0;
+		let mut video = NullVideo::new();
+		let mut input = NullInput::new();
+		input.controller1 = 0x11;
+		input.controller2 = 0x22;
+		
+		cpu.run_frame(&mut input, &mut video, &mut NullAudio);
+		assert_eq!(video.frames, 1);
+		assert_eq!(cpu.get_rx(5), 0);
+		assert!(cpu.vblank);
+		assert_eq!(cpu.memory.read_word(0xFFF0), 0x11);
+		assert_eq!(cpu.memory.read_word(0xFFF2), 0x22);
+		
+		cpu.run_frame(&mut input, &mut video, &mut NullAudio);
+		assert_eq!(video.frames, 2);
+		assert_eq!(cpu.get_rx(5), 1);
+	}
+	
+	#[test]
+	fn sounds() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Snd1, 0, 0x10, 0x00);
+		cpu.add_opcode(Opcode::Snd3, 0, 0x20, 0x00);
+		cpu.add_opcode(Opcode::Snp, 5, 0x30, 0x00);
+		cpu.add_opcode(Opcode::Sng, 0x12, 0x34, 0x56);
+		cpu.add_opcode(Opcode::Snd0, 0, 0, 0);
+		cpu.add_opcode(Opcode::Jmp, 0, 0x14, 0);
+		cpu.set_rx(5, 0x100);
+		cpu.memory.write_word(0x100, 440);
+		cpu.start_test(5);
+		
+		assert_eq!(cpu.sounds, vec![
+			Sound::Tone { frequency: 500, duration: 0x10 },
+			Sound::Tone { frequency: 1500, duration: 0x20 },
+			Sound::Tone { frequency: 440, duration: 0x30 },
+			Sound::Generator { attack: 1, decay: 2, sustain: 5, release: 6, volume: 3, wave: 4 },
+			Sound::Stop,
+		]);
+		
+		let mut video = NullVideo::new();
+		cpu.run_frame(&mut NullInput::new(), &mut video, &mut NullAudio);
+		assert!(cpu.sounds.is_empty());
+	}
+	
+	#[test]
+	fn terminal_render() -> () {
+		let mut cpu = Cpu::new_test();
+		for pixel in cpu.graphics.screen[..320 * 120].iter_mut() {
+			*pixel = 0xF; //Top half white, bottom half stays on the black background
+		}
+		let frame = terminal::render(&cpu.graphics, 2, 1);
+		let cell = "\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m\u{2580}";
+		assert_eq!(frame, format!("{}{}\x1b[0m\r\n", cell, cell));
+		
+		cpu.graphics.set_bg(2);
+		for pixel in cpu.graphics.screen.iter_mut() {
+			*pixel = 0;
+		}
+		let frame = terminal::render(&cpu.graphics, 1, 1);
+		assert_eq!(frame, "\x1b[38;2;136;136;136m\x1b[48;2;136;136;136m\u{2580}\x1b[0m\r\n");
+		
+		let last = terminal::render_rows(&cpu.graphics, 1, 3);
+		for pixel in cpu.graphics.screen[320 * 120..320 * 121].iter_mut() {
+			*pixel = 0xF; //Only the middle row changes
+		}
+		let rows = terminal::render_rows(&cpu.graphics, 1, 3);
+		assert_eq!(terminal::redraw(&last, &rows), format!("\x1b[2;1H{}", rows[1]));
+		assert_eq!(terminal::redraw(&rows, &rows), "");
+		assert_eq!(terminal::redraw(&[], &rows), format!("\x1b[1;1H{}\x1b[2;1H{}\x1b[3;1H{}", rows[0], rows[1], rows[2]));
+	}
+	
+	#[test]
+	fn terminal_input() -> () {
+		let (keys, used) = terminal::parse_keys(b"\x1b[Az\x1b[");
+		assert_eq!(keys, vec![terminal::TermKey::Up, terminal::TermKey::Char(b'z')]);
+		assert_eq!(used, 4);
+		
+		let (sender, receiver) = channel();
+		let mut input = terminal::TerminalInput::from_receiver(receiver);
+		for byte in b"\x1b[Dh".iter() {
+			sender.send(*byte).unwrap();
+		}
+		assert_eq!(input.controllers(), (4, 64));
+		for _ in 0..7 {
+			assert_eq!(input.controllers(), (4, 64));
+		}
+		assert_eq!(input.controllers(), (0, 0));
+		
+		sender.send(0x1B).unwrap(); //Esc on its own waits a frame for an arrow, then the keys after it count again
+		assert_eq!(input.controllers(), (0, 0));
+		sender.send(b'z').unwrap();
+		assert_eq!(input.controllers(), (64, 0)); //Not held back until two more bytes come
+		sender.send(0x1B).unwrap();
+		input.controllers();
+		for byte in b"[A".iter() { //The rest of an arrow in the next frame
+			sender.send(*byte).unwrap();
+		}
+		assert_eq!(input.controllers().0 & 1, 1);
+		
+		sender.send(b'q').unwrap();
+		input.controllers();
+		assert!(input.quit);
+	}
+	
+	#[test]
+	fn write_rgba() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.graphics.screen[1] = 3;
+		cpu.graphics.set_bg(2);
+		let mut buffer = vec![0u8; 76800 * 4];
+		cpu.graphics.write_rgba(&mut buffer);
+		assert_eq!(&buffer[0..4], &[0x88, 0x88, 0x88, 255]);
+		assert_eq!(&buffer[4..8], &[0xBF, 0x39, 0x32, 255]);
+		assert_eq!(&buffer[76799 * 4..], &[0x88, 0x88, 0x88, 255]);
+	}
+	
+	#[test]
+	fn scale_viewport() -> () {
+		assert_eq!(ScaleMode::Integer.viewport(640.0, 480.0), Viewport { x: 0.0, y: 0.0, scale: 2.0 });
+		assert_eq!(ScaleMode::Integer.viewport(1000.0, 700.0), Viewport { x: 180.0, y: 110.0, scale: 2.0 });
+		assert_eq!(ScaleMode::Fit.viewport(1000.0, 700.0), Viewport { x: 33.0, y: 0.0, scale: 700.0 / 240.0 });
+		assert_eq!(ScaleMode::Fit.viewport(800.0, 900.0), Viewport { x: 0.0, y: 150.0, scale: 2.5 });
+		assert_eq!(ScaleMode::Integer.viewport(160.0, 120.0), Viewport { x: 0.0, y: 0.0, scale: 0.5 });
+		assert_eq!(ScaleMode::Integer.viewport_of(1000.0, 700.0, 480.0, 240.0), Viewport { x: 20.0, y: 110.0, scale: 2.0 });
+	}
+	
+	#[test]
+	fn config_text() -> () {
+		let config = Config::parse("# comment\nscale_mode = fit\nunknown = 3\n");
+		assert_eq!(config.scale_mode, ScaleMode::Fit);
+		assert_eq!(Config::parse(&config.to_text()).scale_mode, ScaleMode::Fit);
+		assert_eq!(Config::parse("scale_mode = sideways").scale_mode, ScaleMode::Integer);
+		
+		let config = Config::parse("scale = 3\nspeed = 1.5\npalette_override = grayscale\n\
+			last_rom_dir = /home/me/roms\np2.a = K\np3.a = L\n");
+		assert_eq!(config.scale, 3);
+		assert_eq!(config.speed, 1.5);
+		assert_eq!(config.palette, None);
+		assert_eq!(config.palette_override, Some("grayscale".to_string()));
+		assert_eq!(config.last_rom_dir, Some(PathBuf::from("/home/me/roms")));
+		assert_eq!(config.keys.players[1][6], "K");
+		assert_eq!(Config::parse(&config.to_text()), config);
+		let (defaults, errors) = Config::read("scale = 0\nspeed = -1\nscale_mode = sideways\nspeed = inf\nscale = 2.5\nvolume = 100\n");
+		assert_eq!((defaults.scale, defaults.speed, defaults.scale_mode), (2, 1.0, ScaleMode::Integer));
+		assert_eq!(errors, vec![
+			"scale = 0 is not a scale, it is a whole number from 1 up".to_string(),
+			"speed = -1 is not a speed, it is a number above 0 like 1.5".to_string(),
+			"scale_mode = sideways is not a scaling mode, it is integer or fit".to_string(),
+			"speed = inf is not a speed, it is a number above 0 like 1.5".to_string(),
+			"scale = 2.5 is not a scale, it is a whole number from 1 up".to_string(),
+		]); //volume is gone, it is just another key this version doesn't know
+		let (_, errors) = Config::read("p1.jump = Z\np2.a =\np1.b = X\n");
+		assert_eq!(errors.len(), 2);
+		assert!(errors[0].contains("p1.jump"));
+		
+		let mut saved = Config::parse("# mine\nscale_mode = fit\nfuture = 1\n");
+		saved.scale_mode = ScaleMode::Integer;
+		saved.last_rom_dir = Some(PathBuf::from("/roms"));
+		assert_eq!(saved.update_text("# mine\nscale_mode = fit\nfuture = 1\n", &["scale_mode"]),
+			"# mine\nscale_mode = integer\nfuture = 1\n"); //No bindings added either
+		assert_eq!(saved.update_text("# mine\nfuture = 1", &["last_rom_dir"]), "# mine\nfuture = 1\nlast_rom_dir = /roms\n");
+		
+		let mut options = cli::parse(&args("game.c16 --speed 4")).unwrap();
+		options.apply_config(&config);
+		assert_eq!((options.scale, options.speed), (Some(3), Some(4.0))); //The command line wins
+		assert_eq!(options.palette_override, Some("grayscale".to_string()));
+		let mut options = cli::parse(&args("screenshot game.c16 shot.png --palette-override default")).unwrap();
+		options.apply_config(&config);
+		assert_eq!((options.scale, options.palette_override), (None, Some("default".to_string())));
+	}
+	
+	#[test]
+	fn filter_nearest() -> () {
+		let src = image_from_rows(&["RW", "WK"]);
+		assert_eq!(filters::nearest(&src, 2), image_from_rows(&["RRWW", "RRWW", "WWKK", "WWKK"]));
+		assert_eq!(Filter::Nearest(3).apply(&src).dimensions(), (6, 6));
+		assert_eq!(Filter::None.apply(&src), src);
+		let mut out = RgbaImage::new(1, 1);
+		Filter::Scale2x.apply_into(&src, &mut out);
+		assert_eq!(out, filters::scale2x(&src));
+		let buffer = out.as_ptr();
+		Filter::Scanlines.apply_into(&src, &mut out); //Same size, same buffer
+		assert_eq!((out.as_ptr(), out.clone()), (buffer, filters::scanlines(&src)));
+	}
+	
+	#[test]
+	fn filter_bilinear() -> () {
+		let src = image_from_rows(&["KW"]);
+		let out = filters::bilinear(&src, 2);
+		let reds: Vec<u8> = out.pixels().map(|p| p[0]).collect();
+		assert_eq!(reds, vec![0, 64, 191, 255, 0, 64, 191, 255]);
+	}
+	
+	#[test]
+	fn filter_scanlines() -> () {
+		let src = image_from_rows(&["W"]);
+		let out = filters::scanlines(&src);
+		assert_eq!(*out.get_pixel(1, 0), Rgba([255, 255, 255, 255]));
+		assert_eq!(*out.get_pixel(1, 1), Rgba([127, 127, 127, 255]));
+	}
+	
+	#[test]
+	fn filter_crt() -> () {
+		let src = RgbaImage::from_pixel(320, 240, Rgba([255, 255, 255, 255]));
+		let out = filters::crt(&src);
+		assert_eq!(out.dimensions(), (960, 720));
+		assert_eq!(*out.get_pixel(0, 0), Rgba([0, 0, 0, 255])); //Curved away
+		assert_eq!(*out.get_pixel(480, 360), Rgba([255, 191, 191, 255])); //Red column of the grille
+		assert_eq!(*out.get_pixel(481, 360), Rgba([191, 255, 191, 255]));
+		assert_eq!(*out.get_pixel(480, 362), Rgba([170, 127, 127, 255])); //Scanline
+	}
+	
+	#[test]
+	fn filter_scale2x() -> () {
+		let src = image_from_rows(&["RWWW", "WRWW", "WWRW", "WWWR"]);
+		assert_eq!(filters::scale2x(&src), image_from_rows(&[
+			"RRWWWWWW",
+			"RWRWWWWW",
+			"WRRRWWWW",
+			"WWRRRWWW",
+			"WWWRRRWW",
+			"WWWWRRRW",
+			"WWWWWRWR",
+			"WWWWWWRR",
+		]));
+		let flat = image_from_rows(&["WW", "WW"]);
+		assert_eq!(filters::scale2x(&flat), filters::nearest(&flat, 2));
+	}
+	
+	#[test]
+	fn filter_scale3x() -> () {
+		let src = image_from_rows(&["RWWW", "WRWW", "WWRW", "WWWR"]);
+		assert_eq!(filters::scale3x(&src), image_from_rows(&[
+			"RRRWWWWWWWWW",
+			"RRWRWWWWWWWW",
+			"RWWRWWWWWWWW",
+			"WRRRRRWWWWWW",
+			"WWWRRRWWWWWW",
+			"WWWRRRRWWWWW",
+			"WWWWWRRRRWWW",
+			"WWWWWWRRRWWW",
+			"WWWWWWRRRRRW",
+			"WWWWWWWWRWWR",
+			"WWWWWWWWRWRR",
+			"WWWWWWWWWRRR",
+		]));
+	}
+	
+	#[test]
+	fn filter_hq2x() -> () {
+		let flat = image_from_rows(&["WW", "WW"]);
+		assert_eq!(filters::hq2x(&flat), filters::nearest(&flat, 2));
+		let dot = filters::hq2x(&image_from_rows(&["KKK", "KWK", "KKK"]));
+		for &(x, y) in [(2, 2), (3, 2), (2, 3), (3, 3)].iter() {
+			assert_eq!(*dot.get_pixel(x, y), Rgba([223, 223, 223, 255])); //14/16 of itself, rounded like a dot
+		}
+		assert_eq!(*dot.get_pixel(0, 0), Rgba([0, 0, 0, 255]));
+		
+		//Every quarter follows the same rules turned around, so turning the picture turns the output
+		let src = image_from_rows(&["RWWK", "WRKW", "KWRW", "WKWR", "RRKW"]);
+		let out = filters::hq2x(&src);
+		assert_eq!(out.dimensions(), (8, 10));
+		assert_eq!(filters::hq2x(&imageops::rotate90(&src)), imageops::rotate90(&out));
+		assert_eq!(filters::hq2x(&imageops::flip_horizontal(&src)), imageops::flip_horizontal(&out));
+		assert_eq!(Filter::from_name("hq2x"), Some(Filter::Hq2x));
+		assert_eq!(Filter::Scale3x.next(), Filter::Hq2x);
+	}
+	
+	#[test]
+	fn record_gif() -> () {
+		let path = env::temp_dir().join(format!("rustchip16-test-{}.gif", std::process::id()));
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Jmp, 0, 0, 0);
+		cpu.pc = 0;
+		let mut recorder = Recorder::new(&path, RecordFormat::Gif).unwrap();
+		headless::run_with_video(&mut cpu, 2, &mut recorder);
+		cpu.graphics.screen[0] = 0xF;
+		headless::run_with_video(&mut cpu, 1, &mut recorder);
+		assert_eq!(recorder.finish(), Ok(3));
+		
+		let decoder = GifDecoder::new(BufReader::new(File::open(&path).unwrap())).unwrap();
+		let frames = decoder.into_frames().collect_frames().unwrap();
+		fs::remove_file(&path).unwrap();
+		assert_eq!(frames.len(), 3);
+		assert_eq!(*frames[0].buffer().get_pixel(0, 0), Rgba([0, 0, 0, 255]));
+		assert_eq!(*frames[2].buffer().get_pixel(0, 0), Rgba([255, 255, 255, 255]));
+		let delays: Vec<(u32, u32)> = frames.iter().map(|frame| frame.delay().numer_denom_ms()).collect();
+		assert_eq!(delays, vec![(20, 1), (10, 1), (20, 1)]);
+		assert_eq!((0..60).map(recording::gif_delay).sum::<u32>(), 100); //60 frames a second
+		
+		let path = env::temp_dir().join(format!("rustchip16-test-{}-headless.gif", std::process::id()));
+		let (rom, _) = c16_file("record.c16", &[0x10, 0x00, 0x00, 0x00], 0); //jmp #0000
+		let options = cli::parse(&[String::from("headless"), rom.display().to_string(), String::from("--frames"), String::from("4"),
+			String::from("--record"), path.display().to_string()]).unwrap();
+		cli::execute(&options).unwrap();
+		let decoder = GifDecoder::new(BufReader::new(File::open(&path).unwrap())).unwrap();
+		assert_eq!(decoder.into_frames().count(), 4);
+		fs::remove_file(&path).unwrap();
+		fs::remove_file(&rom).unwrap();
+		assert_eq!(recording::format_for(Path::new("frames")), RecordFormat::PngSequence);
+		assert_eq!(cli::parse(&args("run game.c16 --record out.gif")), Err("--record doesn't work with run".to_string()));
+	}
+	
+	#[test]
+	fn record_png_sequence() -> () {
+		let dir = env::temp_dir().join(format!("rustchip16-test-{}", std::process::id()));
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Jmp, 0, 0, 0);
+		cpu.pc = 0;
+		let mut recorder = Recorder::new(&dir, RecordFormat::PngSequence).unwrap();
+		headless::run_with_video(&mut cpu, 2, &mut recorder);
+		assert_eq!(recorder.finish(), Ok(2));
+		
+		let first = ::image::open(dir.join("frame_000000.png")).unwrap().to_rgba8();
+		assert_eq!(first, cpu.graphics.to_image());
+		assert!(dir.join("frame_000001.png").exists());
+		fs::remove_dir_all(&dir).unwrap();
+	}
+	
+	#[test]
+	fn palette_files() -> () {
+		let dump = palette::to_hex_list(&palette::DEFAULT_PALETTE);
+		assert!(dump.starts_with("#000000 ;0\n#000000 ;1\n#888888 ;2\n"));
+		assert_eq!(palette::parse(dump.as_bytes()), Ok(palette::DEFAULT_PALETTE));
+		
+		let list = "0x000000, 0x111111, 222222 #333333\n444444 555555 666666 777777 888888 999999 AAAAAA BBBBBB CCCCCC DDDDDD EEEEEE FFFFFF";
+		let parsed = palette::parse(list.as_bytes()).unwrap();
+		assert_eq!(parsed[1], 0x111111);
+		assert_eq!(parsed[15], 0xFFFFFF);
+		assert!(palette::parse(b"000000 111111").is_err());
+		assert!(palette::parse(b"00000G").is_err());
+		
+		let mut jasc = String::from("JASC-PAL\r\n0100\r\n16\r\n");
+		for i in 0..16 {
+			jasc.push_str(&format!("{} 0 255\r\n", i * 16));
+		}
+		let parsed = palette::parse(jasc.as_bytes()).unwrap();
+		assert_eq!(parsed[1], 0x1000FF);
+		
+		let raw: Vec<u8> = (0..48).map(|i| (i * 5) as u8).collect();
+		let parsed = palette::parse(&raw).unwrap();
+		assert_eq!(parsed[0], 0x00050A);
+		assert_eq!(parsed[15], 0xE1E6EB);
+	}
+	
+	#[test]
+	fn palette_override() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.graphics.palette_override = palette::preset("high-contrast");
+		cpu.add_opcode(Opcode::Pal, 0, 0x00, 0x0D);
+		cpu.memory.write_byte(0xD00 + 45, 0x11);
+		cpu.start_test(1);
+		
+		assert_eq!(cpu.graphics.palette[15], 0x110000u32);
+		assert_eq!(cpu.graphics.effective_palette()[15], 0xFFFFFFu32);
+		assert_eq!(cpu.graphics.palette_rgba()[3], [255, 0, 0, 255]);
+		assert_eq!(palette::preset("grayscale").unwrap()[3], 0x606060);
+		assert!(palette::preset("sepia").is_none());
+	}
+	
+	#[test]
+	fn sprite_sheet() -> () {
+		assert_eq!(SpriteSheet::parse("0x1234:8x16:20"), Ok(SpriteSheet { address: 0x1234, width: 8, height: 16, count: 20 }));
+		assert_eq!(SpriteSheet::parse("FF00:2x2"), Ok(SpriteSheet { address: 0xFF00, width: 2, height: 2, count: 1 }));
+		assert!(SpriteSheet::parse("0x1234:8").is_err());
+		assert!(SpriteSheet::parse("0x1234:0x4").is_err());
+		assert_eq!(SpriteSheet::parse("0:255x255:2"), Err("0:255x255:2 is more than the 64K of memory".to_string()));
+		assert!(SpriteSheet::parse("0:1x1:65536").is_ok());
+		assert!(SpriteSheet::parse("0:1x1:4294967295").is_err());
+		
+		let mut cpu = Cpu::new_test();
+		cpu.memory.write_byte(0x100, 0xF3); //First sprite, 1 byte by 2 rows
+		cpu.memory.write_byte(0x101, 0x20);
+		cpu.memory.write_byte(0x102, 0x0F); //Second sprite
+		let sheet = SpriteSheet::parse("100:1x2:3").unwrap();
+		assert_eq!(sheet.sprite_address(2), 0x104);
+		
+		let image = sheet.render(&cpu.memory, &palette::DEFAULT_PALETTE, 2);
+		assert_eq!(image.dimensions(), (6, 6));
+		assert_eq!(*image.get_pixel(0, 0), Rgba([255, 255, 255, 255]));
+		assert_eq!(*image.get_pixel(1, 0), Rgba([0xBF, 0x39, 0x32, 255]));
+		assert_eq!(*image.get_pixel(0, 1), Rgba([0x88, 0x88, 0x88, 255]));
+		assert_eq!(*image.get_pixel(1, 1), Rgba([0x20, 0x20, 0x20, 255])); //Transparent
+		assert_eq!(*image.get_pixel(2, 0), Rgba([0x40, 0x00, 0x40, 255])); //Gap
+		assert_eq!(*image.get_pixel(3, 0), Rgba([0x30, 0x30, 0x30, 255]));
+		assert_eq!(*image.get_pixel(4, 0), Rgba([255, 255, 255, 255]));
+		
+		let mut viewer = SpriteViewer::new();
+		assert_eq!(viewer.size(&cpu), (8, 8)); //Before SPR
+		cpu.graphics.state.spritew = 1;
+		cpu.graphics.state.spriteh = 2;
+		viewer.address = 0x100;
+		let pane = viewer.render(&cpu, 2);
+		assert_eq!(pane.dimensions(), (viewer::WIDTH * 2, 480));
+		assert_eq!(*pane.get_pixel(4, 20), Rgba([255, 255, 255, 255])); //Under the title, like the sheet
+		assert_eq!(*pane.get_pixel(4 + 4 * 2, 20), Rgba([255, 255, 255, 255])); //The right pixel of the next sprite, #0102
+		viewer.resize(&cpu, -5, 1);
+		assert_eq!(viewer.size(&cpu), (1, 3));
+		viewer.resize(&cpu, 300, 0);
+		assert_eq!(viewer.size(&cpu), (255, 3));
+		viewer.render(&cpu, 1); //Wider than the pane, it shows what fits
+	}
+	
+	#[test]
+	fn draw_log() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.graphics.draw_log = Some(Vec::new());
+		cpu.add_opcode(Opcode::Spr, 0, 1, 2);
+		cpu.add_opcode(Opcode::Drw, 0x65, 0x00, 0x01);
+		cpu.add_opcode(Opcode::Flip, 0, 0, 2);
+		cpu.add_opcode(Opcode::Drw, 0x65, 0x00, 0x01);
+		cpu.add_opcode(Opcode::Jmp, 0, 0x10, 0);
+		cpu.pc = 0;
+		cpu.set_rx(5, 10);
+		cpu.set_rx(6, 20);
+		cpu.memory.write_byte(0x100, 0x11);
+		
+		let mut writer = DrawLogWriter::new(Vec::new());
+		headless::run_with_video(&mut cpu, 1, &mut writer);
+		assert_eq!(String::from_utf8(writer.out_ref().clone()).unwrap(),
+			"0 drw x=10 y=20 spr=#0100 1x2 hflip=0 vflip=0 carry=0\n\
+			 0 drw x=10 y=20 spr=#0100 1x2 hflip=1 vflip=0 carry=1\n");
+		assert_eq!(cpu.graphics.draw_log, Some(Vec::new())); //Cleared for the next frame
+		assert_eq!(writer.finish(), Ok(1));
+		
+		let mut full = DrawLogWriter::new(std::io::Cursor::new([0u8; 4]));
+		cpu.pc = 0;
+		headless::run_with_video(&mut cpu, 2, &mut full); //Goes on without the log
+		assert_eq!(full.frame, 2);
+		assert!(full.finish().unwrap_err().starts_with("Couldn't write the draw log"));
+	}
+	
+	#[test]
+	fn draw_overlay() -> () {
+		let mut frame = RgbaImage::new(320, 240);
+		let calls = [
+			DrawCall { x: -2, y: 1, address: 0, width: 2, height: 3, hflip: false, vflip: false, collided: false },
+			DrawCall { x: 10, y: 10, address: 0, width: 1, height: 2, hflip: false, vflip: false, collided: true },
+		];
+		overlay::draw_calls(&mut frame, &calls, 1);
+		let green = Rgba([0, 255, 0, 255]);
+		let red = Rgba([255, 0, 0, 255]);
+		let empty = Rgba([0, 0, 0, 0]);
+		assert_eq!(*frame.get_pixel(0, 1), green);
+		assert_eq!(*frame.get_pixel(1, 1), green); //Right edge is x + 4 - 1
+		assert_eq!(*frame.get_pixel(1, 2), green);
+		assert_eq!(*frame.get_pixel(0, 2), empty);
+		assert_eq!(*frame.get_pixel(0, 3), green);
+		assert_eq!(*frame.get_pixel(10, 10), red);
+		assert_eq!(*frame.get_pixel(11, 11), red);
+		
+		let mut frame = RgbaImage::new(640, 480);
+		overlay::draw_calls(&mut frame, &calls[1..], 2);
+		assert_eq!(*frame.get_pixel(20, 20), red);
+		assert_eq!(*frame.get_pixel(23, 23), red);
+		assert_eq!(*frame.get_pixel(21, 21), empty);
+	}
+	
+	//The sprite decoded to a grid of pixels, mirrored as a whole, then blitted one pixel at a time
+	fn reference_drw(screen: &mut [u8], memory: &Memory, x: i32, y: i32, address: u16, (width, height): (usize, usize), (hflip, vflip): (bool, bool)) -> bool {
+		let mut grid: Vec<Vec<u8>> = (0..height).map(|row| {
+			(0..width * 2).map(|column| {
+				let byte = memory.peek(address.wrapping_add((row * width + column / 2) as u16) as usize) as u8;
+				if column % 2 == 0 { byte >> 4 } else { byte & 0xF }
+			}).collect()
+		}).collect();
+		if hflip {
+			for row in grid.iter_mut() {
+				row.reverse();
+			}
+		}
+		if vflip {
+			grid.reverse();
+		}
+		let mut hit = false;
+		for (row, pixels) in grid.iter().enumerate() {
+			for (column, pixel) in pixels.iter().enumerate() {
+				let (px, py) = (x + column as i32, y + row as i32);
+				if *pixel == 0 || !(0..320).contains(&px) || !(0..240).contains(&py) {
+					continue;
+				}
+				let pos = (py * 320 + px) as usize;
+				hit |= screen[pos] != 0;
+				screen[pos] = *pixel;
+			}
+		}
+		hit
+	}
+	
+	#[test]
+	fn drw_matches_reference() -> () {
+		let mut rng = StdRng::seed_from_u64(16);
+		let mut memory = Memory::new();
+		for dir in 0..0x10000 {
+			memory.write_byte(dir, rng.random::<u8>() as i8);
+		}
+		for dir in 0x100..0x110 { //Some fully transparent bytes
+			memory.write_byte(dir, 0);
+		}
+		let mut background = [0u8; 76800];
+		for pixel in background.iter_mut() {
+			*pixel = if rng.random_range(0..4) == 0 { rng.random_range(1..16) } else { 0 };
+		}
+		
+		let xs = [-32768, -9, -8, -7, -5, -4, -3, -2, -1, 0, 1, 2, 157, 311, 312, 313, 315, 316, 317, 318, 319, 320, 32767];
+		let ys = [-32768, -5, -4, -3, -1, 0, 1, 120, 235, 236, 237, 238, 239, 240, 32767];
+		let addresses = [0x0000, 0x00FF, 0x1234, 0xFFFE];
+		let mut graphics = Chip16Graphics::new();
+		for &width in [1u8, 2, 3, 4].iter() {
+			for &height in [1u8, 2, 3, 5].iter() {
+				for flips in 0..4 {
+					for &x in xs.iter() {
+						for &y in ys.iter() {
+							let address = addresses[rng.random_range(0..addresses.len())];
+							let (hflip, vflip) = (flips & 1 != 0, flips & 2 != 0);
+							graphics.screen = background;
+							graphics.state.spritew = width;
+							graphics.state.spriteh = height;
+							graphics.state.hflip = hflip;
+							graphics.state.vflip = vflip;
+							let mut expected = background;
+							let expected_hit = reference_drw(&mut expected, &memory, x, y, address,
+								(width as usize, height as usize), (hflip, vflip));
+							let hit = graphics.drw(&mut memory, x as i16, y as i16, address as i16);
+							assert!(graphics.screen[..] == expected[..],
+								"{}x{} sprite at {},{} from {:04X}, hflip {} vflip {}", width, height, x, y, address, hflip, vflip);
+							assert_eq!(hit, expected_hit,
+								"{}x{} sprite at {},{} from {:04X}, hflip {} vflip {}", width, height, x, y, address, hflip, vflip);
+						}
+					}
+				}
+			}
+		}
+	}
+	
+	#[test]
+	fn drw_clip_left_edge() -> () {
+		let mut memory = Memory::new();
+		let mut graphics = Chip16Graphics::new();
+		graphics.state.spritew = 1;
+		graphics.state.spriteh = 1;
+		memory.write_byte(0x200, 0x12);
+		graphics.screen[0] = 0x3;
+		assert!(graphics.drw(&mut memory, -1, 0, 0x200)); //The odd pixel lands on x=0
+		assert_eq!(graphics.screen[0], 0x2);
+		assert_eq!(graphics.screen[319], 0); //Nothing wraps to the end of the row
+		assert_eq!(graphics.screen[1], 0);
+		
+		graphics.state.hflip = true;
+		graphics.screen = [0; 76800];
+		assert!(!graphics.drw(&mut memory, 319, 0, 0x200)); //Flipped, the even pixel is the leftmost one
+		assert_eq!(graphics.screen[319], 0x2);
+		assert_eq!(graphics.screen[320], 0);
+	}
+	
+	fn args(line: &str) -> Vec<String> {
+		line.split_whitespace().map(|arg| arg.to_string()).collect()
+	}
+	
+	#[test]
+	fn cli_parse() -> () {
+		let options = cli::parse(&args("game.c16")).unwrap();
+		assert_eq!(options.command, Command::Run);
+		assert_eq!(options.rom, PathBuf::from("game.c16"));
+		assert_eq!(options.scale, None);
+		
+		let options = cli::parse(&args("game.c16 3 --seed 42 --speed 1.5 --paused --watch --trace out.txt")).unwrap();
+		assert!(options.watch);
+		assert_eq!(options.scale, Some(3));
+		assert_eq!(options.seed, Some(42));
+		assert_eq!(options.speed, Some(1.5));
+		assert!(options.paused);
+		assert_eq!(options.trace, Some(PathBuf::from("out.txt")));
+		
+		let options = cli::parse(&args("screenshot game.c16 shot.png --frames 10 --scale 2")).unwrap();
+		assert_eq!(options.command, Command::Screenshot);
+		assert_eq!(options.output, PathBuf::from("shot.png"));
+		assert_eq!(options.frames, Some(10));
+		
+		let options = cli::parse(&args("game.c16 2 --dump-palette")).unwrap(); //The old way of asking
+		assert_eq!(options.command, Command::Headless);
+		assert!(options.dump_palette);
+		assert_eq!(cli::parse(&args("info game.c16")).unwrap().command, Command::Info);
+		assert_eq!(cli::parse(&args("disasm game.c16 --help")).unwrap().command, Command::Help);
+		
+		assert_eq!(cli::parse(&args("info")), Err("No ROM specified".to_string()));
+		let options = cli::parse(&args("--scale 3")).unwrap();
+		assert_eq!(options.command, Command::Run);
+		assert_eq!(options.rom, PathBuf::new());
+		assert_eq!(cli::parse(&args("game.c16 two")), Err("two is not a number for the scale".to_string()));
+		assert_eq!(cli::parse(&args("game.c16 --scale 0")), Err("--scale has to be at least 1".to_string()));
+		assert_eq!(cli::parse(&args("info game.c16 --speed 2")), Err("--speed doesn't work with info".to_string()));
+		assert_eq!(cli::parse(&args("run game.c16 --draw-log log.txt")), Err("--draw-log doesn't work with run".to_string()));
+		assert_eq!(cli::parse(&args("game.c16 --fast")), Err("--fast is not an option".to_string()));
+		assert_eq!(cli::parse(&args("game.c16 --seed")), Err("--seed needs a value".to_string()));
+		assert_eq!(cli::parse(&args("screenshot game.c16")), Err("screenshot needs a file to save the frame to".to_string()));
+		assert_eq!(cli::parse(&args("info game.c16 extra")), Err("extra is one argument too many".to_string()));
+	}
+	
+	#[test]
+	fn disassemble() -> () {
+		let none = Symbols::new();
+		assert_eq!(disasm::instruction([0x00, 0, 0, 0], &none), Some("nop".to_string()));
+		assert_eq!(disasm::instruction([0x03, 0, 0x0A, 0], &none), Some("bgc 10".to_string()));
+		assert_eq!(disasm::instruction([0x04, 0, 0x08, 0x10], &none), Some("spr #1008".to_string()));
+		assert_eq!(disasm::instruction([0x05, 0x21, 0x00, 0x30], &none), Some("drw r1, r2, #3000".to_string()));
+		assert_eq!(disasm::instruction([0x06, 0x21, 0x03, 0], &none), Some("drw r1, r2, r3".to_string()));
+		assert_eq!(disasm::instruction([0x08, 0, 0, 3], &none), Some("flip 1, 1".to_string()));
+		assert_eq!(disasm::instruction([0x0B, 0, 0x64, 0], &none), Some("snd2 #0064".to_string()));
+		assert_eq!(disasm::instruction([0x12, 0x01, 0x34, 0x12], &none), Some("jnz #1234".to_string()));
+		assert_eq!(disasm::instruction([0x17, 0x0E, 0x00, 0x02], &none), Some("cle #0200".to_string()));
+		assert_eq!(disasm::instruction([0x18, 0x0F, 0, 0], &none), Some("call rF".to_string()));
+		assert_eq!(disasm::instruction([0x21, 0, 0xF0, 0xFD], &none), Some("ldi sp, #FDF0".to_string()));
+		assert_eq!(disasm::instruction([0x24, 0x10, 0, 0], &none), Some("mov r0, r1".to_string()));
+		assert_eq!(disasm::instruction([0x42, 0x10, 0x02, 0], &none), Some("add r0, r1, r2".to_string()));
+		assert_eq!(disasm::instruction([0x53, 0x05, 0xFF, 0xFF], &none), Some("cmpi r5, #FFFF".to_string()));
+		assert_eq!(disasm::instruction([0xB1, 0x03, 0x04, 0], &none), Some("shr r3, 4".to_string()));
+		assert_eq!(disasm::instruction([0xB4, 0x43, 0, 0], &none), Some("shr r3, r4".to_string()));
+		assert_eq!(disasm::instruction([0xE5, 0x21, 0, 0], &none), Some("neg r1, r2".to_string()));
+		assert_eq!(disasm::instruction([0x0F, 0, 0, 0], &none), None);
+		
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Ldi, 0x01, 0x10, 0x00);
+		cpu.memory.write_byte(4, 0xFF);
+		assert_eq!(disasm::listing(&cpu.memory, 0, 8, &none),
+			"0000  20 01 10 00  ldi r1, #0010\n\
+			 0004  FF 00 00 00  db #FF, #00, #00, #00\n");
+	}
+	
+	fn c16_file(name: &str, program: &[u8], start: u16) -> (PathBuf, Vec<u8>) {
+		let mut bytes = vec![b'C', b'H', b'1', b'6', 0, 0x11];
+		let size = program.len() as u32;
+		let crc = loading::crc32(program);
+		bytes.extend_from_slice(&[size as u8, (size >> 8) as u8, (size >> 16) as u8, (size >> 24) as u8]);
+		bytes.extend_from_slice(&[start as u8, (start >> 8) as u8]);
+		bytes.extend_from_slice(&[crc as u8, (crc >> 8) as u8, (crc >> 16) as u8, (crc >> 24) as u8]);
+		bytes.extend_from_slice(program);
+		let path = env::temp_dir().join(format!("rustchip16-test-{}-{}", std::process::id(), name));
+		File::create(&path).unwrap().write_all(&bytes).unwrap();
+		(path, bytes)
+	}
+	
+	#[test]
+	fn load_c16() -> () {
+		assert_eq!(loading::crc32(b"123456789"), 0xCBF43926);
+		let program = [0x20, 0x01, 0x10, 0x00, 0x10, 0x00, 0x04, 0x00];
+		let (path, bytes) = c16_file("load.c16", &program, 0x0004);
+		let cpu = Cpu::load(&path).unwrap();
+		assert_eq!(cpu.pc, 0x0004);
+		assert_eq!(cpu.memory.peek(0), 0x20);
+		assert_eq!(loading::read_header(&bytes).unwrap().version, (1, 1));
+		assert_eq!(cli::info(&path).unwrap(), format!("File: {}\nFormat: c16, spec 1.1\nSize: 8 bytes\nStart: #0004\nCRC32: #{:08X}\n",
+			path.display(), loading::crc32(&program)));
+		
+		let mut corrupted = bytes.clone();
+		corrupted[16] = 0x21;
+		File::create(&path).unwrap().write_all(&corrupted).unwrap();
+		assert_eq!(Cpu::load(&path).err(), Some("The ROM is corrupted.".to_string()));
+		assert!(cli::info(&path).unwrap().contains(", the header says #"));
+		File::create(&path).unwrap().write_all(&bytes[..8]).unwrap();
+		assert!(Cpu::load(&path).is_err());
+		fs::remove_file(&path).unwrap();
+		
+		assert_eq!(Cpu::load(Path::new("game.rom")).err(), Some("game.rom is not a .bin or .c16 file".to_string()));
+		assert!(Cpu::load(Path::new("/nonexistent/game.c16")).is_err());
+	}
+	
+	#[test]
+	fn c16_start_address_is_little_endian() -> () { //Spec 1.1 header: 0x0A is the start address, little endian like every field, so #0240 is 40 02
+		let mut program = vec![0; 0x244];
+		program[0x240] = 0x10; //jmp #0000
+		let (path, bytes) = c16_file("start.c16", &program, 0x0240);
+		assert_eq!(&bytes[0xA..0xC], &[0x40, 0x02]);
+		assert_eq!(loading::read_header(&bytes).unwrap().start_address, 0x0240);
+		assert_eq!(Cpu::load(&path).unwrap().pc, 0x0240);
+		fs::remove_file(&path).unwrap();
+	}
+	
+	#[test]
+	fn seeded_rnd() -> () {
+		let mut values = Vec::new();
+		for _ in 0..2 {
+			let mut cpu = Cpu::new_test();
+			cpu.seed(7);
+			for _ in 0..8 {
+				cpu.add_opcode(Opcode::Rnd, 0, 0xFF, 0x0F);
+			}
+			cpu.add_opcode(Opcode::Rnd, 1, 0, 0); //Only 0 can come up, not a panic
+			cpu.start_test(9);
+			values.push((cpu.get_rx(0), cpu.get_rx(1)));
+		}
+		assert_eq!(values[0], values[1]);
+		assert!(values[0].0 <= 0x0FFF);
+		assert_eq!(values[0].1, 0);
+	}
+	
+	#[test]
+	fn rnd_max_is_inclusive() -> () { //Spec 1.1: RND RX, HHLL stores a random number from 0 to HHLL, HHLL included
+		let mut cpu = Cpu::new_test();
+		cpu.seed(1);
+		cpu.add_opcode(Opcode::Rnd, 0, 0x01, 0x00);
+		let mut seen = [false; 2];
+		for _ in 0..64 {
+			cpu.start_test(1);
+			seen[cpu.get_rx(0) as usize] = true;
+		}
+		assert_eq!(seen, [true, true]);
+	}
+	
+	#[test]
+	fn trace() -> () {
+		let path = env::temp_dir().join(format!("rustchip16-test-{}-trace.txt", std::process::id()));
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Ldi, 0x01, 0x10, 0x00);
+		cpu.add_opcode(Opcode::Jmp, 0, 0x04, 0x00);
+		cpu.trace = Some(Box::new(File::create(&path).unwrap()));
+		cpu.start_test(3);
+		cpu.trace = None;
+		assert_eq!(fs::read_to_string(&path).unwrap(),
+			"0000  20 01 10 00  ldi r1, #0010\n\
+			 0004  10 00 04 00  jmp #0004\n\
+			 0004  10 00 04 00  jmp #0004\n");
+		fs::remove_file(&path).unwrap();
+		
+		cpu.trace = Some(Box::new(std::io::Cursor::new([0u8; 4]))); //Full after 4 bytes
+		cpu.start_test(3);
+		assert!(cpu.trace.is_none());
+		assert_eq!(cpu.pc, 0x0004); //The program went on without it
+		assert!(cli::finish_trace(&mut cpu).unwrap_err().starts_with("Couldn't write the trace"));
+		assert_eq!(cli::finish_trace(&mut cpu), Ok(()));
+	}
+	
+	#[test]
+	fn key_bindings() -> () {
+		let bindings = KeyBindings::parse("# Player one on the left\np1.a = Z\n p2.start=Return \n").unwrap();
+		assert_eq!(bindings.players[0][6], "Z");
+		assert_eq!(bindings.players[1][5], "Return");
+		assert_eq!(bindings.players[0][0], "Up"); //Defaults for the rest
+		assert_eq!(KeyBindings::parse(&bindings.to_text()), Ok(bindings));
+		assert_eq!(KeyBindings::parse("p3.a = Z"), Err("p3.a is not a button, they go from p1.up to p2.b".to_string()));
+		assert_eq!(KeyBindings::parse("p1.c = Z"), Err("p1.c is not a button, they go from p1.up to p2.b".to_string()));
+		assert_eq!(KeyBindings::parse("p1.a"), Err("p1.a has no key, write it as p1.up = Up".to_string()));
+	}
+	
+	#[test]
+	fn game_settings() -> () {
+		let database = GameDatabase::parse("p1.a = Q\n[5a499cc4] # Pong\nspeed = 2\np2.up = I\np2.down = K\n\
+			[NOTHEX]\nscale = 9\n[00000001]\nscale = 4\npalette_override = grayscale\nscale = 0\n");
+		assert_eq!(database.games.len(), 2);
+		let pong = database.get(0x5A499CC4).unwrap();
+		assert_eq!(pong.speed, Some(2.0));
+		assert_eq!(pong.scale, None);
+		assert_eq!(pong.keys, vec![(1, 0, "I".to_string()), (1, 1, "K".to_string())]);
+		assert_eq!(database.get(1).unwrap().scale, Some(4));
+		assert_eq!(database.get(2), None);
+		assert_eq!(GameDatabase::read(&database.to_text()), (database.clone(), Vec::new()));
+		let (_, errors) = GameDatabase::read("p1.a = Q\n[5a499cc4]\nspeed = fast\np1.jump = Z\nscale_mode = fit\nframes\nfuture = 1\n\
+			[NOTHEX]\nscale = 0\n");
+		assert_eq!(errors, vec![
+			"p1.a = Q comes before any [CRC32] line".to_string(),
+			"speed = fast is not a speed, it is a number above 0 like 1.5".to_string(),
+			"p1.jump is not a button, they go from p1.up to p2.b".to_string(),
+			"scale_mode = fit is the same for every program, it goes in the config file".to_string(),
+			"frames is not key = value".to_string(),
+			"[NOTHEX] is not a [CRC32] line, the settings under it are skipped".to_string(),
+		]);
+		
+		let mut config = Config::parse("speed = 1.5\nscale = 3\n");
+		pong.apply(&mut config);
+		assert_eq!((config.speed, config.scale), (2.0, 3));
+		assert_eq!(config.keys.players[1][0], "I");
+		assert_eq!(config.keys.players[0][6], "NumPad7");
+		
+		let program = [0x10, 0x00, 0x00, 0x00];
+		let (path, _) = c16_file("games.c16", &program, 0);
+		let crc = games::rom_crc(&path).unwrap();
+		assert_eq!(crc, loading::crc32(&program));
+		let database = GameDatabase::parse(&format!("[{:08X}]\nspeed = 3\npalette = high-contrast\n", crc));
+		let mut options = cli::parse(&[path.display().to_string(), "--palette".to_string(), "default".to_string()]).unwrap();
+		let defaults = cli::rom_defaults(&options, &Config::new(), &database);
+		options.apply_config(&defaults);
+		assert_eq!(options.speed, Some(3.0));
+		assert_eq!(options.palette, Some("default".to_string())); //The command line still wins
+		let raw = cli::parse(&args("run")).unwrap(); //Started on the ROM browser, then the game is picked
+		let config = Config::parse("palette = grayscale\np1.a = Q\n");
+		let (before, _) = cli::resolve(&raw, &config, &database);
+		assert_eq!((before.speed, before.palette.as_ref().map(|p| &p[..])), (Some(1.0), Some("grayscale")));
+		let (launched, defaults) = cli::resolve(&cli::Options { rom: path.clone(), ..raw.clone() }, &config, &database);
+		assert_eq!((launched.speed, launched.palette.as_ref().map(|p| &p[..])), (Some(3.0), Some("high-contrast")));
+		assert_eq!(cli::key_bindings(&launched, &defaults).unwrap().players[0][6], "Q");
+		options.rom = PathBuf::from("missing.c16");
+		assert_eq!(cli::rom_defaults(&options, &Config::new(), &database), Config::new());
+		fs::remove_file(&path).unwrap();
+	}
+	
+	#[test]
+	fn draw_text() -> () {
+		let white = Rgba([255, 255, 255, 255]);
+		let mut frame = RgbaImage::new(20, 10);
+		font::draw_text(&mut frame, 1, 1, "I!", white, 1);
+		let lit: Vec<(u32, u32)> = frame.enumerate_pixels().filter(|p| p.2[3] != 0).map(|p| (p.0, p.1)).collect();
+		assert_eq!(lit.iter().filter(|p| p.0 < 7).count(), 11); //I has a column of 7 and serifs at both ends
+		assert!(lit.contains(&(3, 1)) && lit.contains(&(3, 7)) && lit.contains(&(2, 1)) && lit.contains(&(4, 7)));
+		assert_eq!(lit.iter().filter(|p| p.0 >= 7).cloned().collect::<Vec<_>>(), vec![(9, 1), (9, 2), (9, 3), (9, 4), (9, 5), (9, 7)]);
+		assert_eq!(font::text_width("I!", 2), 24);
+		
+		let mut scaled = RgbaImage::new(12, 16);
+		font::draw_text(&mut scaled, 0, 0, "\u{e9}", white, 2); //Anything that isn't ASCII shows as ?
+		let mut question = RgbaImage::new(12, 16);
+		font::draw_text(&mut question, 0, 0, "?", white, 2);
+		assert_eq!(scaled, question);
+		assert_eq!(scaled.pixels().filter(|p| p[3] != 0).count() % 4, 0);
+		font::draw_text(&mut scaled, -100, -100, "clipped", white, 3);
+		font::draw_text(&mut scaled, 100, 100, "clipped", white, 3);
+		assert_eq!(scaled, question);
+	}
+	
+	#[test]
+	fn rom_browser() -> () {
+		let root = env::temp_dir().join(format!("rustchip16-test-{}-browser", std::process::id()));
+		let roms = root.join("ROMs");
+		fs::create_dir_all(roms.join("Games")).unwrap();
+		fs::create_dir_all(root.join("Documents")).unwrap();
+		let (c16, _) = c16_file("browser.c16", &[0x10, 0x00, 0x00, 0x00], 0);
+		fs::rename(&c16, roms.join("Games").join("Zeta.c16")).unwrap();
+		File::create(roms.join("alpha.BIN")).unwrap();
+		File::create(roms.join("beta.bin")).unwrap();
+		File::create(roms.join("notes.txt")).unwrap();
+		File::create(roms.join(".hidden.c16")).unwrap();
+		File::create(root.join("Documents").join("zeta.txt")).unwrap().write_all(b"Zeta\r\n\tby somebody\r\n").unwrap();
+		
+		let mut menu = Browser::open(&roms).unwrap();
+		let names: Vec<&str> = menu.entries.iter().map(|entry| &entry.name[..]).collect();
+		assert_eq!(names, vec!["..", "Games", "beta.bin"]); //Extensions are matched as they are written
+		assert_eq!(menu.selected, 1);
+		menu.move_by(-5);
+		assert_eq!(menu.selected, 0);
+		menu.move_by(10);
+		assert_eq!(menu.selected, 2);
+		assert_eq!(menu.activate(), Some(menu.dir.join("beta.bin")));
+		
+		menu.select(1);
+		assert_eq!(menu.activate(), None);
+		assert!(menu.dir.ends_with("ROMs/Games"));
+		assert_eq!(menu.selected, 1);
+		let zeta = menu.activate().unwrap();
+		assert_eq!(browser::document(&zeta), Some(root.canonicalize().unwrap().join("Documents").join("zeta.txt")));
+		let details = browser::details(&zeta);
+		assert_eq!(details[0], "Format: c16, spec 1.1");
+		assert_eq!(&details[details.len() - 3..], &["".to_string(), "Zeta".to_string(), "    by somebody".to_string()]);
+		assert_eq!(browser::details(&roms.join("beta.bin")).len(), 4); //No document, only the header lines
+		
+		let image = menu.render();
+		assert_eq!(image.dimensions(), (browser::WIDTH, browser::HEIGHT));
+		assert!(image.pixels().any(|p| p[0] == 0x30 && p[2] == 0x7F)); //The selection
+		menu.parent();
+		assert!(menu.dir.ends_with("ROMs"));
+		assert_eq!(menu.entries[menu.selected].name, "Games"); //Back on the directory we came from
+		
+		fs::remove_dir_all(&root).unwrap();
+		assert!(Browser::open(&root).is_err());
+		let mut config = Config::new();
+		config.last_rom_dir = Some(root.clone());
+		assert!(browser::start_dir(&config) != root); //Gone, so it isn't used
+	}
+	
+	#[test]
+	fn soft_reset() -> () {
+		let program = [0x00, 0x00, 0x00, 0x00, 0x20, 0x03, 0x34, 0x12, 0x30, 0x03, 0x00, 0x20, 0x02, 0x00, 0x00, 0x00];
+		let (path, _) = c16_file("reset.c16", &program, 0x0004); //ldi r3, #1234; stm r3, #2000; bgc 0
+		let mut cpu = Cpu::load(&path).unwrap();
+		fs::remove_file(&path).unwrap();
+		assert_eq!(cpu.start, 0x0004);
+		for _ in 0..3 {
+			cpu.step().unwrap();
+		}
+		cpu.graphics.screen[10] = 5;
+		cpu.set_spr_wh(8, 8);
+		cpu.sp = 0xFE00;
+		cpu.soft_reset();
+		assert_eq!((cpu.pc, cpu.sp, cpu.rx[3], cpu.flags), (0x0004, 0xFDF0, 0, 0));
+		assert_eq!(cpu.graphics.screen[10], 0);
+		assert_eq!(cpu.graphics.state.spritew, 0);
+		assert_eq!(cpu.memory.peek(0x2000), 0x34); //Memory is kept
+		assert_eq!(cpu.memory.peek(0x2001), 0x12);
+		cpu.step().unwrap();
+		assert_eq!(cpu.rx[3], 0x1234);
+	}
+	
+	#[test]
+	fn watch_rom() -> () {
+		let path = env::temp_dir().join(format!("rustchip16-test-{}-watch.bin", std::process::id()));
+		File::create(&path).unwrap().write_all(&[0; 4]).unwrap();
+		let mut watcher = Watcher::new(&path);
+		assert!(!watcher.changed());
+		File::create(&path).unwrap().write_all(&[0; 8]).unwrap();
+		assert!(!watcher.changed()); //It could still be writing
+		assert!(watcher.changed());
+		assert!(!watcher.changed());
+		fs::remove_file(&path).unwrap();
+		assert!(!watcher.changed());
+		assert!(!watcher.changed());
+		File::create(&path).unwrap().write_all(&[0; 12]).unwrap();
+		assert!(!watcher.changed());
+		assert!(watcher.changed());
+		fs::remove_file(&path).unwrap();
+	}
+	
+	#[test]
+	fn hud() -> () {
+		assert_eq!(hud::pad_names(0), "-");
+		assert_eq!(hud::pad_names(Pad::Up as u16 | Pad::A as u16 | Pad::Start as u16), "Up+Start+A");
+		
+		let mut hud = Hud::new();
+		for _ in 0..59 {
+			hud.tick(16900, 1, 1);
+		}
+		assert_eq!(hud.fps, 0.0); //Nothing until a second went by
+		hud.tick(16900, 1, 2);
+		assert!((hud.fps - 60.0 / 1.014).abs() < 0.001);
+		assert!((hud.vblank_rate - 61.0 / 1.014).abs() < 0.001);
+		
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Ldi, 0x01, 0x10, 0x00);
+		cpu.add_opcode(Opcode::Vblnk, 0, 0, 0);
+		cpu.add_opcode(Opcode::Jmp, 0, 0, 0);
+		cpu.pc = 0;
+		let (mut input, mut video) = (NullInput::new(), NullVideo::new());
+		cpu.run_frame(&mut input, &mut video, &mut NullAudio);
+		assert_eq!(cpu.busy, 1); //Only the LDI, VBLNK waits the rest of the frame
+		cpu.run_frame(&mut input, &mut video, &mut NullAudio);
+		assert_eq!(cpu.busy, 3); //VBLNK goes through, then JMP and LDI until the next one
+		let lines = hud.lines(&cpu, (Pad::Left as u16, 0));
+		assert_eq!(lines[1], "Instructions 3/16666 per vblank");
+		assert_eq!(lines[3], "P1 0004 Left");
+		assert_eq!(lines[4], "P2 0000 -");
+		
+		let mut frame = RgbaImage::from_pixel(640, 480, Rgba([255, 255, 255, 255]));
+		hud::draw(&mut frame, &lines, 2);
+		assert_eq!(*frame.get_pixel(1, 1), Rgba([95, 95, 95, 255])); //Darkened
+		assert_eq!(*frame.get_pixel(639, 479), Rgba([255, 255, 255, 255]));
+	}
+	
+	#[test]
+	fn call_frames() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Call, 0, 0x10, 0x00);
+		cpu.pc = 0x10;
+		cpu.add_opcode(Opcode::Ldi, 0x02, 0xCD, 0xAB);
+		cpu.add_opcode(Opcode::Call, 0, 0x20, 0x00);
+		cpu.add_opcode(Opcode::Ret, 0, 0, 0);
+		cpu.pc = 0x20;
+		cpu.add_opcode(Opcode::Push, 0x02, 0, 0);
+		cpu.add_opcode(Opcode::Pop, 0x03, 0, 0);
+		cpu.add_opcode(Opcode::Ret, 0, 0, 0);
+		cpu.start_test(4);
+		assert_eq!(cpu.pc, 0x24);
+		assert_eq!(cpu.calls, vec![
+			CallFrame { slot: 0xFDF0, from: 0x0000, target: 0x0010 },
+			CallFrame { slot: 0xFDF2, from: 0x0014, target: 0x0020 },
+		]);
+		assert_eq!(panel::stack_lines(&cpu), vec!["FDF4 #ABCD", "FDF2 #0018 call #0020", "FDF0 #0004 call #0010"]);
+		let lines = panel::lines(&cpu);
+		assert_eq!(lines[0], "PC #0024  SP #FDF6");
+		assert_eq!(lines[4], "R2 #ABCD  RA #0000");
+		assert_eq!(lines[11], "Stack, 2 calls deep");
+		for _ in 0..2 {
+			cpu.step().unwrap();
+		}
+		assert_eq!(cpu.calls.len(), 1);
+		assert_eq!(cpu.pc, 0x18);
+		cpu.step().unwrap();
+		assert_eq!(cpu.calls, vec![]);
+		assert_eq!(panel::stack_lines(&cpu), vec!["empty"]);
+		
+		cpu.pc = 0x0000;
+		cpu.step().unwrap();
+		cpu.sp = 0xFDF0; //Like LDI SP, the call is forgotten without a RET
+		cpu.pc = 0x0000;
+		cpu.step().unwrap();
+		assert_eq!(cpu.calls.len(), 1);
+		cpu.sp = 0x1000;
+		assert_eq!(panel::stack_lines(&cpu), vec!["SP is below #FDF0"]);
+		
+		assert_eq!(panel::flag_names(0), "----");
+		assert_eq!(panel::flag_names(0xC2u8 as i8), "C-ON");
+		let frame = RgbaImage::from_pixel(640, 480, Rgba([255, 0, 0, 255]));
+		let image = panel::beside(&frame, &lines, 2);
+		assert_eq!(image.dimensions(), (640 + 320, 480));
+		assert_eq!(*image.get_pixel(639, 479), Rgba([255, 0, 0, 255]));
+		assert!(image.pixels().skip(640).take(320).all(|p| p[0] != 255));
+	}
+	
+	#[test]
+	fn hex_editor() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Ldi, 0x01, 0x41, 0x42);
+		cpu.add_opcode(Opcode::Stm, 0x01, 0x00, 0x30);
+		cpu.add_opcode(Opcode::Vblnk, 0, 0, 0);
+		cpu.add_opcode(Opcode::Jmp, 0, 0x08, 0x00);
+		cpu.pc = 0;
+		let (mut input, mut video) = (NullInput::new(), NullVideo::new());
+		cpu.memory.write_byte(0x4000, 1);
+		assert!(!cpu.memory.was_written(0x4000)); //Only while the editor shows them
+		cpu.memory.track_writes(true);
+		cpu.run_frame(&mut input, &mut video, &mut NullAudio);
+		assert!(cpu.memory.was_written(0x3000) && cpu.memory.was_written(0x3001));
+		assert!(cpu.memory.was_written(0xFFF0)); //The controllers
+		assert!(!cpu.memory.was_written(0x0000)); //The program was written before this frame
+		cpu.run_frame(&mut input, &mut video, &mut NullAudio);
+		assert!(!cpu.memory.was_written(0x3000));
+		
+		let mut editor = HexEditor::new();
+		editor.follow = Follow::Pc;
+		editor.sync(&cpu);
+		assert_eq!((editor.cursor, editor.top), (0x0008, 0x0000));
+		editor.go_to(0x3001);
+		assert_eq!(editor.top as u32, 0x3000 - (hexedit::ROWS - 1) * 8);
+		editor.move_by(-1);
+		assert_eq!(editor.cursor, 0x3000);
+		editor.follow = Follow::Sp;
+		assert_eq!(editor.title(true), "#3000, follows SP, editing");
+		assert_eq!(editor.type_digit(&mut cpu, 0xC), None);
+		assert_eq!(editor.follow, Follow::Nothing);
+		let image = editor.render(&cpu, true, 1);
+		assert_eq!(image.dimensions(), (hexedit::WIDTH, 240));
+		assert_eq!(editor.type_digit(&mut cpu, 0x3), Some(0x3000));
+		assert_eq!(cpu.memory.peek(0x3000), 0xC3);
+		assert_eq!(editor.cursor, 0x3001);
+		assert!(cpu.memory.was_written(0x3000));
+		editor.go_to(0xFFFF);
+		editor.type_digit(&mut cpu, 1);
+		editor.type_digit(&mut cpu, 2);
+		assert_eq!((editor.cursor, editor.top), (0xFFFF, (0x10000 - hexedit::ROWS * 8) as u16));
+		editor.move_by(-0x20000);
+		assert_eq!((editor.cursor, editor.top), (0x0000, 0x0000));
+		
+		editor.go_to(0x3000);
+		let image = editor.render(&cpu, false, 2);
+		assert_eq!(image.dimensions(), (hexedit::WIDTH * 2, 480));
+		assert!(image.pixels().any(|p| *p == Rgba([0x30, 0x40, 0x7F, 255]))); //The cursor
+		assert!(image.pixels().any(|p| *p == Rgba([0xFF, 0xD0, 0x40, 255]))); //The byte typed at #3000
+		let joined = panel::join(&RgbaImage::new(10, 5), &RgbaImage::new(4, 8));
+		assert_eq!(joined.dimensions(), (14, 8));
+	}
+	
+	#[test]
+	fn cheats() -> () {
+		let list = cheats::parse("# Ninja\n3000 = 05 # lives\nfff0 = 0080\n\n").unwrap();
+		assert_eq!(list, vec![
+			Cheat { address: 0x3000, value: 0x05, word: false, name: "lives".to_string() },
+			Cheat { address: 0xFFF0, value: 0x0080, word: true, name: String::new() },
+		]);
+		assert_eq!(cheats::to_text(&list), "3000 = 05 # lives\nFFF0 = 0080\n");
+		assert_eq!(cheats::parse(&cheats::to_text(&list)), Ok(list.clone()));
+		assert_eq!(cheats::parse("3000"), Err("3000 has no value, write it as 3000 = 05".to_string()));
+		assert_eq!(cheats::parse("3000 = 12345"), Err("12345 is not a byte or a word".to_string()));
+		assert_eq!(cheats::parse("zz = 1"), Err("zz is not an address".to_string()));
+		
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Ldi, 0x00, 0x09, 0x00);
+		cpu.add_opcode(Opcode::Stm, 0x00, 0x00, 0x30); //9 lives
+		cpu.add_opcode(Opcode::Vblnk, 0, 0, 0);
+		cpu.add_opcode(Opcode::Ldm, 0x00, 0x00, 0x30);
+		cpu.add_opcode(Opcode::Subi, 0x00, 0x01, 0x00); //One less every frame
+		cpu.add_opcode(Opcode::Stm, 0x00, 0x00, 0x30);
+		cpu.add_opcode(Opcode::Jmp, 0x00, 0x08, 0x00);
+		cpu.pc = 0;
+		let (mut input, mut video) = (NullInput::new(), NullVideo::new());
+		let rom = Path::new("unused.c16");
+		let mut console = Console::new();
+		let mut run = |cpu: &mut Cpu, frames: u32| for _ in 0..frames {
+			cpu.run_frame(&mut input, &mut video, &mut NullAudio);
+		};
+		run(&mut cpu, 2);
+		assert_eq!(console.execute(&mut cpu, rom, "search changed"), Err("There is nothing to compare with yet, start with search new".to_string()));
+		assert_eq!(console.execute(&mut cpu, rom, "search new"), Ok("Searching all 65536 bytes\n".to_string()));
+		run(&mut cpu, 1);
+		let found = console.execute(&mut cpu, rom, "search down").unwrap();
+		assert!(found.contains("3000 = 07\n"));
+		run(&mut cpu, 1);
+		assert_eq!(console.execute(&mut cpu, rom, "search = 6"), Ok("1 addresses\n3000 = 06\n".to_string()));
+		assert_eq!(console.execute(&mut cpu, rom, "cheat #3000 9 infinite lives"), Ok("3000 = 09 # infinite lives\n".to_string()));
+		assert_eq!(cpu.memory.peek(0x3000), 9); //Right away
+		run(&mut cpu, 1);
+		assert_eq!(cpu.memory.peek(0x3000), 9); //And again at vblank
+		assert_eq!(console.execute(&mut cpu, rom, "cheatw 0x4000 12C"), Ok("4000 = 012C\n".to_string()));
+		assert_eq!(console.execute(&mut cpu, rom, "cheat 5000 10"), Ok("5000 = 10\n".to_string())); //Hex like the file
+		assert_eq!(console.execute(&mut cpu, rom, "uncheat 5000"), Ok(String::new()));
+		assert_eq!(console.execute(&mut cpu, rom, "cheat #3000 100"), Err("#3000 100 doesn't fit".to_string()));
+		assert_eq!(console.execute(&mut cpu, rom, "cheats"), Ok("3000 = 09 # infinite lives\n4000 = 012C\n".to_string()));
+		assert_eq!(console.execute(&mut cpu, rom, "uncheat #3000"), Ok(String::new()));
+		assert_eq!(console.execute(&mut cpu, rom, "uncheat #3000"), Err("There is no cheat at #3000".to_string()));
+		run(&mut cpu, 1);
+		assert_eq!(cpu.memory.peek(0x3000), 8);
+		assert_eq!(console.execute(&mut cpu, rom, "fly"), Err("fly is not a command, help lists them".to_string()));
+		
+		let path = env::temp_dir().join(format!("rustchip16-test-{}-cheats.txt", std::process::id()));
+		File::create(&path).unwrap().write_all(b"3000 = 05\n").unwrap();
+		let (c16, _) = c16_file("cheats.c16", &[0x10, 0x00, 0x00, 0x00], 0);
+		let options = cli::parse(&[c16.display().to_string(), "--cheats".to_string(), path.display().to_string()]).unwrap();
+		assert_eq!(cli::load_cpu(&options).unwrap().cheats.len(), 1);
+		assert_eq!(cli::parse(&args("info x.c16 --cheats c.txt")), Err("--cheats doesn't work with info".to_string()));
+		fs::remove_file(&path).unwrap();
+		fs::remove_file(&c16).unwrap();
+	}
+	
+	#[test]
+	fn symbols() -> () {
+		let labels = Symbols::parse("; Made by hand\n0004 game_loop\ndraw = #0040 ; sprites\n0x0000 :start\n").unwrap();
+		assert_eq!(labels.name(4), Some("game_loop"));
+		assert_eq!(labels.name(0), Some("start"));
+		assert_eq!(labels.address("draw"), Some(0x40));
+		assert_eq!(labels.address("nowhere"), None);
+		assert_eq!(labels.operand(0x40), "draw");
+		assert_eq!(labels.operand(0x44), "#0044");
+		assert_eq!(labels.describe(0x48), "draw+8");
+		assert_eq!(labels.describe(4), "game_loop");
+		assert_eq!(Symbols::new().describe(4), "#0004");
+		let mut aliases = Symbols::new();
+		for (i, label) in ["a", "b", "c", "d", "e", "f", "g"].iter().enumerate() {
+			aliases.add(if i == 6 { 0 } else { 4 }, label);
+		}
+		assert_eq!(format!("{:?}", aliases), "Symbols { labels: [(0, \"g\"), (4, \"a\"), (4, \"b\"), (4, \"c\"), (4, \"d\"), (4, \"e\"), (4, \"f\")] }");
+		assert_eq!(Symbols::parse("0004\n"), Err("Line 1 isn't ADDRESS LABEL or LABEL = ADDRESS: 0004".to_string()));
+		assert_eq!(disasm::instruction([0x14, 0, 0x04, 0x00], &labels), Some("call game_loop".to_string()));
+		assert_eq!(disasm::instruction([0x22, 0x03, 0x48, 0x00], &labels), Some("ldm r3, #0048".to_string()));
+		
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Ldi, 0x00, 0x00, 0x00);
+		cpu.add_opcode(Opcode::Addi, 0x00, 0x01, 0x00);
+		cpu.add_opcode(Opcode::Vblnk, 0, 0, 0);
+		cpu.add_opcode(Opcode::Jmp, 0x00, 0x04, 0x00);
+		cpu.pc = 0;
+		assert_eq!(disasm::listing(&cpu.memory, 0, 8, &labels),
+			":start\n\
+			 0000  20 00 00 00  ldi r0, #0000\n\
+			 :game_loop\n\
+			 0004  40 00 01 00  addi r0, #0001\n");
+		cpu.symbols = labels;
+		let path = env::temp_dir().join(format!("rustchip16-test-{}-labelled-trace.txt", std::process::id()));
+		cpu.trace = Some(Box::new(File::create(&path).unwrap()));
+		cpu.start_test(2);
+		cpu.trace = None;
+		assert_eq!(fs::read_to_string(&path).unwrap(),
+			":start\n\
+			 0000  20 00 00 00  ldi r0, #0000\n\
+			 :game_loop\n\
+			 0004  40 00 01 00  addi r0, #0001\n");
+		fs::remove_file(&path).unwrap();
+		
+		cpu.pc = 0;
+		let rom = Path::new("unused.c16");
+		let mut console = Console::new();
+		assert_eq!(console.execute(&mut cpu, rom, "break game_loop"), Ok("Breaks at game_loop\n".to_string()));
+		assert_eq!(console.execute(&mut cpu, rom, "break #000C"), Ok("Breaks at #000C\n".to_string()));
+		assert_eq!(console.execute(&mut cpu, rom, "breaks"), Ok("0004 game_loop\n000C game_loop+8\n".to_string()));
+		assert_eq!(console.execute(&mut cpu, rom, "unbreak C"), Ok(String::new()));
+		assert_eq!(console.execute(&mut cpu, rom, "unbreak C"), Err("There is no breakpoint at C".to_string()));
+		assert_eq!(console.execute(&mut cpu, rom, "break nowhere"), Err("nowhere is not a label or an address".to_string()));
+		let (mut input, mut video) = (NullInput::new(), NullVideo::new());
+		cpu.run_frame(&mut input, &mut video, &mut NullAudio);
+		assert_eq!((cpu.break_hit, cpu.pc, cpu.rx[0], cpu.vblank), (Some(4), 4, 0, false));
+		cpu.run_frame(&mut input, &mut video, &mut NullAudio); //The rest of the frame, then the vblank
+		assert_eq!((cpu.break_hit, cpu.pc, cpu.rx[0], cpu.vblank), (None, 8, 1, true));
+		cpu.run_frame(&mut input, &mut video, &mut NullAudio);
+		assert_eq!((cpu.break_hit, cpu.pc, cpu.rx[0]), (Some(4), 4, 1));
+		assert!(panel::lines(&cpu).contains(&"At game_loop".to_string()));
+		
+		cpu.breakpoints.clear();
+		cpu.pc = 0x20;
+		cpu.add_opcode(Opcode::Call, 0x00, 0x04, 0x00);
+		cpu.pc = 0x20;
+		cpu.step().unwrap();
+		assert_eq!(panel::stack_lines(&cpu), vec!["FDF0 #0024 call game_loop".to_string()]);
+		
+		let (c16, _) = c16_file("labelled.c16", &[0x10, 0x00, 0x00, 0x00], 0);
+		let sym = symbols::default_path(&c16);
+		File::create(&sym).unwrap().write_all(b"0000 start\n").unwrap();
+		let options = cli::parse(&args(&format!("headless {}", c16.display()))).unwrap();
+		assert_eq!(cli::load_cpu(&options).unwrap().symbols.name(0), Some("start"));
+		assert_eq!(cli::disassemble(&c16, &cli::symbols(&options).unwrap()).unwrap(), ":start\n0000  10 00 00 00  jmp start\n");
+		let options = cli::parse(&args(&format!("disasm {} --symbols missing.sym", c16.display()))).unwrap();
+		assert!(cli::symbols(&options).is_err());
+		assert_eq!(cli::parse(&args("info x.c16 --symbols x.sym")), Err("--symbols doesn't work with info".to_string()));
+		fs::remove_file(&sym).unwrap();
+		fs::remove_file(&c16).unwrap();
+	}
+	
+	//Polls until the client has a whole packet back, then gives its data
+	fn rsp_reply(server: &mut gdb::Server, cpu: &mut Cpu, client: &mut TcpStream) -> String {
+		let mut reply = Vec::new();
+		for _ in 0..1000 {
+			server.poll(cpu, &mut NullInput::new(), &mut NullVideo::new(), &mut NullAudio).unwrap();
+			let mut chunk = [0u8; 256];
+			if let Ok(count) = client.read(&mut chunk) {
+				reply.extend_from_slice(&chunk[..count]);
+			}
+			let text = String::from_utf8(reply.clone()).unwrap();
+			if let (Some(start), Some(end)) = (text.find('$'), text.find('#')) {
+				if text.len() >= end + 3 {
+					assert_eq!(&text[..start], "+");
+					assert_eq!(gdb::packet(&text[start + 1..end]), &text.as_bytes()[start..end + 3]);
+					return text[start + 1..end].to_string();
+				}
+			}
+		}
+		panic!("No reply, got {:?}", String::from_utf8_lossy(&reply));
+	}
+	
+	fn rsp(server: &mut gdb::Server, cpu: &mut Cpu, client: &mut TcpStream, data: &str) -> String {
+		client.write_all(&gdb::packet(data)).unwrap();
+		rsp_reply(server, cpu, client)
+	}
+	
+	#[test]
+	fn gdb_stub() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Ldi, 0x00, 0x00, 0x00);
+		cpu.add_opcode(Opcode::Addi, 0x00, 0x01, 0x00);
+		cpu.add_opcode(Opcode::Vblnk, 0, 0, 0);
+		cpu.add_opcode(Opcode::Jmp, 0x00, 0x04, 0x00);
+		cpu.pc = 0;
+		cpu.rx[1] = 0x1234;
+		let mut server = gdb::Server::bind(0).unwrap();
+		assert!(server.running());
+		let mut client = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+		client.set_read_timeout(Some(Duration::from_millis(1))).unwrap();
+		let server = &mut server;
+		assert!(rsp(server, &mut cpu, &mut client, "qSupported:multiprocess+").contains("PacketSize"));
+		assert!(server.connected() && !server.running()); //A debugger stops the program
+		assert_eq!(rsp(server, &mut cpu, &mut client, "?"), "S05");
+		let registers = rsp(server, &mut cpu, &mut client, "g");
+		assert_eq!(registers.len(), gdb::REGISTERS * 4);
+		assert_eq!(&registers[..8], "00003412");
+		assert_eq!(&registers[64..], "0000f0fd0000"); //pc, sp and flags
+		assert_eq!(rsp(server, &mut cpu, &mut client, "P1=cdab"), "OK");
+		assert_eq!(cpu.rx[1] as u16, 0xABCD);
+		assert_eq!(rsp(server, &mut cpu, &mut client, "p11"), "f0fd");
+		assert_eq!(rsp(server, &mut cpu, &mut client, "p13"), "E01");
+		assert_eq!(rsp(server, &mut cpu, &mut client, "M3000,2:beef"), "OK");
+		assert_eq!(cpu.memory.peek(0x3001) as u8, 0xEF);
+		assert_eq!(rsp(server, &mut cpu, &mut client, "m3000,3"), "beef00");
+		assert_eq!(rsp(server, &mut cpu, &mut client, "mffff,2"), "E01");
+		assert_eq!(rsp(server, &mut cpu, &mut client, "m1,ffffffff"), "E01");
+		assert_eq!(rsp(server, &mut cpu, &mut client, &format!("G000\u{e9}{}", "0".repeat(gdb::REGISTERS * 4 - 5))), "E01");
+		assert_eq!(rsp(server, &mut cpu, &mut client, "vMustReplyEmpty"), "");
+		
+		assert_eq!(rsp(server, &mut cpu, &mut client, "s"), "S05");
+		assert_eq!(cpu.pc, 4);
+		assert_eq!(rsp(server, &mut cpu, &mut client, "Z0,8,4"), "OK");
+		assert_eq!(cpu.breakpoints, vec![8]);
+		client.write_all(&gdb::packet("c")).unwrap();
+		while !server.running() {
+			server.poll(&mut cpu, &mut NullInput::new(), &mut NullVideo::new(), &mut NullAudio).unwrap();
+		}
+		cpu.run_frame(&mut NullInput::new(), &mut NullVideo::new(), &mut NullAudio);
+		assert_eq!(rsp_reply(server, &mut cpu, &mut client), "S05");
+		assert_eq!((cpu.pc, cpu.rx[0]), (8, 1));
+		client.write_all(&gdb::packet("c")).unwrap();
+		while !server.running() {
+			server.poll(&mut cpu, &mut NullInput::new(), &mut NullVideo::new(), &mut NullAudio).unwrap();
+		}
+		cpu.run_frame(&mut NullInput::new(), &mut NullVideo::new(), &mut NullAudio); //Waits at the breakpoint, it doesn't stop again
+		cpu.run_frame(&mut NullInput::new(), &mut NullVideo::new(), &mut NullAudio);
+		assert_eq!(rsp_reply(server, &mut cpu, &mut client), "S05");
+		assert_eq!((cpu.pc, cpu.rx[0]), (8, 2));
+		client.write_all(&gdb::packet("c")).unwrap();
+		while !server.running() {
+			server.poll(&mut cpu, &mut NullInput::new(), &mut NullVideo::new(), &mut NullAudio).unwrap();
+		}
+		client.write_all(&[0x03]).unwrap(); //Ctrl-C
+		assert_eq!(rsp_reply(server, &mut cpu, &mut client), "S02");
+		assert_eq!(rsp(server, &mut cpu, &mut client, "z0,8,4"), "OK");
+		assert!(cpu.breakpoints.is_empty());
+		cpu.breakpoints.push(0x20); //Not the debugger's, it stays
+		assert_eq!(rsp(server, &mut cpu, &mut client, "Z0,8,4"), "OK");
+		cpu.break_hit = Some(8);
+		assert_eq!(rsp(server, &mut cpu, &mut client, "D"), "OK");
+		assert!(!server.connected() && server.running());
+		assert_eq!((cpu.breakpoints.clone(), cpu.break_hit), (vec![0x20], None));
+		
+		let mut client = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+		client.set_read_timeout(Some(Duration::from_millis(1))).unwrap();
+		assert_eq!(rsp(server, &mut cpu, &mut client, "Z0,4,4"), "OK");
+		drop(client); //Hung up without a word
+		while server.connected() {
+			server.poll(&mut cpu, &mut NullInput::new(), &mut NullVideo::new(), &mut NullAudio).unwrap();
+		}
+		assert_eq!(cpu.breakpoints, vec![0x20]);
+		
+		assert_eq!(cli::parse(&args("headless game.c16 --gdb 1616")).unwrap().gdb, Some(1616));
+		assert_eq!(cli::parse(&args("screenshot game.c16 out.png --gdb 1616")), Err("--gdb doesn't work with screenshot".to_string()));
+	}
+	
+	#[test]
+	fn halt() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.memory.write_byte(0, -1); //FF isn't an opcode
+		cpu.pc = 0;
+		assert_eq!(cpu.step(), Err("FF is not an opcode at #0000".to_string()));
+		assert_eq!(cpu.pc, 0);
+		cpu.pc = 4;
+		cpu.add_opcode(Opcode::Jx, 0x0F, 0x00, 0x00); //Conditions go up to E
+		cpu.pc = 4;
+		assert_eq!(cpu.step(), Err("F is not a condition at #0004".to_string()));
+		assert_eq!(cpu.pc, 4);
+		cpu.pc = 0xFFFC;
+		assert_eq!(cpu.step(), Err("PC got to #FFFC, there is no instruction left in memory".to_string()));
+		
+		cpu.pc = 0;
+		headless::run(&mut cpu, 3);
+		assert_eq!(cpu.halted, Some("FF is not an opcode at #0000".to_string()));
+		assert_eq!(cpu.pc, 0);
+		cpu.soft_reset();
+		assert_eq!(cpu.halted, None);
+		
+		let (rom, _) = c16_file("halt.c16", &[0xFF, 0x00, 0x00, 0x00], 0);
+		let options = cli::parse(&[String::from("headless"), rom.display().to_string()]).unwrap();
+		assert_eq!(cli::execute(&options), Err("The program stopped: FF is not an opcode at #0000".to_string()));
+		fs::remove_file(&rom).unwrap();
+	}
+	
+	#[test]
+	fn gdb_halt() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Nop, 0, 0, 0);
+		cpu.memory.write_byte(4, -1);
+		cpu.pc = 0;
+		let mut server = gdb::Server::bind(0).unwrap();
+		let mut client = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+		client.set_read_timeout(Some(Duration::from_millis(1))).unwrap();
+		let server = &mut server;
+		assert_eq!(rsp(server, &mut cpu, &mut client, "s"), "S05");
+		assert_eq!(rsp(server, &mut cpu, &mut client, "s"), "S04"); //Stopped for good, not at a breakpoint
+		assert_eq!(rsp(server, &mut cpu, &mut client, "?"), "S04");
+		assert_eq!(cpu.pc, 4);
+		client.write_all(&gdb::packet("c")).unwrap();
+		while !server.running() {
+			server.poll(&mut cpu, &mut NullInput::new(), &mut NullVideo::new(), &mut NullAudio).unwrap();
+		}
+		cpu.run_frame(&mut NullInput::new(), &mut NullVideo::new(), &mut NullAudio);
+		assert_eq!(rsp_reply(server, &mut cpu, &mut client), "S04");
+	}
+	
+	#[test]
+	fn profile() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Call, 0x00, 0x10, 0x00);
+		cpu.add_opcode(Opcode::Vblnk, 0, 0, 0);
+		cpu.add_opcode(Opcode::Jmp, 0x00, 0x00, 0x00);
+		cpu.pc = 0x10;
+		cpu.add_opcode(Opcode::Addi, 0x00, 0x01, 0x00);
+		cpu.add_opcode(Opcode::Ret, 0, 0, 0);
+		cpu.pc = 0;
+		cpu.profile = Some(Profile::new());
+		let (mut input, mut video) = (NullInput::new(), NullVideo::new());
+		cpu.run_frame(&mut input, &mut video, &mut NullAudio); //call, addi, ret, then VBLNK waits
+		cpu.run_frame(&mut input, &mut video, &mut NullAudio); //VBLNK, jmp, call, addi, ret
+		let profile = cpu.profile.take().unwrap();
+		assert_eq!((profile.frames, profile.busy(), profile.waits), (2, 8, 2 * 16666 - 8));
+		assert_eq!((profile.counts[0x10], profile.counts[4]), (2, profile.waits + 1));
+		assert_eq!(profile.routines(0), vec![
+			Routine { target: 0, own: 4, total: 8 },
+			Routine { target: 0x10, own: 4, total: 4 },
+		]);
+		let labels = Symbols::parse("0000 start\n0010 add_one\n").unwrap();
+		assert_eq!(profile.folded(&labels, 0), "start 4\nstart;VBLNK 33324\nstart;add_one 4\n");
+		assert_eq!(profile.folded(&Symbols::new(), 0), "#0000 4\n#0000;#0010 4\n#0000;VBLNK 33324\n");
+		let report = profile.report(&labels, 0);
+		assert!(report.starts_with("2 frames, 4 busy instructions per frame of 16666, 16662 waiting on VBLNK\n"));
+		assert!(report.contains("\nadd_one                          2.0    0.0%         2.0\n"));
+		assert!(report.contains("\n0004 start+4                 16662.5  100.0%\n"));
+		
+		let path = env::temp_dir().join(format!("rustchip16-test-{}-profile.folded", std::process::id()));
+		let options = cli::parse(&[String::from("headless"), String::from("game.c16"), String::from("--folded"), path.display().to_string()]).unwrap();
+		cpu.profile = Some(profile);
+		cpu.symbols = labels;
+		cli::write_profile(&cpu, &options).unwrap();
+		assert_eq!(fs::read_to_string(&path).unwrap(), "start 4\nstart;VBLNK 33324\nstart;add_one 4\n");
+		fs::remove_file(&path).unwrap();
+		assert_eq!(cli::parse(&args("disasm game.c16 --profile p.txt")), Err("--profile doesn't work with disasm".to_string()));
+	}
+	
+	#[test]
+	fn coverage() -> () {
+		let mut cpu = Cpu::new_test();
+		cpu.add_opcode(Opcode::Ldm, 0x00, 0x00, 0x01);
+		cpu.add_opcode(Opcode::Jmp, 0x00, 0x04, 0x00);
+		cpu.memory.write_word(0x100, 0x1234);
+		cpu.memory.coverage = Some(Coverage::new());
+		cpu.start_test(3); //ldm, then jmp twice
+		cpu.drw(0, 0, 0x200);
+		cpu.graphics.state.spritew = 1;
+		cpu.graphics.state.spriteh = 2;
+		cpu.drw(0, 0, 0x300);
+		assert_eq!(cpu.memory.peek(0x102), 0); //Tools don't count
+		let coverage = cpu.memory.coverage.take().unwrap();
+		assert!((0..8).all(|dir| coverage.executed(dir)) && !coverage.executed(8));
+		assert!(!coverage.was_read(0) && coverage.was_read(0x100) && coverage.was_read(0x101) && !coverage.was_read(0x102));
+		assert!(coverage.was_read(0x300) && coverage.was_read(0x301) && !coverage.was_read(0x302) && !coverage.was_read(0x200));
+		assert_eq!((coverage.runs(0), coverage.runs(4), coverage.runs(8)), (1, 2, 0));
+		assert_eq!(coverage.summary(0x104), (8, 2, 0x104 - 10));
+		let labels = Symbols::parse("0004 forever\n").unwrap();
+		assert_eq!(coverage.annotate(&cpu.memory, 12, &labels),
+			"; 8 of 12 bytes executed, 0 read as data, 4 not touched\n\
+			 X  0000  22 00 00 01  ldm r0, #0100\n   :forever\n\
+			 X  0004  10 00 04 00  jmp forever\n\
+			 -  0008  00 00 00 00  nop\n");
+		assert!(coverage.annotate(&cpu.memory, 0x104, &labels).ends_with("\nD  0100  34 12 00 00  db #34, #12, #00, #00\n"));
+		let lcov = coverage.lcov(Path::new("game.c16"), 0x104);
+		assert!(lcov.starts_with("TN:\nSF:game.c16\nDA:1,1\nDA:2,2\nDA:3,0\n"));
+		assert!(lcov.ends_with("DA:64,0\nLF:64\nLH:2\nend_of_record\n")); //The data at #0100 isn't a line
+		assert_eq!(cli::parse(&args("run game.c16 --coverage game.info")).unwrap().coverage, Some(PathBuf::from("game.info")));
+		assert_eq!(cli::parse(&args("screenshot game.c16 out.png --coverage c.txt")), Err("--coverage doesn't work with screenshot".to_string()));
+		
+		let (rom, _) = c16_file("coverage.c16", &[0x10, 0x00, 0x00, 0x00], 0); //jmp #0000
+		let path = env::temp_dir().join(format!("rustchip16-test-{}-coverage.info", std::process::id()));
+		let options = cli::parse(&[String::from("headless"), rom.display().to_string(), String::from("--coverage"), path.display().to_string()]).unwrap();
+		let mut cpu = cli::load_cpu(&options).unwrap();
+		headless::run(&mut cpu, 1);
+		cli::write_coverage(&cpu, &options).unwrap(); //The length comes from the ROM, not from memory
+		assert_eq!(fs::read_to_string(&path).unwrap(), format!("TN:\nSF:{}\nDA:1,16666\nLF:1\nLH:1\nend_of_record\n", rom.display()));
+		fs::remove_file(&path).unwrap();
+		fs::remove_file(&rom).unwrap();
+	}
+}
@@ -1,77 +1,112 @@
 use cpu::Cpu;
-use std::fs::{File};
-use std::io::{Read, Seek};
-use std::io;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
 
-pub fn load_bin(file: &mut File, cpu: &mut Cpu) -> () {
-	let mut i: usize = 0;
-	for byte in file.bytes() {
-		let byte = match byte {
-			Ok(number) => number as i8,
-			Err(e) => panic!("{}", e.to_string()),
-		};
-		cpu.memory.write_byte(i, byte);
-		i += 1;
+pub const HEADER_SIZE: usize = 0x10;
+
+//The header of a .c16 file, every field is little endian
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RomHeader {
+	pub version: (u8, u8), //Major and minor version of the spec the program was written for
+	pub rom_size: u32,
+	pub start_address: u16,
+	pub checksum: u32,
+}
+
+pub fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+	let mut bytes = Vec::new();
+	match File::open(path) {
+		Ok(mut file) => if let Err(e) = file.read_to_end(&mut bytes) {
+			return Err(format!("{} {}", e, path.display()));
+		},
+		Err(e) => return Err(format!("{} {}", e, path.display())),
+	}
+	Ok(bytes)
+}
+
+//Picks the loader from the extension
+pub fn load(path: &Path, cpu: &mut Cpu) -> Result<(), String> {
+	match path.extension().and_then(|ext| ext.to_str()) {
+		Some("bin") => load_bin(&read_file(path)?, cpu),
+		Some("c16") => load_c16(&read_file(path)?, cpu),
+		_ => Err(format!("{} is not a .bin or .c16 file", path.display())),
 	}
 }
 
-pub fn load_c16(file: &mut File, cpu: &mut Cpu) -> () {
-	match file.seek(io::SeekFrom::Start(0)){
-			Ok(ok) => ok,
-			Err(e) => panic!("{}", e.to_string())
-	};
-	let mut buf: Vec<u8> = Vec::with_capacity(70000);
-	match file.read_to_end(&mut buf) {
-		Ok(_) => {},
-		Err(e) => panic!("{}", e.to_string()),
+//The header if the file has one and the program that goes in memory, without checking them
+pub fn split_rom<'a>(path: &Path, bytes: &'a [u8]) -> Result<(Option<RomHeader>, &'a [u8]), String> {
+	match path.extension().and_then(|ext| ext.to_str()) {
+		Some("bin") => Ok((None, bytes)),
+		Some("c16") => Ok((Some(read_header(bytes)?), &bytes[HEADER_SIZE..])),
+		_ => Err(format!("{} is not a .bin or .c16 file", path.display())),
+	}
+}
+
+pub fn load_bin(bytes: &[u8], cpu: &mut Cpu) -> Result<(), String> {
+	if bytes.len() > 0x10000 {
+		return Err(format!("The program is {:X} bytes, memory only has 10000", bytes.len()));
+	}
+	for (i, byte) in bytes.iter().enumerate() {
+		cpu.memory.write_byte(i, *byte as i8);
+	}
+	Ok(())
+}
+
+pub fn read_header(buf: &[u8]) -> Result<RomHeader, String> {
+	if buf.len() < HEADER_SIZE {
+		return Err(format!("A .c16 file starts with a {} byte header, this one has {} bytes", HEADER_SIZE, buf.len()));
 	}
-	
 	let magic_number = ((buf[0] as u32) << 24) +
 	                   ((buf[1] as u32) << 16) +
 					   ((buf[2] as u32) << 8) +
 					   (buf[3] as u32);
 	if magic_number != 0x43483136 {
-		panic!("Expected 0x43483136 got {:X} as magic number", magic_number);
+		return Err(format!("Expected 0x43483136 got {:X} as magic number", magic_number));
 	}
-	
+
 	let rom_size = ((buf[9] as u32) << 24) +
 	               ((buf[8] as u32) << 16) +
 				   ((buf[7] as u32) << 8) +
 				   (buf[6] as u32);
 
-	let start_address = ((buf[0xA] as u16) << 8) +
-						(buf[0xB] as u16);
-						
-	cpu.pc = start_address;
-	
+	let start_address = ((buf[0xB] as u16) << 8) +
+						(buf[0xA] as u16);
+
 	let checksum = ((buf[0xF] as u32) << 24) +
 	               ((buf[0xE] as u32) << 16) +
 				   ((buf[0xD] as u32) << 8) +
 				   (buf[0xC] as u32);
 
-	check_rom_size(file, rom_size);
-	crc32_checksum(file, checksum);
+	Ok(RomHeader {
+		version: (buf[5] >> 4, buf[5] & 0xF),
+		rom_size: rom_size,
+		start_address: start_address,
+		checksum: checksum,
+	})
+}
 
-	match file.seek(io::SeekFrom::Start(0x10)){
-		Ok(ok) => ok,
-		Err(e) => panic!("{}", e.to_string())
-	};
-	
-	load_bin(file, cpu);
+pub fn load_c16(buf: &[u8], cpu: &mut Cpu) -> Result<(), String> {
+	let header = read_header(buf)?;
+	let program = &buf[HEADER_SIZE..];
+	check_rom_size(program, header.rom_size)?;
+	if crc32(program) != header.checksum {
+		return Err("The ROM is corrupted.".to_string());
+	}
+	load_bin(program, cpu)?;
+	cpu.start = header.start_address;
+	cpu.pc = header.start_address;
+	Ok(())
 }
 
-fn check_rom_size(file: &mut File, rom_size: u32) -> () {
-	let file_size = match file.metadata() {
-		Ok(file_stat) => file_stat.len() - 0x10,
-		Err(e) => panic!("{}", e.to_string())
-	};
-	if rom_size as u64 != file_size {
-		panic!("Invalid ROM size, header says {:X} and it is {:X}", rom_size, file_size);
+fn check_rom_size(program: &[u8], rom_size: u32) -> Result<(), String> {
+	if rom_size as usize != program.len() {
+		return Err(format!("Invalid ROM size, header says {:X} and it is {:X}", rom_size, program.len()));
 	}
+	Ok(())
 }
 
-fn crc32_checksum(file: &mut File, checksum: u32) -> () {
-	let table: [u32; 256] =[ //Precalculated table
+const CRC32_TABLE: [u32; 256] = [ //Precalculated table
 	0x00000000, 0x77073096, 0xee0e612c, 0x990951ba,
 	0x076dc419, 0x706af48f, 0xe963a535, 0x9e6495a3,
 	0x0edb8832, 0x79dcb8a4, 0xe0d5e91e, 0x97d2d988,
@@ -137,26 +172,10 @@ fn crc32_checksum(file: &mut File, checksum: u32) -> () {
 	0xb3667a2e, 0xc4614ab8, 0x5d681b02, 0x2a6f2b94,
 	0xb40bbe37, 0xc30c8ea1, 0x5a05df1b, 0x2d02ef8d];
 
+pub fn crc32(bytes: &[u8]) -> u32 {
 	let mut crc: u32 = 0xFFFFFFFF;
-
-	match file.seek(io::SeekFrom::Start(0x10)){
-		Ok(ok) => ok,
-		Err(e) => panic!("{}", e.to_string())
-	};
-
-	for byte in file.bytes() {
-		let byte = match byte {
-			Ok(number) => number as u8,
-			Err(e) => panic!("{}", e.to_string()),
-		};
-		crc = (crc >> 8) ^ table[(crc as u8 ^ byte) as usize];
+	for byte in bytes {
+		crc = (crc >> 8) ^ CRC32_TABLE[(crc as u8 ^ *byte) as usize];
 	}
-
-	crc = !crc;
-
-	if checksum != crc {
-		panic!("The ROM is corrupted.");
-	};
-
-	()
-}
\ No newline at end of file
+	!crc
+}
@@ -1,13 +1,15 @@
 extern crate num;
 extern crate rand;
 use self::num::integer::Integer;
-use std::mem;
 use self::rand::Rng;
 use self::Opcode::*;
-use cpu::Cpu;
+use cpu::{Cpu, Sound};
 
-pub fn to_opcode(v: i8) -> Opcode {
-	unsafe { mem::transmute(v) }
+pub fn to_opcode(v: i8) -> Result<Opcode, String> {
+	match Opcode::from_byte(v as u8) {
+		Some(op) => Ok(op),
+		None => Err(format!("{:02X} is not an opcode", v as u8)),
+	}
 }
 
 pub fn join_bytes(ll: i8, hh: i8) -> i16 {
@@ -29,6 +31,7 @@ pub fn separate_byte(byte: i8) -> (i8, i8) {
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Opcode {
 	Nop = 0,
 	Cls,
@@ -115,7 +118,96 @@ pub enum Opcode {
 }
 
 impl Opcode {
-	pub fn execute(&self, cpu: &mut Cpu, byte1: i8, byte2: i8, byte3: i8) {
+	pub fn from_byte(byte: u8) -> Option<Opcode> {
+		match byte {
+			0x00 => Some(Nop),
+			0x01 => Some(Cls),
+			0x02 => Some(Vblnk),
+			0x03 => Some(Bgc),
+			0x04 => Some(Spr),
+			0x05 => Some(Drw),
+			0x06 => Some(Drw2),
+			0x07 => Some(Rnd),
+			0x08 => Some(Flip),
+			0x09 => Some(Snd0),
+			0x0A => Some(Snd1),
+			0x0B => Some(Snd2),
+			0x0C => Some(Snd3),
+			0x0D => Some(Snp),
+			0x0E => Some(Sng),
+			0x10 => Some(Jmp),
+			0x11 => Some(Jmc),
+			0x12 => Some(Jx),
+			0x13 => Some(Jme),
+			0x14 => Some(Call),
+			0x15 => Some(Ret),
+			0x16 => Some(Jmp2),
+			0x17 => Some(Cx),
+			0x18 => Some(Call2),
+			0x20 => Some(Ldi),
+			0x21 => Some(Ldi2),
+			0x22 => Some(Ldm),
+			0x23 => Some(Ldm2),
+			0x24 => Some(Mov),
+			0x30 => Some(Stm),
+			0x31 => Some(Stm2),
+			0x40 => Some(Addi),
+			0x41 => Some(Add),
+			0x42 => Some(Add2),
+			0x50 => Some(Subi),
+			0x51 => Some(Sub),
+			0x52 => Some(Sub2),
+			0x53 => Some(Cmpi),
+			0x54 => Some(Cmp),
+			0x60 => Some(Andi),
+			0x61 => Some(And),
+			0x62 => Some(And2),
+			0x63 => Some(Tsti),
+			0x64 => Some(Tst),
+			0x70 => Some(Ori),
+			0x71 => Some(Or),
+			0x72 => Some(Or2),
+			0x80 => Some(Xori),
+			0x81 => Some(Xor),
+			0x82 => Some(Xor2),
+			0x90 => Some(Muli),
+			0x91 => Some(Mul),
+			0x92 => Some(Mul2),
+			0xA0 => Some(Divi),
+			0xA1 => Some(Div),
+			0xA2 => Some(Div2),
+			0xA3 => Some(Modi),
+			0xA4 => Some(Mod),
+			0xA5 => Some(Mod2),
+			0xA6 => Some(Remi),
+			0xA7 => Some(Rem),
+			0xA8 => Some(Rem2),
+			0xB0 => Some(Shl),
+			0xB1 => Some(Shr),
+			0xB2 => Some(Sar),
+			0xB3 => Some(Shl2),
+			0xB4 => Some(Shr2),
+			0xB5 => Some(Sar2),
+			0xC0 => Some(Push),
+			0xC1 => Some(Pop),
+			0xC2 => Some(Pushall),
+			0xC3 => Some(Popall),
+			0xC4 => Some(Pushf),
+			0xC5 => Some(Popf),
+			0xD0 => Some(Pal),
+			0xD1 => Some(Pal2),
+			0xE0 => Some(Noti),
+			0xE1 => Some(Not),
+			0xE2 => Some(Not2),
+			0xE3 => Some(Negi),
+			0xE4 => Some(Neg),
+			0xE5 => Some(Neg2),
+			_ => None,
+		}
+	}
+
+	//Only a condition that isn't one fails, the program can't go on from there
+	pub fn execute(&self, cpu: &mut Cpu, byte1: i8, byte2: i8, byte3: i8) -> Result<(), String> {
 		match *self {
 			Nop => nop(),
 			Cls => cpu.clear_fg_bg(),
@@ -138,15 +230,15 @@ impl Opcode {
 			},
 			Rnd => rnd(cpu, byte1, join_bytes(byte2, byte3)),
 			Flip => flip(cpu, byte3),
-			Snd0 => nop(),
-			Snd1 => nop(),
-			Snd2 => nop(),
-			Snd3 => nop(),
-			Snp => nop(),
-			Sng => nop(),
+			Snd0 => cpu.sounds.push(Sound::Stop),
+			Snd1 => snd(cpu, 500, join_bytes(byte2, byte3)),
+			Snd2 => snd(cpu, 1000, join_bytes(byte2, byte3)),
+			Snd3 => snd(cpu, 1500, join_bytes(byte2, byte3)),
+			Snp => snp(cpu, byte1, join_bytes(byte2, byte3)),
+			Sng => sng(cpu, byte1, byte2, byte3),
 			Jmp => jmp(cpu, join_bytes(byte2, byte3)),
 			Jmc => jmc(cpu, join_bytes(byte2, byte3)),
-			Jx => jx(cpu, byte1, join_bytes(byte2, byte3)),
+			Jx => jx(cpu, byte1, join_bytes(byte2, byte3))?,
 			Jme => jme(cpu, separate_byte(byte1), join_bytes(byte2, byte3)),
 			Call => call(cpu, join_bytes(byte2, byte3)),
 			Ret => ret(cpu),
@@ -154,7 +246,7 @@ impl Opcode {
 				let rx = cpu.get_rx(byte1);
 				jmp(cpu, rx)
 			},
-			Cx => cx(cpu, byte1, join_bytes(byte2, byte3)),
+			Cx => cx(cpu, byte1, join_bytes(byte2, byte3))?,
 			Call2 => {
 				let rx = cpu.get_rx(byte1);
 				call(cpu, rx)
@@ -254,6 +346,7 @@ impl Opcode {
 				neg(cpu, x, y);
 			},
 		}
+		Ok(())
 	}
 }
 
@@ -271,9 +364,29 @@ fn flip(cpu: &mut Cpu, byte3: i8) -> () {
 	cpu.flip(byte3 > 1, byte3.is_odd())
 }
 
+fn snd(cpu: &mut Cpu, frequency: u16, duration: i16) -> () {
+	cpu.sounds.push(Sound::Tone { frequency: frequency, duration: duration as u16 });
+}
+
+fn snp(cpu: &mut Cpu, rx: i8, duration: i16) -> () {
+	let dir = cpu.get_rx(rx) as u16 as usize;
+	let frequency = cpu.memory.read_word(dir) as u16;
+	snd(cpu, frequency, duration);
+}
+
+fn sng(cpu: &mut Cpu, ad: i8, vt: i8, sr: i8) -> () {
+	let (attack, decay) = separate_byte(ad);
+	let (volume, wave) = separate_byte(vt);
+	let (sustain, release) = separate_byte(sr);
+	cpu.sounds.push(Sound::Generator {
+		attack: attack as u8, decay: decay as u8, sustain: sustain as u8,
+		release: release as u8, volume: volume as u8, wave: wave as u8,
+	});
+}
+
 fn rnd(cpu: &mut Cpu, rx: i8, max_rand: i16) -> () {
-	let mut rng = rand::rng();
-	cpu.set_rx(rx, rng.random_range(0..max_rand as u16) as i16);
+	let value = cpu.rng.random_range(0..=max_rand as u16); //HHLL itself can come up
+	cpu.set_rx(rx, value as i16);
 }
 
 fn jmp(cpu: &mut Cpu, new_dir: i16) -> () {
@@ -286,10 +399,11 @@ fn jmc(cpu: &mut Cpu, new_dir: i16) -> () {
 	}
 }
 
-fn jx(cpu: &mut Cpu, flag_index: i8, new_dir: i16) -> () {
-	if cpu.check_flags(flag_index) {
+fn jx(cpu: &mut Cpu, flag_index: i8, new_dir: i16) -> Result<(), String> {
+	if cpu.check_flags(flag_index)? {
 		jmp(cpu, new_dir);
 	}
+	Ok(())
 }
 
 fn jme(cpu: &mut Cpu, (y, x): (i8, i8), new_dir: i16) -> () {
@@ -300,19 +414,22 @@ fn jme(cpu: &mut Cpu, (y, x): (i8, i8), new_dir: i16) -> () {
 
 fn call(cpu: &mut Cpu, new_dir: i16) -> () {
 	let pc = cpu.pc;
+	cpu.enter_call(pc.wrapping_sub(4), new_dir as u16);
 	cpu.push_stack(pc as i16);
 	cpu.pc = new_dir as u16;
 }
 
 fn ret(cpu: &mut Cpu) -> () {
 	let pc = cpu.pop_stack();
+	cpu.leave_call();
 	cpu.pc = pc as u16;
 }
 
-fn cx(cpu: &mut Cpu, flag_index: i8, new_dir: i16) -> () {
-	if cpu.check_flags(flag_index) {
+fn cx(cpu: &mut Cpu, flag_index: i8, new_dir: i16) -> Result<(), String> {
+	if cpu.check_flags(flag_index)? {
 		call(cpu, new_dir);
 	}
+	Ok(())
 }
 
 fn ldi(cpu: &mut Cpu, rx: i8, value: i16) -> () {
@@ -0,0 +1,107 @@
+use image::{Rgba, RgbaImage};
+use std::io::Write;
+use cpu::{Chip16Graphics, DrawCall};
+use frontend::VideoSink;
+
+const OUTLINE: Rgba<u8> = Rgba([0x00, 0xFF, 0x00, 255]);
+const COLLISION: Rgba<u8> = Rgba([0xFF, 0x00, 0x00, 255]);
+
+//Rectangle in screen pixels, the parts outside the frame are skipped
+pub fn outline(frame: &mut RgbaImage, x: i64, y: i64, width: i64, height: i64, colour: Rgba<u8>) -> () {
+	if width <= 0 || height <= 0 {
+		return;
+	}
+	let (frame_width, frame_height) = (frame.width() as i64, frame.height() as i64);
+	let mut put = |px: i64, py: i64| {
+		if px >= 0 && py >= 0 && px < frame_width && py < frame_height {
+			frame.put_pixel(px as u32, py as u32, colour);
+		}
+	};
+	for px in x..x + width {
+		put(px, y);
+		put(px, y + height - 1);
+	}
+	for py in y..y + height {
+		put(x, py);
+		put(x + width - 1, py);
+	}
+}
+
+//Blends colour over a rectangle by its alpha, for backgrounds that keep the game visible under text
+pub fn fill(frame: &mut RgbaImage, x: i64, y: i64, width: i64, height: i64, colour: Rgba<u8>) -> () {
+	let alpha = colour[3] as u32;
+	let x0 = x.max(0).min(frame.width() as i64) as u32;
+	let y0 = y.max(0).min(frame.height() as i64) as u32;
+	let x1 = (x + width).max(0).min(frame.width() as i64) as u32;
+	let y1 = (y + height).max(0).min(frame.height() as i64) as u32;
+	for py in y0..y1 {
+		for px in x0..x1 {
+			let pixel = frame.get_pixel_mut(px, py);
+			for c in 0..3 {
+				pixel[c] = ((colour[c] as u32 * alpha + pixel[c] as u32 * (255 - alpha)) / 255) as u8;
+			}
+			pixel[3] = 255;
+		}
+	}
+}
+
+//scale is how many frame pixels a Chip16 pixel takes, for filtered frames
+pub fn draw_calls(frame: &mut RgbaImage, calls: &[DrawCall], scale: u32) -> () {
+	let scale = scale as i64;
+	for call in calls.iter().filter(|call| !call.collided) { //Collisions go on top
+		outline(frame, call.x as i64 * scale, call.y as i64 * scale,
+			call.width as i64 * 2 * scale, call.height as i64 * scale, OUTLINE);
+	}
+	for call in calls.iter().filter(|call| call.collided) {
+		outline(frame, call.x as i64 * scale, call.y as i64 * scale,
+			call.width as i64 * 2 * scale, call.height as i64 * scale, COLLISION);
+	}
+}
+
+//One line per DRW, like "120 drw x=10 y=20 spr=#5555 5x10 hflip=0 vflip=1 carry=1"
+pub fn format_draw_calls(frame: u64, calls: &[DrawCall]) -> String {
+	let mut text = String::new();
+	for call in calls {
+		text.push_str(&format!("{} drw x={} y={} spr=#{:04X} {}x{} hflip={} vflip={} carry={}\n",
+			frame, call.x, call.y, call.address, call.width, call.height,
+			call.hflip as u8, call.vflip as u8, call.collided as u8));
+	}
+	text
+}
+
+//Writes the DRW log of every frame, for headless runs
+pub struct DrawLogWriter<W: Write> {
+	out: W,
+	pub frame: u64,
+	pub error: Option<String>, //Set when the log couldn't be written, nothing else is written after it
+}
+
+impl<W: Write> DrawLogWriter<W> {
+	pub fn new(out: W) -> DrawLogWriter<W> {
+		DrawLogWriter { out: out, frame: 0, error: None }
+	}
+
+	pub fn out_ref(&self) -> &W {
+		&self.out
+	}
+
+	//Writes what is buffered, or says why the log stopped
+	pub fn finish(mut self) -> Result<u64, String> {
+		if let Some(e) = self.error.take() {
+			return Err(e);
+		}
+		self.out.flush().map_err(|e| format!("Couldn't write the draw log: {}", e))?;
+		Ok(self.frame)
+	}
+}
+
+impl<W: Write> VideoSink for DrawLogWriter<W> {
+	fn present(&mut self, graphics: &Chip16Graphics) -> () {
+		if let (Some(log), None) = (graphics.draw_log.as_ref(), self.error.as_ref()) {
+			if let Err(e) = self.out.write_all(format_draw_calls(self.frame, log).as_bytes()) {
+				self.error = Some(format!("Couldn't write the draw log: {}", e));
+			}
+		}
+		self.frame += 1;
+	}
+}
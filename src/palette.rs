@@ -0,0 +1,123 @@
+use std::path::Path;
+use loading::read_file;
+
+pub const DEFAULT_PALETTE: [u32; 16] = [0x000000, 0x000000, 0x888888, 0xBF3932, 0xDE7AAE, 0x4C3D21, 0x905F25, 0xE49452,
+	0xEAD979, 0x537A3B, 0xABD54A, 0x252E38, 0x00467F, 0x68ABCC, 0xBCDEE4, 0xFFFFFF];
+
+//Same roles as the default palette, with colours from the Okabe-Ito set
+const COLOURBLIND_PALETTE: [u32; 16] = [0x000000, 0x000000, 0x999999, 0xD55E00, 0xCC79A7, 0x7F4F00, 0xB36B00, 0xE69F00,
+	0xF0E442, 0x009E73, 0x66D1B3, 0x333333, 0x0072B2, 0x56B4E9, 0xB3DDF2, 0xFFFFFF];
+
+const HIGH_CONTRAST_PALETTE: [u32; 16] = [0x000000, 0x000000, 0x808080, 0xFF0000, 0xFF00FF, 0x800000, 0x808000, 0xFF8000,
+	0xFFFF00, 0x008000, 0x00FF00, 0x000080, 0x0000FF, 0x00FFFF, 0xC0C0C0, 0xFFFFFF];
+
+pub fn preset(name: &str) -> Option<[u32; 16]> {
+	match name {
+		"default" => Some(DEFAULT_PALETTE),
+		"colourblind" | "colorblind" => Some(COLOURBLIND_PALETTE),
+		"high-contrast" => Some(HIGH_CONTRAST_PALETTE),
+		"grayscale" | "greyscale" => Some(grayscale(&DEFAULT_PALETTE)),
+		_ => None,
+	}
+}
+
+pub fn grayscale(palette: &[u32; 16]) -> [u32; 16] {
+	let mut gray = [0u32; 16];
+	for (g, colour) in gray.iter_mut().zip(palette.iter()) {
+		let r = (colour >> 16) & 0xFF;
+		let green = (colour >> 8) & 0xFF;
+		let b = colour & 0xFF;
+		let luma = (r * 299 + green * 587 + b * 114) / 1000;
+		*g = (luma << 16) | (luma << 8) | luma;
+	}
+	gray
+}
+
+//Either 16 hex colours (#RRGGBB, 0xRRGGBB or RRGGBB separated by spaces, commas or lines),
+//a JASC-PAL text file or 48 raw RGB bytes
+pub fn parse(bytes: &[u8]) -> Result<[u32; 16], String> {
+	let text = match ::std::str::from_utf8(bytes) {
+		Ok(text) => text,
+		Err(_) => return parse_raw(bytes),
+	};
+	if text.starts_with("JASC-PAL") {
+		return parse_jasc(text);
+	}
+	let mut palette = [0u32; 16];
+	let mut count = 0;
+	for line in text.lines() {
+		let line = match line.find(';') { //Comments
+			Some(i) => &line[..i],
+			None => line,
+		};
+		for word in line.split(|c: char| c.is_whitespace() || c == ',').filter(|w| !w.is_empty()) {
+			let hex = word.trim_start_matches('#').trim_start_matches("0x").trim_start_matches("0X");
+			let colour = match u32::from_str_radix(hex, 16) {
+				Ok(colour) if hex.len() == 6 => colour,
+				_ => {
+					if bytes.len() == 48 {
+						return parse_raw(bytes);
+					}
+					return Err(format!("{} is not a RRGGBB colour", word));
+				},
+			};
+			if count == 16 {
+				return Err("A palette has 16 colours, found more".to_string());
+			}
+			palette[count] = colour;
+			count += 1;
+		}
+	}
+	if count != 16 {
+		return Err(format!("A palette has 16 colours, found {}", count));
+	}
+	Ok(palette)
+}
+
+fn parse_raw(bytes: &[u8]) -> Result<[u32; 16], String> {
+	if bytes.len() != 48 {
+		return Err(format!("A binary palette has 48 bytes, found {}", bytes.len()));
+	}
+	let mut palette = [0u32; 16];
+	for (colour, rgb) in palette.iter_mut().zip(bytes.chunks(3)) {
+		*colour = ((rgb[0] as u32) << 16) | ((rgb[1] as u32) << 8) | rgb[2] as u32;
+	}
+	Ok(palette)
+}
+
+fn parse_jasc(text: &str) -> Result<[u32; 16], String> {
+	let mut lines = text.lines().skip(3); //JASC-PAL, version and colour count
+	let mut palette = [0u32; 16];
+	for colour in palette.iter_mut() {
+		let line = match lines.next() {
+			Some(line) => line,
+			None => return Err("A palette has 16 colours, the file ended early".to_string()),
+		};
+		let rgb: Vec<u32> = line.split_whitespace().filter_map(|c| c.parse().ok()).collect();
+		if rgb.len() != 3 || rgb.iter().any(|c| *c > 255) {
+			return Err(format!("{} is not an R G B colour", line));
+		}
+		*colour = (rgb[0] << 16) | (rgb[1] << 8) | rgb[2];
+	}
+	Ok(palette)
+}
+
+pub fn load(path: &Path) -> Result<[u32; 16], String> {
+	parse(&read_file(path)?)
+}
+
+//A preset name or a palette file
+pub fn from_argument(argument: &str) -> Result<[u32; 16], String> {
+	match preset(argument) {
+		Some(palette) => Ok(palette),
+		None => load(Path::new(argument)),
+	}
+}
+
+pub fn to_hex_list(palette: &[u32; 16]) -> String {
+	let mut text = String::new();
+	for (i, colour) in palette.iter().enumerate() {
+		text.push_str(&format!("#{:06X} ;{:X}\n", colour, i));
+	}
+	text
+}
@@ -0,0 +1,94 @@
+use image::{GenericImage, Rgba, RgbaImage};
+use cpu::Cpu;
+use font::{self, GLYPH_HEIGHT};
+
+//In Chip16 pixels, the panel goes to the right of the 320x240 screen
+pub const WIDTH: u32 = 160;
+pub const STACK_START: u16 = 0xFDF0;
+const ROWS: usize = (240 / (GLYPH_HEIGHT + 1)) as usize;
+
+const BACKGROUND: Rgba<u8> = Rgba([0x18, 0x18, 0x20, 255]);
+const TEXT: Rgba<u8> = Rgba([0xE0, 0xE0, 0xE0, 255]);
+
+//The flags by the letters of the spec, - for the ones that are clear
+pub fn flag_names(flags: i8) -> String {
+	let flags = flags as u8;
+	[(0x02, 'C'), (0x04, 'Z'), (0x40, 'O'), (0x80, 'N')].iter()
+		.map(|&(bit, name)| if flags & bit != 0 { name } else { '-' })
+		.collect()
+}
+
+fn peek_word(cpu: &Cpu, address: u16) -> u16 {
+	let low = cpu.memory.peek(address as usize) as u8 as u16;
+	let high = cpu.memory.peek(address.wrapping_add(1) as usize) as u8 as u16;
+	(high << 8) | low
+}
+
+//The stack from the top down to where it starts, return addresses of calls still running say where they go back to
+pub fn stack_lines(cpu: &Cpu) -> Vec<String> {
+	if cpu.sp < STACK_START {
+		return vec![format!("SP is below #{:04X}", STACK_START)];
+	}
+	let mut lines = Vec::new();
+	let mut address = cpu.sp;
+	while address >= STACK_START + 2 {
+		address -= 2;
+		let word = peek_word(cpu, address);
+		let line = match cpu.calls.iter().rev().find(|frame| frame.slot == address) {
+			Some(frame) => format!("{:04X} #{:04X} call {}", address, word, cpu.symbols.operand(frame.target)),
+			None => format!("{:04X} #{:04X}", address, word),
+		};
+		lines.push(line);
+	}
+	if lines.is_empty() {
+		lines.push("empty".to_string());
+	}
+	lines
+}
+
+//Registers, flags and as much of the stack as fits
+pub fn lines(cpu: &Cpu) -> Vec<String> {
+	let mut lines = vec![
+		format!("PC #{:04X}  SP #{:04X}", cpu.pc, cpu.sp),
+		format!("Flags {}", flag_names(cpu.flags)),
+	];
+	if !cpu.symbols.is_empty() {
+		lines.insert(1, format!("At {}", cpu.symbols.describe(cpu.pc)));
+	}
+	for i in 0..8 {
+		lines.push(format!("R{:X} #{:04X}  R{:X} #{:04X}", i, cpu.rx[i] as u16, i + 8, cpu.rx[i + 8] as u16));
+	}
+	lines.push(String::new());
+	lines.push(format!("Stack, {} calls deep", cpu.calls.len()));
+	let stack = stack_lines(cpu);
+	let room = ROWS - lines.len();
+	if stack.len() > room {
+		let hidden = stack.len() - room + 1;
+		lines.extend(stack.into_iter().take(room - 1));
+		lines.push(format!("{} more", hidden));
+	} else {
+		lines.extend(stack);
+	}
+	lines
+}
+
+//A new image with the panel to the right of frame, scale is the size of a Chip16 pixel in frame
+pub fn beside(frame: &RgbaImage, lines: &[String], scale: u32) -> RgbaImage {
+	let scale = scale.max(1);
+	let mut image = RgbaImage::from_pixel(frame.width() + WIDTH * scale, frame.height(), BACKGROUND);
+	image.copy_from(frame, 0, 0).unwrap();
+	let line_height = (GLYPH_HEIGHT + 1) * scale;
+	for (i, line) in lines.iter().enumerate() {
+		let x = (frame.width() + 3 * scale) as i64;
+		font::draw_text(&mut image, x, (scale + i as u32 * line_height) as i64, line, TEXT, scale);
+	}
+	image
+}
+
+//Two images side by side, the space under the shorter one is black
+pub fn join(left: &RgbaImage, right: &RgbaImage) -> RgbaImage {
+	let mut image = RgbaImage::from_pixel(left.width() + right.width(), left.height().max(right.height()), Rgba([0, 0, 0, 255]));
+	image.copy_from(left, 0, 0).unwrap();
+	image.copy_from(right, left.width(), 0).unwrap();
+	image
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use cpu::{CallFrame, INSTRUCTIONS_PER_FRAME};
+use symbols::Symbols;
+
+const SHOWN: usize = 20; //Addresses listed in the report
+
+//Counts every instruction Cpu::step runs, by address and by the calls it ran in
+pub struct Profile {
+	pub counts: Vec<u64>, //Instructions run at each address, VBLNK waiting included
+	pub frames: u64,
+	pub waits: u64, //Steps VBLNK spent waiting for the frame to end
+	stacks: HashMap<Vec<u16>, u64>, //Busy instructions by the targets of the calls running, outermost first
+	scratch: Vec<u16>, //So looking up a stack doesn't allocate every step
+}
+
+//A subroutine by the address it was called at, the program's start for the code outside any call
+#[derive(Clone, Debug, PartialEq)]
+pub struct Routine {
+	pub target: u16,
+	pub own: u64, //Instructions run in it
+	pub total: u64, //Those and the ones of everything it called
+}
+
+impl Default for Profile {
+	fn default() -> Profile {
+		Profile::new()
+	}
+}
+
+impl Profile {
+	pub fn new() -> Profile {
+		Profile { counts: vec![0; 0x10000], frames: 0, waits: 0, stacks: HashMap::new(), scratch: Vec::new() }
+	}
+
+	//An instruction at pc with the calls running when it started
+	pub fn record(&mut self, pc: u16, waiting: bool, calls: &[CallFrame]) -> () {
+		self.counts[pc as usize] += 1;
+		if waiting {
+			self.waits += 1;
+			return;
+		}
+		self.scratch.clear();
+		self.scratch.extend(calls.iter().map(|frame| frame.target));
+		if let Some(count) = self.stacks.get_mut(&self.scratch[..]) {
+			*count += 1;
+			return;
+		}
+		self.stacks.insert(self.scratch.clone(), 1);
+	}
+
+	pub fn busy(&self) -> u64 {
+		self.stacks.values().sum()
+	}
+
+	//Busiest first, start is where the code outside any call belongs
+	pub fn routines(&self, start: u16) -> Vec<Routine> {
+		let mut routines: HashMap<u16, Routine> = HashMap::new();
+		for (stack, &count) in self.stacks.iter() {
+			let innermost = stack.last().cloned().unwrap_or(start);
+			let mut seen = vec![start];
+			seen.extend(stack.iter().cloned());
+			seen.sort();
+			seen.dedup(); //Recursion counts once towards the total
+			for &target in seen.iter() {
+				let routine = routines.entry(target).or_insert(Routine { target: target, own: 0, total: 0 });
+				routine.total += count;
+				if target == innermost {
+					routine.own += count;
+				}
+			}
+		}
+		let mut routines: Vec<Routine> = routines.into_values().collect();
+		routines.sort_by(|a, b| b.total.cmp(&a.total).then(b.own.cmp(&a.own)).then(a.target.cmp(&b.target)));
+		routines
+	}
+
+	//Where the vblank budget goes, by subroutine and by address
+	pub fn report(&self, symbols: &Symbols, start: u16) -> String {
+		let frames = self.frames.max(1) as f64;
+		let budget = frames * INSTRUCTIONS_PER_FRAME as f64;
+		let mut text = format!("{} frames, {:.0} busy instructions per frame of {}, {:.0} waiting on VBLNK\n\n",
+			self.frames, self.busy() as f64 / frames, INSTRUCTIONS_PER_FRAME, self.waits as f64 / frames);
+		text.push_str("Subroutine               Total/frame  Budget   Own/frame\n");
+		for routine in self.routines(start) {
+			text.push_str(&format!("{:<24} {:>11.1} {:>6.1}% {:>11.1}\n", symbols.operand(routine.target),
+				routine.total as f64 / frames, routine.total as f64 * 100.0 / budget, routine.own as f64 / frames));
+		}
+		text.push_str("\nAddress                  Count/frame  Budget\n");
+		let mut addresses: Vec<usize> = (0..self.counts.len()).filter(|&pc| self.counts[pc] > 0).collect();
+		addresses.sort_by(|&a, &b| self.counts[b].cmp(&self.counts[a]).then(a.cmp(&b)));
+		for &pc in addresses.iter().take(SHOWN) {
+			let name = if symbols.is_empty() {
+				format!("{:04X}", pc)
+			} else {
+				format!("{:04X} {}", pc, symbols.describe(pc as u16))
+			};
+			text.push_str(&format!("{:<24} {:>11.1} {:>6.1}%\n", name,
+				self.counts[pc] as f64 / frames, self.counts[pc] as f64 * 100.0 / budget));
+		}
+		text
+	}
+
+	//A line per call stack like start;game_loop;draw_scores 1234, what flamegraph.pl and speedscope read.
+	//VBLNK waiting shows as a VBLNK frame on its own
+	pub fn folded(&self, symbols: &Symbols, start: u16) -> String {
+		let mut lines: Vec<String> = self.stacks.iter().map(|(stack, count)| {
+			let mut names = vec![symbols.operand(start)];
+			names.extend(stack.iter().map(|&target| symbols.operand(target)));
+			format!("{} {}", names.join(";"), count)
+		}).collect();
+		if self.waits > 0 {
+			lines.push(format!("{};VBLNK {}", symbols.operand(start), self.waits));
+		}
+		lines.sort();
+		lines.iter().map(|line| format!("{}\n", line)).collect()
+	}
+}
@@ -0,0 +1,140 @@
+use image::{self, Delay, Frame, RgbaImage};
+use image::codecs::gif::{GifEncoder, Repeat};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+use cpu::Chip16Graphics;
+use frontend::VideoSink;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordFormat {
+	Gif,
+	PngSequence,
+}
+
+enum Output {
+	Gif(GifEncoder<BufWriter<File>>),
+	Png(PathBuf),
+}
+
+const QUEUED: usize = 120; //Frames waiting for the encoder before capture waits for it
+
+//Encodes on a thread of its own so the emulation doesn't wait for every frame
+pub struct Recorder {
+	sender: Option<SyncSender<RgbaImage>>,
+	worker: Option<JoinHandle<Result<u32, String>>>,
+	pub path: PathBuf,
+	pub frames: u32,
+	pub error: Option<String>, //Set when a frame couldn't be written, nothing else is captured after it
+}
+
+//A name that won't clash with older recordings, like rustchip16-1700000000.gif
+pub fn default_path(format: RecordFormat) -> PathBuf {
+	let seconds = match SystemTime::now().duration_since(UNIX_EPOCH) {
+		Ok(time) => time.as_secs(),
+		Err(_) => 0,
+	};
+	match format {
+		RecordFormat::Gif => PathBuf::from(format!("rustchip16-{}.gif", seconds)),
+		RecordFormat::PngSequence => PathBuf::from(format!("rustchip16-{}", seconds)),
+	}
+}
+
+//A GIF for a .gif file, PNGs in a directory for anything else
+pub fn format_for(path: &Path) -> RecordFormat {
+	match path.extension().and_then(|extension| extension.to_str()) {
+		Some(extension) if extension.eq_ignore_ascii_case("gif") => RecordFormat::Gif,
+		_ => RecordFormat::PngSequence,
+	}
+}
+
+impl Recorder {
+	//A GIF goes to path, a PNG sequence goes inside the path directory
+	pub fn new(path: &Path, format: RecordFormat) -> Result<Recorder, String> {
+		let output = match format {
+			RecordFormat::Gif => {
+				let file = File::create(path).map_err(|e| format!("{} {}", e, path.display()))?;
+				let mut encoder = GifEncoder::new(BufWriter::new(file));
+				encoder.set_repeat(Repeat::Infinite).map_err(|e| e.to_string())?;
+				Output::Gif(encoder)
+			},
+			RecordFormat::PngSequence => {
+				fs::create_dir_all(path).map_err(|e| format!("{} {}", e, path.display()))?;
+				Output::Png(path.to_path_buf())
+			},
+		};
+		let (sender, receiver) = mpsc::sync_channel::<RgbaImage>(QUEUED);
+		let worker = thread::spawn(move || {
+			let mut output = output;
+			let mut written = 0;
+			for frame in receiver {
+				write_frame(&mut output, frame, written)?;
+				written += 1;
+			}
+			drop(output); //The GIF trailer is written when the encoder goes away
+			Ok(written)
+		});
+		Ok(Recorder { sender: Some(sender), worker: Some(worker), path: path.to_path_buf(), frames: 0, error: None })
+	}
+
+	pub fn capture(&mut self, frame: RgbaImage) -> () {
+		if self.error.is_some() {
+			return;
+		}
+		let sent = match self.sender {
+			Some(ref sender) => sender.send(frame).is_ok(),
+			None => false,
+		};
+		if sent {
+			self.frames += 1;
+		} else { //The encoder stopped, finish says why
+			self.error = Some(self.stop().err().unwrap_or_else(|| "The encoder stopped".to_string()));
+		}
+	}
+
+	//Waits for the frames still queued
+	fn stop(&mut self) -> Result<u32, String> {
+		self.sender = None;
+		match self.worker.take() {
+			Some(worker) => worker.join().unwrap_or_else(|_| Err("The encoder crashed".to_string())),
+			None => Ok(self.frames),
+		}
+	}
+
+	pub fn finish(mut self) -> Result<u32, String> {
+		let written = self.stop();
+		match self.error {
+			Some(e) => Err(e),
+			None => written,
+		}
+	}
+}
+
+//How long a GIF frame shows in hundredths of a second. A frame is 5/3 of one, so they go 2, 1, 2, 2, 1, 2...
+//and every 3 frames take 5 like at 60 frames per second
+pub fn gif_delay(frame: u32) -> u32 {
+	let start = |frame: u32| (frame as u64 * 100 + 30) / 60; //Rounded
+	(start(frame + 1) - start(frame)) as u32
+}
+
+fn write_frame(output: &mut Output, frame: RgbaImage, index: u32) -> Result<(), String> {
+	match *output {
+		Output::Gif(ref mut encoder) => {
+			let delay = Delay::from_numer_denom_ms(gif_delay(index) * 10, 1);
+			encoder.encode_frame(Frame::from_parts(frame, 0, 0, delay)).map_err(|e| e.to_string())
+		},
+		Output::Png(ref dir) => {
+			let file = dir.join(format!("frame_{:06}.png", index));
+			frame.save_with_format(&file, image::ImageFormat::Png).map_err(|e| format!("{} {}", e, file.display()))
+		},
+	}
+}
+
+impl VideoSink for Recorder {
+	fn present(&mut self, graphics: &Chip16Graphics) -> () {
+		self.capture(graphics.to_image());
+	}
+}
@@ -0,0 +1,118 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+//Labels of a program by address, from a symbol file next to the ROM or given with --symbols
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbols {
+	labels: Vec<(u16, String)>, //By address
+}
+
+fn hex(text: &str) -> Option<u16> {
+	let digits = text.strip_prefix('#').or_else(|| text.strip_prefix("0x")).unwrap_or(text);
+	u16::from_str_radix(digits, 16).ok()
+}
+
+//The file a ROM's symbols are looked for in when none is given, ROM.sym
+pub fn default_path(rom: &Path) -> PathBuf {
+	rom.with_extension("sym")
+}
+
+impl Default for Symbols {
+	fn default() -> Symbols {
+		Symbols::new()
+	}
+}
+
+impl Symbols {
+	pub fn new() -> Symbols {
+		Symbols { labels: Vec::new() }
+	}
+
+	//Lines are ADDRESS LABEL or LABEL = ADDRESS with the address in hex, ; starts a comment
+	pub fn parse(text: &str) -> Result<Symbols, String> {
+		let mut symbols = Symbols::new();
+		for (number, line) in text.lines().enumerate() {
+			let line = match line.find(';') {
+				Some(i) => &line[..i],
+				None => line,
+			}.trim();
+			if line.is_empty() {
+				continue;
+			}
+			let words: Vec<&str> = line.split(|c: char| c.is_whitespace() || c == '=').filter(|w| !w.is_empty()).collect();
+			let symbol = match (words.len(), line.contains('=')) {
+				(2, false) => hex(words[0]).map(|address| (address, words[1])),
+				(2, true) => hex(words[1]).map(|address| (address, words[0])),
+				_ => None,
+			};
+			match symbol {
+				Some((address, label)) => symbols.add(address, label.trim_start_matches(':')),
+				None => return Err(format!("Line {} isn't ADDRESS LABEL or LABEL = ADDRESS: {}", number + 1, line)),
+			}
+		}
+		Ok(symbols)
+	}
+
+	pub fn load(path: &Path) -> Result<Symbols, String> {
+		let mut text = String::new();
+		File::open(path).and_then(|mut file| file.read_to_string(&mut text)).map_err(|e| format!("{} {}", e, path.display()))?;
+		Symbols::parse(&text).map_err(|e| format!("{} in {}", e, path.display()))
+	}
+
+	//From the file given, else ROM.sym when there is one
+	pub fn for_rom(rom: &Path, file: Option<&Path>) -> Result<Symbols, String> {
+		match file {
+			Some(path) => Symbols::load(path),
+			None if default_path(rom).is_file() => Symbols::load(&default_path(rom)),
+			None => Ok(Symbols::new()),
+		}
+	}
+
+	pub fn add(&mut self, address: u16, label: &str) -> () {
+		let i = self.labels.partition_point(|&(a, _)| a <= address); //After the labels already there, in the order of the file
+		self.labels.insert(i, (address, label.to_string()));
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.labels.is_empty()
+	}
+
+	//The first label of an address
+	pub fn name(&self, address: u16) -> Option<&str> {
+		match self.labels.binary_search_by_key(&address, |&(a, _)| a) {
+			Ok(mut i) => {
+				while i > 0 && self.labels[i - 1].0 == address {
+					i -= 1;
+				}
+				Some(&self.labels[i].1)
+			},
+			Err(_) => None,
+		}
+	}
+
+	pub fn address(&self, label: &str) -> Option<u16> {
+		self.labels.iter().find(|(_, name)| name == label).map(|&(address, _)| address)
+	}
+
+	//A label, or #ADDRESS when there is none
+	pub fn operand(&self, address: u16) -> String {
+		match self.name(address) {
+			Some(name) => name.to_string(),
+			None => format!("#{:04X}", address),
+		}
+	}
+
+	//Where an address is, like draw_scores+8 after the closest label before it
+	pub fn describe(&self, address: u16) -> String {
+		let i = match self.labels.binary_search_by_key(&address, |&(a, _)| a) {
+			Ok(_) => return self.name(address).unwrap().to_string(),
+			Err(i) => i,
+		};
+		if i == 0 {
+			return format!("#{:04X}", address);
+		}
+		let (start, ref name) = self.labels[i - 1];
+		format!("{}+{}", name, address - start)
+	}
+}
@@ -0,0 +1,137 @@
+use image::{imageops, Rgba, RgbaImage};
+use cpu::{Cpu, Memory};
+use opcode::separate_byte;
+use filters;
+use font::{self, GLYPH_HEIGHT};
+
+//In Chip16 pixels, the live viewer goes beside the screen like the memory editor
+pub const WIDTH: u32 = 160;
+const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 1;
+
+const BACKGROUND: Rgba<u8> = Rgba([0x18, 0x10, 0x18, 255]);
+const DIM: Rgba<u8> = Rgba([0x90, 0x80, 0x90, 255]);
+
+//A memory range read as count sprites of width bytes (two pixels each) by height rows, like SPR sets them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteSheet {
+	pub address: u16,
+	pub width: u8,
+	pub height: u8,
+	pub count: u32,
+}
+
+impl SpriteSheet {
+	//ADDRESS:WIDTHxHEIGHT[:COUNT], the address in hex with or without 0x, width in bytes
+	pub fn parse(text: &str) -> Result<SpriteSheet, String> {
+		let parts: Vec<&str> = text.split(':').collect();
+		if parts.len() < 2 || parts.len() > 3 {
+			return Err(format!("{} is not ADDRESS:WIDTHxHEIGHT[:COUNT]", text));
+		}
+		let address = parts[0].trim_start_matches("0x").trim_start_matches('#');
+		let address = u16::from_str_radix(address, 16).map_err(|_| format!("{} is not an address", parts[0]))?;
+		let size: Vec<&str> = parts[1].split('x').collect();
+		if size.len() != 2 {
+			return Err(format!("{} is not WIDTHxHEIGHT", parts[1]));
+		}
+		let width: u8 = size[0].parse().map_err(|_| format!("{} is not a width", size[0]))?;
+		let height: u8 = size[1].parse().map_err(|_| format!("{} is not a height", size[1]))?;
+		let count: u32 = match parts.get(2) {
+			Some(count) => count.parse().map_err(|_| format!("{} is not a count", count))?,
+			None => 1,
+		};
+		if width == 0 || height == 0 || count == 0 {
+			return Err("Sprites need a width, a height and a count".to_string());
+		}
+		if width as u64 * height as u64 * count as u64 > 0x10000 {
+			return Err(format!("{} is more than the 64K of memory", text));
+		}
+		Ok(SpriteSheet { address: address, width: width, height: height, count: count })
+	}
+
+	pub fn sprite_address(&self, index: u32) -> u16 {
+		let size = self.width as u32 * self.height as u32;
+		(self.address as u32).wrapping_add(size * index) as u16
+	}
+
+	//Sprites are laid out left to right with a one pixel gap, transparent pixels show a checkerboard
+	pub fn render(&self, memory: &Memory, palette: &[u32; 16], columns: u32) -> RgbaImage {
+		let columns = columns.max(1).min(self.count);
+		let rows = self.count.div_ceil(columns);
+		let cell_width = self.width as u32 * 2 + 1;
+		let cell_height = self.height as u32 + 1;
+		let mut sheet = RgbaImage::from_pixel(cell_width * columns, cell_height * rows, Rgba([0x40, 0x00, 0x40, 255]));
+		for index in 0..self.count {
+			let left = (index % columns) * cell_width;
+			let top = (index / columns) * cell_height;
+			let address = self.sprite_address(index);
+			for y in 0..self.height as u32 {
+				for x in 0..self.width as u32 {
+					let dir = address.wrapping_add((y * self.width as u32 + x) as u16);
+					let (hh, ll) = separate_byte(memory.peek(dir as usize));
+					for (i, colour) in [hh, ll].iter().enumerate() {
+						let px = left + x * 2 + i as u32;
+						let py = top + y;
+						let pixel = if *colour == 0 {
+							if (px / 2 + py / 2).is_multiple_of(2) { Rgba([0x20, 0x20, 0x20, 255]) } else { Rgba([0x30, 0x30, 0x30, 255]) }
+						} else {
+							let c = palette[*colour as usize];
+							Rgba([(c >> 16) as u8, (c >> 8) as u8, c as u8, 255])
+						};
+						sheet.put_pixel(px, py, pixel);
+					}
+				}
+			}
+		}
+		sheet
+	}
+}
+
+//The window's viewer, sprites from an address at the size SPR set last unless one was chosen
+pub struct SpriteViewer {
+	pub address: u16,
+	pub size: Option<(u8, u8)>, //Width in bytes and height, None follows SPR
+}
+
+impl Default for SpriteViewer {
+	fn default() -> SpriteViewer {
+		SpriteViewer::new()
+	}
+}
+
+impl SpriteViewer {
+	pub fn new() -> SpriteViewer {
+		SpriteViewer { address: 0, size: None }
+	}
+
+	//8x8 until the program sets a size with SPR
+	pub fn size(&self, cpu: &Cpu) -> (u8, u8) {
+		match self.size {
+			Some(size) => size,
+			None if cpu.graphics.state.spritew == 0 || cpu.graphics.state.spriteh == 0 => (8, 8),
+			None => (cpu.graphics.state.spritew, cpu.graphics.state.spriteh),
+		}
+	}
+
+	//By a byte or a row, from the size shown
+	pub fn resize(&mut self, cpu: &Cpu, width: i32, height: i32) -> () {
+		let (w, h) = self.size(cpu);
+		self.size = Some(((w as i32 + width).clamp(1, 255) as u8, (h as i32 + height).clamp(1, 255) as u8));
+	}
+
+	//As many sprites as fit under the title, scale is the size of a Chip16 pixel
+	pub fn render(&self, cpu: &Cpu, scale: u32) -> RgbaImage {
+		let (width, height) = self.size(cpu);
+		let mut pane = RgbaImage::from_pixel(WIDTH, 240, BACKGROUND);
+		let title = format!("#{:04X} {}x{}{}", self.address, width, height, if self.size.is_none() { ", SPR" } else { "" });
+		font::draw_text(&mut pane, 2, 1, &title, DIM, 1);
+		let top = LINE_HEIGHT + 1;
+		let columns = ((WIDTH - 2) / (width as u32 * 2 + 1)).max(1);
+		let rows = ((240 - top) / (height as u32 + 1)).max(1);
+		let count = (columns * rows).min(0x10000 / (width as u32 * height as u32)).max(1);
+		let sheet = SpriteSheet { address: self.address, width: width, height: height, count: count };
+		let sprites = sheet.render(&cpu.memory, cpu.graphics.effective_palette(), columns);
+		let shown = imageops::crop_imm(&sprites, 0, 0, sprites.width().min(WIDTH - 2), sprites.height().min(240 - top)).to_image();
+		imageops::replace(&mut pane, &shown, 2, top as i64);
+		filters::nearest(&pane, scale.max(1))
+	}
+}
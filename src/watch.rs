@@ -0,0 +1,40 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+//What tells two versions of a file apart without reading it
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Stamp {
+	modified: Option<SystemTime>,
+	len: u64,
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+	fs::metadata(path).ok().map(|metadata| Stamp { modified: metadata.modified().ok(), len: metadata.len() })
+}
+
+//Polls a ROM for changes, like an assembler writing a new build
+pub struct Watcher {
+	pub path: PathBuf,
+	loaded: Option<Stamp>, //The version that is running
+	last: Option<Stamp>, //What the previous poll saw
+}
+
+impl Watcher {
+	pub fn new(path: &Path) -> Watcher {
+		let current = stamp(path);
+		Watcher { path: path.to_path_buf(), loaded: current, last: current }
+	}
+
+	//True once the file changed and then looked the same on two polls in a row, so a build still being written
+	//isn't loaded half way. A file that is gone never counts as a change
+	pub fn changed(&mut self) -> bool {
+		let current = stamp(&self.path);
+		let settled = current.is_some() && current == self.last && current != self.loaded;
+		self.last = current;
+		if settled {
+			self.loaded = current;
+		}
+		settled
+	}
+}